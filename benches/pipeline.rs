@@ -0,0 +1,123 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use mp32cdda::conversion::{self, ConversionOptions, ResampleQuality};
+use mp32cdda::progress::ProgressSink;
+
+// Full convert_files runs over a fixed set of synthetic MP3s, so the numbers cover decoding,
+// resampling and writing together. Needs ffmpeg with libmp3lame on the PATH.
+
+const INPUT_FILES: usize = 4;
+const INPUT_SECONDS: u32 = 30;
+
+struct Quiet;
+
+impl ProgressSink for Quiet {}
+
+// Same content for every run: a tone over pink noise, so the encoder and resampler have real work
+fn make_inputs(folder: &Path, sample_rate: u32) -> Vec<PathBuf> {
+    fs::create_dir_all(folder).expect("Failed to create the input folder");
+    (0..INPUT_FILES)
+        .map(|index| {
+            let path = folder.join(format!("input_{}.mp3", index));
+            if path.exists() {
+                return path;
+            }
+            let source = format!(
+                "sine=frequency={}:sample_rate={rate}:duration={secs},anoisesrc=color=pink:amplitude=0.1:seed={}:sample_rate={rate}:duration={secs}",
+                220 * (index + 1),
+                index + 1,
+                rate = sample_rate,
+                secs = INPUT_SECONDS,
+            );
+            let status = Command::new("ffmpeg")
+                .args(["-v", "error", "-y", "-f", "lavfi", "-i", &format!("{},amix=inputs=2", source)])
+                .args(["-ac", "2", "-ar", &sample_rate.to_string(), "-codec:a", "libmp3lame", "-b:a", "256k"])
+                .arg(&path)
+                .status()
+                .expect("Failed to run ffmpeg");
+            assert!(status.success(), "ffmpeg could not generate {:?}", path);
+            path
+        })
+        .collect()
+}
+
+fn convert(inputs: &[PathBuf], options: &ConversionOptions) {
+    let summary = conversion::convert_files(
+        inputs.to_vec(),
+        Arc::new(AtomicBool::new(false)),
+        Arc::new(AtomicBool::new(false)),
+        Arc::new(AtomicBool::new(false)),
+        options,
+        &Quiet,
+    )
+    .expect("Conversion failed");
+    assert_eq!(summary.tracks.len(), inputs.len(), "Not every input was converted");
+}
+
+// Runs the conversion like iter() would and prints how many times faster than realtime it was
+fn bench_batch(c: &mut Criterion, group_name: &str, inputs: &[PathBuf], cases: &[(String, ConversionOptions)]) {
+    let audio = Duration::from_secs(u64::from(INPUT_SECONDS) * inputs.len() as u64);
+    let mut group = c.benchmark_group(group_name);
+    group.sample_size(10);
+    for (name, options) in cases {
+        let mut fastest = Duration::MAX;
+        group.bench_function(BenchmarkId::from_parameter(name), |b| {
+            b.iter_custom(|iters| {
+                let start = Instant::now();
+                for _ in 0..iters {
+                    convert(inputs, options);
+                }
+                let elapsed = start.elapsed();
+                fastest = fastest.min(elapsed / iters.max(1) as u32);
+                elapsed
+            })
+        });
+        if fastest < Duration::MAX {
+            println!("{}/{}: {:.1}x realtime", group_name, name, audio.as_secs_f64() / fastest.as_secs_f64());
+        }
+    }
+    group.finish();
+}
+
+fn cases(output_dir: &Path, qualities: &[ResampleQuality]) -> Vec<(String, ConversionOptions)> {
+    let mut cases = Vec::new();
+    for &quality in qualities {
+        for (jobs, label) in [(Some(1), "sequential"), (None, "parallel")] {
+            cases.push((
+                format!("{}/{}", quality.label(), label),
+                ConversionOptions {
+                    resample_quality: quality,
+                    max_parallel_files: jobs,
+                    output_dir: Some(output_dir.to_path_buf()),
+                    skip_existing: false,
+                    save_cover_art: false,
+                    ..ConversionOptions::default()
+                },
+            ));
+        }
+    }
+    cases
+}
+
+fn pipeline(c: &mut Criterion) {
+    let root = std::env::temp_dir().join("mp32cdda-bench");
+    let output_dir = root.join("output");
+
+    // 48 kHz sources go through the resampler, so every quality preset is measured
+    let resampled = make_inputs(&root.join("48000"), 48000);
+    bench_batch(c, "48000_to_44100", &resampled, &cases(&output_dir, &ResampleQuality::ALL));
+
+    // At 44.1 kHz the resampler has nothing to do and the preset makes no difference
+    let native = make_inputs(&root.join("44100"), 44100);
+    bench_batch(c, "44100_to_44100", &native, &cases(&output_dir, &[ResampleQuality::default()]));
+
+    let _ = fs::remove_dir_all(&output_dir);
+}
+
+criterion_group!(benches, pipeline);
+criterion_main!(benches);
@@ -0,0 +1,52 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use anyhow::{Context, Result};
+
+// Names media libraries look for, by the codec ffmpeg reports for the embedded picture
+const COVER_NAMES: &[(&str, &str)] = &[("mjpeg", "folder.jpg"), ("png", "cover.png")];
+
+pub fn has_cover(folder: &Path) -> bool {
+    COVER_NAMES.iter().any(|(_, name)| folder.join(name).exists())
+}
+
+// Copies the first embedded cover picture of input into folder without re-encoding.
+// Returns None when the file has no picture in a format we can store as is.
+pub fn extract_cover_art(input: &Path, folder: &Path) -> Result<Option<PathBuf>> {
+    let output = Command::new("ffprobe")
+        .args([
+            "-v", "error",
+            "-select_streams", "v",
+            "-show_entries", "stream=codec_name:stream_disposition=attached_pic",
+            "-of", "csv=p=0",
+        ])
+        .arg(input)
+        .output()
+        .context("Failed to run ffprobe")?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!("ffprobe failed: {}", String::from_utf8_lossy(&output.stderr)));
+    }
+
+    // One "codec,attached_pic" line per video stream, in stream order
+    let streams = String::from_utf8_lossy(&output.stdout);
+    let cover = streams.lines().enumerate().find_map(|(position, line)| {
+        let (codec, attached) = line.trim().split_once(',')?;
+        let (_, name) = COVER_NAMES.iter().find(|(known, _)| *known == codec)?;
+        (attached == "1").then_some((position, *name))
+    });
+    let Some((position, name)) = cover else {
+        return Ok(None);
+    };
+
+    let cover_path = folder.join(name);
+    let status = Command::new("ffmpeg")
+        .args(["-v", "error", "-n", "-i"])
+        .arg(input)
+        .args(["-map", &format!("0:v:{}", position), "-c:v", "copy", "-frames:v", "1"])
+        .arg(&cover_path)
+        .status()
+        .context("Failed to run ffmpeg")?;
+    if !status.success() {
+        return Err(anyhow::anyhow!("ffmpeg could not extract the cover of {:?}", input));
+    }
+    Ok(Some(cover_path))
+}
@@ -0,0 +1,140 @@
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::thread;
+use anyhow::{Context, Result};
+
+// A drive that can write CDs
+#[derive(Clone, Debug, PartialEq)]
+pub struct BurnDevice {
+    // What the backend is given to address the drive, e.g. /dev/sr0
+    pub id: String,
+    pub description: String,
+}
+
+// Writes a finished CUE sheet, and the audio files it names, to a disc.
+pub trait BurnBackend: Send + Sync {
+    fn name(&self) -> &str;
+
+    fn list_devices(&self) -> Result<Vec<BurnDevice>>;
+
+    // Output lines of the burner are passed to on_output as they arrive
+    fn burn(&self, cue_sheet: &Path, device: &BurnDevice, on_output: &(dyn Fn(&str) + Sync)) -> Result<()>;
+
+    // Opens the drive's tray. Burning leaves it closed, so callers eject only once a burn succeeded.
+    fn eject(&self, device: &BurnDevice) -> Result<()>;
+}
+
+// Shells out to cdrdao, which reads CUE sheets directly and writes them disc-at-once, so the gaps
+// and INDEX points come out as written.
+pub struct ExternalCueBurner {
+    program: String,
+}
+
+impl Default for ExternalCueBurner {
+    fn default() -> Self {
+        Self {
+            program: "cdrdao".to_string(),
+        }
+    }
+}
+
+impl BurnBackend for ExternalCueBurner {
+    fn name(&self) -> &str {
+        &self.program
+    }
+
+    fn list_devices(&self) -> Result<Vec<BurnDevice>> {
+        let output = Command::new(&self.program)
+            .arg("scanbus")
+            .output()
+            .with_context(|| format!("Failed to run {}; is it installed?", self.program))?;
+        // cdrdao prints the drives on stderr as "/dev/sr0 : VENDOR, MODEL, REVISION"
+        let text = String::from_utf8_lossy(&output.stderr).into_owned() + &String::from_utf8_lossy(&output.stdout);
+        let devices = text
+            .lines()
+            .filter_map(|line| line.split_once(" : "))
+            .map(|(id, description)| BurnDevice {
+                id: id.trim().to_string(),
+                description: description.trim().to_string(),
+            })
+            .filter(|device| !device.id.is_empty() && !device.id.contains(' '))
+            .collect();
+        Ok(devices)
+    }
+
+    fn burn(&self, cue_sheet: &Path, device: &BurnDevice, on_output: &(dyn Fn(&str) + Sync)) -> Result<()> {
+        log_info!("Burning {:?} on {} with {}", cue_sheet, device.id, self.program);
+        let mut child = Command::new(&self.program)
+            .args(["write", "--device", &device.id])
+            .arg(cue_sheet)
+            // cdrdao resolves the CUE's FILE entries relative to the working directory
+            .current_dir(cue_sheet.parent().unwrap_or_else(|| Path::new(".")))
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("Failed to run {}; is it installed?", self.program))?;
+
+        let (stdout, stderr) = (child.stdout.take(), child.stderr.take());
+        thread::scope(|scope| {
+            if let Some(stdout) = stdout {
+                scope.spawn(|| forward_lines(stdout, on_output));
+            }
+            if let Some(stderr) = stderr {
+                scope.spawn(|| forward_lines(stderr, on_output));
+            }
+        });
+
+        let status = child.wait().context("Failed to wait for the burner")?;
+        if !status.success() {
+            return Err(anyhow::anyhow!("{} failed with {}", self.program, status));
+        }
+        log_info!("Finished burning {:?}", cue_sheet);
+        Ok(())
+    }
+
+    // cdrdao only ejects as part of a write, so this goes through the platform's own tool
+    fn eject(&self, device: &BurnDevice) -> Result<()> {
+        let mut command = if cfg!(target_os = "macos") {
+            // drutil addresses drives its own way, so this is the first (usually only) one
+            let mut command = Command::new("drutil");
+            command.args(["tray", "eject"]);
+            command
+        } else if cfg!(unix) {
+            let mut command = Command::new("eject");
+            command.arg(&device.id);
+            command
+        } else {
+            return Err(anyhow::anyhow!("Ejecting isn't supported on this platform; open the tray by hand"));
+        };
+        log_info!("Ejecting {}", device.id);
+        let output = command.output().context("Failed to run the eject command")?;
+        if !output.status.success() {
+            return Err(anyhow::anyhow!("Could not eject {}: {}", device.id, String::from_utf8_lossy(&output.stderr).trim()));
+        }
+        Ok(())
+    }
+}
+
+// Progress is redrawn with carriage returns, so those end a line too
+fn forward_lines(stream: impl Read, on_output: &(dyn Fn(&str) + Sync)) {
+    for chunk in BufReader::new(stream).split(b'\r').map_while(|chunk| chunk.ok()) {
+        for line in String::from_utf8_lossy(&chunk).lines().filter(|line| !line.trim().is_empty()) {
+            log_info!("{}", line);
+            on_output(line);
+        }
+    }
+}
+
+// Sent from a burn thread to the UI
+pub enum BurnEvent {
+    Devices(Result<Vec<BurnDevice>, String>),
+    Output(String),
+    Finished(Result<(), String>),
+}
+
+// Windows and macOS ship their own burning tools with no command line for CD audio, so an
+// external burner has to be installed there too; cdrdao is available for both.
+pub fn default_backend() -> Box<dyn BurnBackend> {
+    Box::new(ExternalCueBurner::default())
+}
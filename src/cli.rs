@@ -0,0 +1,718 @@
+use clap::{Parser, ValueEnum};
+use log::LevelFilter;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use mp32cdda::conversion::{self, ConversionOptions, ExistingOutputPolicy, GainMode, OutputContainer, PlannedFile, ShortTrackPolicy, SplitMode, TimeRange};
+use mp32cdda::disc;
+use mp32cdda::image;
+use mp32cdda::priority::{self, WorkerPriority};
+use mp32cdda::progress::ProgressSink;
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum ExistingArg {
+    /// Replace it
+    Overwrite,
+    /// Keep it and use it as the track
+    Skip,
+    /// Rename it to NAME.~1~, NAME.~2~... first
+    Backup,
+    /// Convert nothing if any output exists
+    Abort,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum PriorityArg {
+    /// Below normal, to keep the rest of the machine responsive
+    Background,
+    Normal,
+    /// Above normal; on Linux this needs privileges
+    High,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum SplitArg {
+    /// At silent gaps of at least --split-gap seconds
+    Silence,
+    /// At the tracks of a .cue file with the same name as the input
+    Cue,
+    /// At the chapters stored in the input, e.g. an audiobook's; without any it stays one track
+    Chapters,
+}
+
+#[derive(Parser, Debug)]
+#[command(name = "mp3-2-cdda", version, about = "Convert audio files to CDDA (44.1 kHz, 16-bit, stereo) WAV")]
+pub struct Cli {
+    /// Audio files (MP3, FLAC, WAV, OGG, Opus, M4A, AAC), folders or M3U/PLS playlists to convert
+    #[arg(short, long, required_unless_present = "stdin", num_args = 1..)]
+    pub input: Vec<PathBuf>,
+
+    /// Folder to write converted files to (defaults to CDDA_Converted next to each input); with --stdin, the output file
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+
+    /// Convert one audio stream read from standard input into the --output file; implies --no-gui
+    #[arg(long, requires = "output", conflicts_with_all = ["input", "image", "cue_file", "split", "start", "end", "dry_run", "preview"])]
+    pub stdin: bool,
+
+    /// Convert in the terminal instead of opening the GUI with the inputs selected
+    #[arg(long)]
+    pub no_gui: bool,
+
+    /// Write a CUE sheet for each output folder
+    #[arg(long)]
+    pub cue: bool,
+
+    /// UPC/EAN barcode to write as the CUE sheet's CATALOG; defaults to the first track's barcode tag
+    #[arg(long, value_name = "CODE")]
+    pub catalog: Option<String>,
+
+    /// Add the tracks to the CUE sheet already in the output folder, numbered on from its last track
+    #[arg(long, requires = "cue")]
+    pub append: bool,
+
+    /// Level every track to -18 LUFS
+    #[arg(long)]
+    pub normalize: bool,
+
+    /// With --normalize, give every track of an album (output folder, or the whole --image) one gain
+    /// from their combined loudness, like ReplayGain album gain
+    #[arg(long, requires = "normalize")]
+    pub album_gain: bool,
+
+    /// Cut leading and trailing silence from every track
+    #[arg(long)]
+    pub trim_silence: bool,
+
+    /// Level in dBFS below which audio counts as silence for --trim-silence
+    #[arg(long, value_name = "DB", default_value_t = conversion::DEFAULT_SILENCE_THRESHOLD_DB, allow_hyphen_values = true)]
+    pub silence_threshold: f64,
+
+    /// Limit peaks so resampling and normalization can't clip
+    #[arg(long)]
+    pub limit: bool,
+
+    /// Remove DC offset with a 10 Hz high-pass
+    #[arg(long)]
+    pub remove_dc: bool,
+
+    /// dB to add to the left channel, e.g. -1.5 to even out a rip that leans left
+    #[arg(long, value_name = "DB", default_value_t = 0.0, allow_hyphen_values = true)]
+    pub left_gain: f64,
+
+    /// dB to add to the right channel
+    #[arg(long, value_name = "DB", default_value_t = 0.0, allow_hyphen_values = true)]
+    pub right_gain: f64,
+
+    /// Ceiling in dBFS for --limit
+    #[arg(long, value_name = "DB", default_value_t = conversion::DEFAULT_PEAK_CEILING_DB, allow_hyphen_values = true)]
+    pub limit_ceiling: f64,
+
+    /// Split each input into several CD tracks
+    #[arg(long, value_enum, value_name = "MODE")]
+    pub split: Option<SplitArg>,
+
+    /// Split the one --input audio file at the tracks of this CUE sheet, into numbered files and a new CUE sheet
+    #[arg(long, value_name = "CUE", conflicts_with_all = ["split", "image", "start", "end"])]
+    pub cue_file: Option<PathBuf>,
+
+    /// Shortest silent gap, in seconds, that --split silence splits at
+    #[arg(long, value_name = "SECONDS", default_value_t = conversion::DEFAULT_SPLIT_GAP_SECONDS)]
+    pub split_gap: f64,
+
+    /// Fade every track in over this many seconds
+    #[arg(long, value_name = "SECONDS", default_value_t = 0.0)]
+    pub fade_in: f64,
+
+    /// Fade every track out over this many seconds
+    #[arg(long, value_name = "SECONDS", default_value_t = 0.0)]
+    pub fade_out: f64,
+
+    /// Don't copy embedded cover art into the output folders
+    #[arg(long)]
+    pub no_cover_art: bool,
+
+    /// List what would be converted, and to which files, without writing anything
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Convert only the first SECONDS of each file into "<name> (preview).wav", to try out settings
+    #[arg(long, value_name = "SECONDS")]
+    pub preview: Option<f64>,
+
+    /// Convert each input from this many seconds in, e.g. to pull one song out of a long recording
+    #[arg(long, value_name = "SECONDS")]
+    pub start: Option<f64>,
+
+    /// Stop converting each input at this many seconds in
+    #[arg(long, value_name = "SECONDS")]
+    pub end: Option<f64>,
+
+    /// Fail inputs that aren't already at the output sample rate instead of resampling them
+    #[arg(long)]
+    pub no_resample: bool,
+
+    /// Fail inputs below the output sample rate instead of upsampling them; higher rates are
+    /// still downsampled
+    #[arg(long, conflicts_with = "no_resample")]
+    pub no_upsample: bool,
+
+    /// Re-encode inputs that are already CD audio WAVs instead of copying them through
+    #[arg(long)]
+    pub reencode: bool,
+
+    /// Write 16-bit 44.1 kHz stereo lossless inputs without any processing and verify the samples match
+    #[arg(long)]
+    pub bit_perfect: bool,
+
+    /// Reconvert files whose output already exists and is valid CD audio
+    #[arg(long)]
+    pub overwrite: bool,
+
+    /// What to do with an output file that already exists and isn't skipped as already converted
+    #[arg(long, value_enum, value_name = "ACTION", default_value = "overwrite")]
+    pub existing: ExistingArg,
+
+    /// Write every input into one raw BIN image at this path, with a CUE sheet next to it
+    #[arg(long, value_name = "BIN")]
+    pub image: Option<PathBuf>,
+
+    /// With --image, CD frames (1/75 s) of silence between tracks; 0 is gapless. Without it each
+    /// gap is chosen from the audio: none where a track runs into the next, otherwise 2 s
+    #[arg(long, value_name = "FRAMES")]
+    pub gap_frames: Option<u64>,
+
+    /// CD frames of silence before track 1: written into the BIN with --image, otherwise a PREGAP
+    /// in the CUE sheet. Red Book's standard is 150 (2 s)
+    #[arg(long, value_name = "FRAMES", default_value_t = mp32cdda::cue::STANDARD_PREGAP_FRAMES)]
+    pub pregap_frames: u64,
+
+    /// With --image, crossfade consecutive tracks over this many seconds
+    #[arg(long, value_name = "SECONDS", default_value_t = 0.0)]
+    pub crossfade: f64,
+
+    /// Pad CD tracks shorter than 4 seconds with silence instead of only reporting them
+    #[arg(long)]
+    pub pad_short: bool,
+
+    /// Recreate the input folder tree under the output folder instead of putting every file in one folder
+    #[arg(long)]
+    pub mirror: bool,
+
+    /// Output file names from tags, e.g. "{track:02} - {artist} - {title}"; placeholders are
+    /// {track}, {artist}, {title}, {album} and {stem} (the input's name)
+    #[arg(long, value_name = "TEMPLATE")]
+    pub name: Option<String>,
+
+    /// Sample rate of the outputs; anything but 44100 is not CD audio, e.g. 48000 for DAT or video
+    #[arg(long, value_name = "HZ", default_value_t = 44100, value_parser = clap::value_parser!(u32).range(8000..=384000))]
+    pub rate: u32,
+
+    /// Write AIFF files instead of WAV, for Mac burning and authoring tools
+    #[arg(long)]
+    pub aiff: bool,
+
+    /// Number of files to convert at once (defaults to the number of CPU cores)
+    #[arg(short, long)]
+    pub jobs: Option<usize>,
+
+    /// OS scheduling priority for the conversion and the ffmpeg processes it starts
+    #[arg(long, value_enum, value_name = "LEVEL", default_value = "normal")]
+    pub priority: PriorityArg,
+
+    /// Log debug details, such as the ffmpeg filter chains
+    #[arg(short, long)]
+    pub verbose: bool,
+
+    /// Also append the log to this file
+    #[arg(long, value_name = "FILE")]
+    pub log_file: Option<PathBuf>,
+
+    /// Print progress as one JSON object per line on stdout, for scripts; the log goes to stderr
+    #[arg(long)]
+    pub json: bool,
+}
+
+impl Cli {
+    pub fn log_level(&self) -> LevelFilter {
+        if self.verbose {
+            LevelFilter::Debug
+        } else {
+            LevelFilter::Info
+        }
+    }
+
+    pub fn conversion_options(&self) -> ConversionOptions {
+        ConversionOptions {
+            write_cue: self.cue,
+            cue_catalog: self.catalog.clone(),
+            append_to_project: self.append,
+            output_dir: self.output.clone(),
+            normalize: self.normalize,
+            gain_mode: if self.album_gain { GainMode::Album } else { GainMode::Track },
+            max_parallel_files: self.jobs,
+            priority: match self.priority {
+                PriorityArg::Background => WorkerPriority::Background,
+                PriorityArg::Normal => WorkerPriority::Normal,
+                PriorityArg::High => WorkerPriority::High,
+            },
+            trim_silence: self.trim_silence,
+            silence_threshold_db: self.silence_threshold,
+            limit_peaks: self.limit,
+            remove_dc: self.remove_dc,
+            left_gain_db: self.left_gain,
+            right_gain_db: self.right_gain,
+            peak_ceiling_db: self.limit_ceiling,
+            split_mode: match self.split {
+                None => SplitMode::Off,
+                Some(SplitArg::Silence) => SplitMode::Silence,
+                Some(SplitArg::Cue) => SplitMode::CueSheet,
+                Some(SplitArg::Chapters) => SplitMode::Chapters,
+            },
+            split_min_gap_seconds: self.split_gap,
+            fade_in_seconds: self.fade_in,
+            fade_out_seconds: self.fade_out,
+            gap_frames: self.gap_frames.unwrap_or(0),
+            detect_gaps: self.gap_frames.is_none(),
+            first_track_pregap_frames: self.pregap_frames,
+            crossfade_seconds: self.crossfade,
+            short_tracks: if self.pad_short { ShortTrackPolicy::Pad } else { ShortTrackPolicy::Flag },
+            save_cover_art: !self.no_cover_art,
+            dry_run: self.dry_run,
+            preview_seconds: self.preview,
+            time_range: (self.start.is_some() || self.end.is_some()).then(|| TimeRange {
+                start: Duration::from_secs_f64(self.start.unwrap_or(0.0).max(0.0)),
+                end: self.end.map(|end| Duration::from_secs_f64(end.max(0.0))),
+            }),
+            skip_existing: !self.overwrite,
+            existing_outputs: match self.existing {
+                ExistingArg::Overwrite => ExistingOutputPolicy::Overwrite,
+                ExistingArg::Skip => ExistingOutputPolicy::Skip,
+                ExistingArg::Backup => ExistingOutputPolicy::NumberedBackup,
+                ExistingArg::Abort => ExistingOutputPolicy::Abort,
+            },
+            force_reencode: self.reencode,
+            bit_perfect: self.bit_perfect,
+            no_resample: self.no_resample,
+            no_upsample: self.no_upsample,
+            mirror_folders: self.mirror,
+            name_template: self.name.clone(),
+            target_rate: self.rate,
+            output_container: if self.aiff { OutputContainer::Aiff } else { OutputContainer::Wav },
+            ..ConversionOptions::default()
+        }
+    }
+}
+
+// How often a scan of the inputs reports its count; one line per file would bury the rest
+const SCAN_REPORT_FILES: usize = 100;
+
+// One line per finished file, for following a batch in the terminal.
+struct ConsoleProgress {
+    total_files: AtomicUsize,
+    pause_flag: Arc<AtomicBool>,
+    // Held while waiting for Enter, so workers that fill the disk together ask one at a time
+    prompt: Mutex<()>,
+}
+
+impl ProgressSink for ConsoleProgress {
+    fn on_scanning(&self, files_found: usize) {
+        if files_found.is_multiple_of(SCAN_REPORT_FILES) {
+            println!("Scanning: {} files found", files_found);
+        }
+    }
+
+    fn on_file_start(&self, _index: usize, total_files: usize, _input: &Path) {
+        self.total_files.store(total_files, Ordering::Relaxed);
+    }
+
+    fn on_file_done(&self, index: usize, input: &Path, succeeded: bool) {
+        println!(
+            "[{}/{}] {} {}",
+            index + 1,
+            self.total_files.load(Ordering::Relaxed),
+            if succeeded { "converted" } else { "FAILED" },
+            input.display()
+        );
+    }
+
+    fn on_disk_full(&self, output: &Path) {
+        wait_for_resume(&self.prompt, &self.pause_flag, output);
+    }
+
+    fn on_planned(&self, file: &PlannedFile) {
+        println!(
+            "{:<40} {:>7} {:>6} Hz {:>2} ch {:<10} {}",
+            file.input.file_name().unwrap_or_default().to_string_lossy(),
+            file.duration.map_or_else(|| "?".to_string(), disc::format_minutes),
+            file.sample_rate,
+            file.channels,
+            if file.already_converted {
+                "exists"
+            } else if file.needs_resample() {
+                "resample"
+            } else {
+                ""
+            },
+            file.output_path.display()
+        );
+    }
+}
+
+// A terminal run has no Resume button, so the user confirms on stdin instead
+fn wait_for_resume(prompt: &Mutex<()>, pause_flag: &AtomicBool, output: &Path) {
+    let _prompt = prompt.lock().unwrap();
+    if !pause_flag.load(Ordering::Relaxed) {
+        return; // Another worker's prompt already resumed the batch
+    }
+    eprintln!("Disk full while writing {}. Free some space and press Enter to resume.", output.display());
+    let _ = std::io::stdin().read_line(&mut String::new());
+    pause_flag.store(false, Ordering::Relaxed);
+}
+
+// One line of --json output. Field names are part of the interface scripts rely on, so only add to them.
+#[derive(Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum JsonEvent<'a> {
+    FileStart {
+        index: usize,
+        total_files: usize,
+        path: &'a Path,
+    },
+    Progress {
+        index: usize,
+        path: &'a Path,
+        percent: u32,
+    },
+    Scanning {
+        files_found: usize,
+    },
+    FileDone {
+        index: usize,
+        path: &'a Path,
+        succeeded: bool,
+        elapsed_seconds: f64,
+    },
+    Planned {
+        path: &'a Path,
+        output: &'a Path,
+        duration_seconds: Option<f64>,
+        sample_rate: u32,
+        channels: u16,
+        already_converted: bool,
+    },
+    DiskFull {
+        output: &'a Path,
+    },
+    Error {
+        path: Option<&'a Path>,
+        message: &'a str,
+    },
+    Clipping {
+        path: &'a Path,
+        peak_dbfs: f64,
+        clipped_runs: u64,
+    },
+    NearMono {
+        path: &'a Path,
+        correlation_percent: f64,
+    },
+    TooShort {
+        path: &'a Path,
+        duration_seconds: f64,
+    },
+    // Every track that wasn't at the output rate gets one
+    Resampled {
+        path: &'a Path,
+        source_rate: u32,
+        output_rate: u32,
+        direction: &'static str,
+    },
+    // Every track with a measured level gets one, compressed or not
+    DynamicRange {
+        path: &'a Path,
+        dynamic_range_db: f64,
+        compressed: bool,
+    },
+    BatchDone {
+        converted: usize,
+        skipped: usize,
+        failed: usize,
+        duration_seconds: f64,
+    },
+}
+
+impl JsonEvent<'_> {
+    fn emit(&self) {
+        match serde_json::to_string(self) {
+            Ok(line) => println!("{}", line),
+            Err(e) => log_error!("Failed to encode a progress event: {}", e),
+        }
+    }
+}
+
+struct JsonFile {
+    path: PathBuf,
+    started: Instant,
+    percent: Option<u32>,
+}
+
+// Newline-delimited JSON on stdout for --json, one event per line
+struct JsonProgress {
+    files: Mutex<HashMap<usize, JsonFile>>,
+    pause_flag: Arc<AtomicBool>,
+    prompt: Mutex<()>,
+}
+
+impl ProgressSink for JsonProgress {
+    fn on_scanning(&self, files_found: usize) {
+        if files_found.is_multiple_of(SCAN_REPORT_FILES) {
+            JsonEvent::Scanning { files_found }.emit();
+        }
+    }
+
+    fn on_file_start(&self, index: usize, total_files: usize, input: &Path) {
+        let file = JsonFile {
+            path: input.to_path_buf(),
+            started: Instant::now(),
+            percent: None,
+        };
+        self.files.lock().unwrap().insert(index, file);
+        JsonEvent::FileStart { index, total_files, path: input }.emit();
+    }
+
+    // Only whole percents that changed, so a fast file doesn't flood the reader
+    fn on_progress(&self, index: usize, done: u64, total: u64) {
+        if total == 0 {
+            return;
+        }
+        let mut files = self.files.lock().unwrap();
+        let Some(file) = files.get_mut(&index) else {
+            return;
+        };
+        let percent = (done.min(total) * 100 / total) as u32;
+        if file.percent != Some(percent) {
+            file.percent = Some(percent);
+            JsonEvent::Progress { index, path: &file.path, percent }.emit();
+        }
+    }
+
+    fn on_file_done(&self, index: usize, input: &Path, succeeded: bool) {
+        let started = self.files.lock().unwrap().remove(&index).map(|file| file.started);
+        JsonEvent::FileDone {
+            index,
+            path: input,
+            succeeded,
+            elapsed_seconds: started.map_or(0.0, |started| started.elapsed().as_secs_f64()),
+        }
+        .emit();
+    }
+
+    fn on_disk_full(&self, output: &Path) {
+        JsonEvent::DiskFull { output }.emit();
+        wait_for_resume(&self.prompt, &self.pause_flag, output);
+    }
+
+    fn on_planned(&self, file: &PlannedFile) {
+        JsonEvent::Planned {
+            path: &file.input,
+            output: &file.output_path,
+            duration_seconds: file.duration,
+            sample_rate: file.sample_rate,
+            channels: file.channels,
+            already_converted: file.already_converted,
+        }
+        .emit();
+    }
+}
+
+// Runs a headless conversion and returns the process exit code.
+pub fn run(cli: Cli) -> i32 {
+    let options = cli.conversion_options();
+    // The whole run is the conversion, so the main thread can take the priority itself
+    priority::set_current_thread(options.priority);
+    let cancel_flag = Arc::new(AtomicBool::new(false));
+    // Only a full disk pauses a terminal run; Ctrl+Z already suspends the whole process group
+    let pause_flag = Arc::new(AtomicBool::new(false));
+    // Nothing in a terminal run asks to stop after the current file; Ctrl+C ends the process
+    let stop_flag = Arc::new(AtomicBool::new(false));
+    let progress: Box<dyn ProgressSink> = if cli.json {
+        Box::new(JsonProgress {
+            files: Mutex::new(HashMap::new()),
+            pause_flag: Arc::clone(&pause_flag),
+            prompt: Mutex::new(()),
+        })
+    } else {
+        Box::new(ConsoleProgress {
+            total_files: AtomicUsize::new(0),
+            pause_flag: Arc::clone(&pause_flag),
+            prompt: Mutex::new(()),
+        })
+    };
+
+    if cli.stdin {
+        return run_stdin(&cli, &options, cancel_flag, pause_flag);
+    }
+    let result = match (&cli.image, &cli.cue_file) {
+        (_, Some(cue_sheet)) => match cli.input.as_slice() {
+            [audio] => {
+                let output = conversion::output_folder_for(audio, &options);
+                conversion::convert_with_cue(audio, cue_sheet, &output, cancel_flag, pause_flag, stop_flag, &options, progress.as_ref())
+            }
+            _ => Err(anyhow::anyhow!("--cue-file takes exactly one --input, the audio file the sheet describes")),
+        },
+        (Some(image_path), None) if !options.dry_run && options.preview_seconds.is_none() => {
+            image::convert_to_image(cli.input, image_path, cancel_flag, pause_flag, stop_flag, &options, progress.as_ref())
+        }
+        _ => conversion::convert_files(cli.input, cancel_flag, pause_flag, stop_flag, &options, progress.as_ref()),
+    };
+    if cli.json {
+        return report_json(result);
+    }
+    match result {
+        Ok(summary) => {
+            println!(
+                "{} converted, {} already converted, {} failed ({})",
+                summary.converted,
+                summary.skipped,
+                summary.failed(),
+                disc::format_minutes(summary.total_duration_seconds())
+            );
+            for (input, error) in &summary.failures {
+                eprintln!("  {}: {}", input.display(), error);
+            }
+            for track in summary.resampled_tracks() {
+                if let Some(resampling) = track.resampling() {
+                    println!("  {}: {} Hz, {} to {} Hz", track.input.display(), track.source_rate, resampling.label(), track.sample_rate);
+                }
+            }
+            for track in summary.flagged_tracks() {
+                println!("  check: {}: {}", track.input.display(), track.problems().join("; "));
+            }
+            if summary.failed() > 0 {
+                1
+            } else {
+                0
+            }
+        }
+        Err(e) => {
+            eprintln!("Conversion failed: {:?}", e);
+            1
+        }
+    }
+}
+
+// --stdin: no names to plan, so there is no batch, just the one output file
+fn run_stdin(cli: &Cli, options: &ConversionOptions, cancel_flag: Arc<AtomicBool>, pause_flag: Arc<AtomicBool>) -> i32 {
+    let output = cli.output.as_deref().expect("clap requires --output with --stdin");
+    let options = ConversionOptions {
+        output_dir: None,
+        ..options.clone()
+    };
+    match conversion::convert_reader(Box::new(std::io::stdin()), output, cancel_flag, pause_flag, &options) {
+        // No frames means the output existed and --existing skip kept it
+        Ok(frames) => {
+            let seconds = frames as f64 / f64::from(options.target_rate);
+            if cli.json {
+                JsonEvent::BatchDone {
+                    converted: usize::from(frames > 0),
+                    skipped: usize::from(frames == 0),
+                    failed: 0,
+                    duration_seconds: seconds,
+                }
+                .emit();
+            } else if frames > 0 {
+                println!("Converted standard input to {} ({})", output.display(), disc::format_minutes(seconds));
+            } else {
+                println!("{} already exists; nothing was converted", output.display());
+            }
+            0
+        }
+        Err(e) => {
+            if cli.json {
+                JsonEvent::Error { path: None, message: &format!("{:#}", e) }.emit();
+            } else {
+                eprintln!("Conversion failed: {:?}", e);
+            }
+            1
+        }
+    }
+}
+
+fn report_json(result: anyhow::Result<conversion::ConversionSummary>) -> i32 {
+    match result {
+        Ok(summary) => {
+            for (input, error) in &summary.failures {
+                JsonEvent::Error { path: Some(input), message: error }.emit();
+            }
+            for track in &summary.tracks {
+                if let Some(resampling) = track.resampling() {
+                    JsonEvent::Resampled {
+                        path: &track.input,
+                        source_rate: track.source_rate,
+                        output_rate: track.sample_rate,
+                        direction: resampling.label(),
+                    }
+                    .emit();
+                }
+                let Some(signal) = track.signal else {
+                    continue;
+                };
+                if let Some(dynamic_range_db) = signal.dynamic_range_db {
+                    JsonEvent::DynamicRange {
+                        path: &track.input,
+                        dynamic_range_db,
+                        compressed: signal.is_compressed(),
+                    }
+                    .emit();
+                }
+            }
+            for track in summary.flagged_tracks() {
+                if track.is_too_short_for_cd() {
+                    JsonEvent::TooShort {
+                        path: &track.input,
+                        duration_seconds: track.duration_seconds(),
+                    }
+                    .emit();
+                }
+                let Some(signal) = track.signal else {
+                    continue;
+                };
+                if signal.clips() {
+                    JsonEvent::Clipping {
+                        path: &track.input,
+                        peak_dbfs: signal.peak_dbfs(),
+                        clipped_runs: signal.clipped_runs,
+                    }
+                    .emit();
+                }
+                if let Some(correlation) = signal.stereo_correlation.filter(|_| signal.near_mono()) {
+                    JsonEvent::NearMono {
+                        path: &track.input,
+                        correlation_percent: correlation * 100.0,
+                    }
+                    .emit();
+                }
+            }
+            JsonEvent::BatchDone {
+                converted: summary.converted,
+                skipped: summary.skipped,
+                failed: summary.failed(),
+                duration_seconds: summary.total_duration_seconds(),
+            }
+            .emit();
+            if summary.failed() > 0 {
+                1
+            } else {
+                0
+            }
+        }
+        Err(e) => {
+            JsonEvent::Error { path: None, message: &format!("{:#}", e) }.emit();
+            1
+        }
+    }
+}
@@ -1,108 +1,2187 @@
 // No explicit macro import needed; rely on #[macro_use] in main.rs
 
+use std::collections::HashMap;
+use std::ffi::{OsStr, OsString};
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
 use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use walkdir::WalkDir;
 use std::io::Read;
+use crate::art;
+use crate::cue::{self, CueTrack};
+use crate::logging::{self, TrackReport};
+use crate::loudness::{self, Loudness};
+use crate::metadata::{self, TrackInfo};
+use crate::naming::NameTemplate;
+use crate::playlist;
+use crate::priority::WorkerPriority;
+use crate::progress::ProgressSink;
+use crate::silence;
+use crate::split::{self, Segment};
+use crate::verify;
+use crate::wav::{self, SignalStats};
 
-pub fn convert_files(paths: Vec<PathBuf>, cancel_flag: Arc<Mutex<bool>>) -> Result<()> {
-    if paths.is_empty() {
+const CUE_SHEET_NAME: &str = "disc.cue";
+const OUTPUT_FOLDER_NAME: &str = "CDDA_Converted";
+// One CD frame (1/75 s, one 2352-byte sector) of 44.1 kHz audio
+pub(crate) const SAMPLES_PER_CD_FRAME: u64 = 588;
+
+// Input formats ffmpeg decodes that we accept from the file dialog and folder scans
+pub const SUPPORTED_EXTENSIONS: &[&str] = &["mp3", "flac", "wav", "ogg", "opus", "m4a", "aac"];
+
+pub fn is_supported_audio(path: &Path) -> bool {
+    path.extension()
+        .and_then(|s| s.to_str())
+        .is_some_and(|ext| SUPPORTED_EXTENSIONS.iter().any(|supported| ext.eq_ignore_ascii_case(supported)))
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DitherMode {
+    None,
+    // Triangular (TPDF) dither, flat spectrum
+    #[default]
+    Tpdf,
+    // TPDF with the noise pushed up towards high frequencies
+    NoiseShaped,
+}
+
+impl DitherMode {
+    pub const ALL: [DitherMode; 3] = [DitherMode::None, DitherMode::Tpdf, DitherMode::NoiseShaped];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            DitherMode::None => "None",
+            DitherMode::Tpdf => "TPDF",
+            DitherMode::NoiseShaped => "TPDF, noise shaped",
+        }
+    }
+
+    pub(crate) fn ffmpeg_method(self) -> &'static str {
+        match self {
+            DitherMode::None => "none",
+            DitherMode::Tpdf => "triangular",
+            DitherMode::NoiseShaped => "triangular_hp",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OutputFormat {
+    // Red Book audio: 16-bit, dithered, padded to whole sectors
+    #[default]
+    Cdda16,
+    // High-resolution 44.1 kHz WAVs for further work in a DAW; not burnable as is
+    Pcm24,
+    Float32,
+}
+
+impl OutputFormat {
+    pub const ALL: [OutputFormat; 3] = [OutputFormat::Cdda16, OutputFormat::Pcm24, OutputFormat::Float32];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            OutputFormat::Cdda16 => "CD audio (16-bit)",
+            OutputFormat::Pcm24 => "24-bit PCM",
+            OutputFormat::Float32 => "32-bit float",
+        }
+    }
+
+    pub fn is_cdda(self) -> bool {
+        self == OutputFormat::Cdda16
+    }
+
+    fn codec(self, container: OutputContainer) -> &'static str {
+        match (self, container) {
+            (OutputFormat::Cdda16, OutputContainer::Wav) => "pcm_s16le",
+            (OutputFormat::Pcm24, OutputContainer::Wav) => "pcm_s24le",
+            (OutputFormat::Float32, OutputContainer::Wav) => "pcm_f32le",
+            (OutputFormat::Cdda16, OutputContainer::Aiff) => "pcm_s16be",
+            (OutputFormat::Pcm24, OutputContainer::Aiff) => "pcm_s24be",
+            // ffmpeg writes this as AIFF-C
+            (OutputFormat::Float32, OutputContainer::Aiff) => "pcm_f32be",
+        }
+    }
+
+    // Sample format aresample hands to the encoder; pcm_s24le takes 32-bit input
+    fn sample_format(self) -> &'static str {
+        match self {
+            OutputFormat::Cdda16 => "s16",
+            OutputFormat::Pcm24 => "s32",
+            OutputFormat::Float32 => "flt",
+        }
+    }
+}
+
+// File type of each output track; the samples are the same in both
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OutputContainer {
+    #[default]
+    Wav,
+    // Big-endian, preferred by Mac burning and authoring tools
+    Aiff,
+}
+
+impl OutputContainer {
+    pub const ALL: [OutputContainer; 2] = [OutputContainer::Wav, OutputContainer::Aiff];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            OutputContainer::Wav => "WAV",
+            OutputContainer::Aiff => "AIFF",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            OutputContainer::Wav => "wav",
+            OutputContainer::Aiff => "aiff",
+        }
+    }
+
+    // Named explicitly, since the file being written has a .tmp extension
+    fn ffmpeg_format(self) -> &'static str {
+        match self {
+            OutputContainer::Wav => "wav",
+            OutputContainer::Aiff => "aiff",
+        }
+    }
+}
+
+// Channels of the non-CD formats; CD audio is always stereo
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ChannelMode {
+    #[default]
+    ForceStereo,
+    // Mono sources stay mono, e.g. for spoken word at half the size
+    PreserveMono,
+    DownmixToMono,
+}
+
+impl ChannelMode {
+    pub const ALL: [ChannelMode; 3] = [ChannelMode::ForceStereo, ChannelMode::PreserveMono, ChannelMode::DownmixToMono];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ChannelMode::ForceStereo => "Stereo",
+            ChannelMode::PreserveMono => "Keep mono as mono",
+            ChannelMode::DownmixToMono => "Mono",
+        }
+    }
+
+    fn output_channels(self, source_channels: u16) -> u16 {
+        match self {
+            ChannelMode::ForceStereo => 2,
+            ChannelMode::PreserveMono if source_channels == 1 => 1,
+            ChannelMode::PreserveMono => 2,
+            ChannelMode::DownmixToMono => 1,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ResampleQuality {
+    // Short filter with linear interpolation between phases
+    Fast,
+    // ffmpeg's own defaults
+    #[default]
+    Balanced,
+    // Long filter and finer phase table, several times slower than Balanced
+    Best,
+}
+
+impl ResampleQuality {
+    pub const ALL: [ResampleQuality; 3] = [ResampleQuality::Fast, ResampleQuality::Balanced, ResampleQuality::Best];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ResampleQuality::Fast => "Fast",
+            ResampleQuality::Balanced => "Balanced",
+            ResampleQuality::Best => "Best",
+        }
+    }
+
+    pub(crate) fn swr_options(self, source_rate: u32, target_rate: u32) -> String {
+        let filter = match self {
+            ResampleQuality::Fast => "filter_size=16:phase_shift=8:linear_interp=1",
+            ResampleQuality::Balanced => "filter_size=32:phase_shift=10",
+            ResampleQuality::Best => "filter_size=128:phase_shift=14",
+        };
+        match downsample_cutoff(source_rate, target_rate) {
+            Some(cutoff) => format!("{}:cutoff={:.3}", filter, cutoff),
+            None => filter.to_string(),
+        }
+    }
+}
+
+// The resampler's low-pass sits at a fixed fraction of the output Nyquist, which is fine close
+// to the target rate but lets some of the far larger band above it alias back in from 96 kHz and
+// up. The cutoff is pulled down by a step for every doubling of the rate ratio, so the transition
+// band ends below the output Nyquist. None when upsampling, where ffmpeg's default is right.
+fn downsample_cutoff(source_rate: u32, target_rate: u32) -> Option<f64> {
+    const DEFAULT_CUTOFF: f64 = 0.97;
+    const STEP_PER_OCTAVE: f64 = 0.03;
+    if source_rate <= target_rate {
+        return None;
+    }
+    let ratio = f64::from(source_rate) / f64::from(target_rate);
+    Some((DEFAULT_CUTOFF - STEP_PER_OCTAVE * ratio.log2()).max(0.85))
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FadeCurve {
+    #[default]
+    Linear,
+    // Quarter sine; keeps perceived loudness steadier through the fade
+    EqualPower,
+}
+
+impl FadeCurve {
+    pub const ALL: [FadeCurve; 2] = [FadeCurve::Linear, FadeCurve::EqualPower];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            FadeCurve::Linear => "Linear",
+            FadeCurve::EqualPower => "Equal power",
+        }
+    }
+
+    fn ffmpeg_curve(self) -> &'static str {
+        match self {
+            FadeCurve::Linear => "tri",
+            FadeCurve::EqualPower => "qsin",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SplitMode {
+    // Every input is one track
+    #[default]
+    Off,
+    // Split long inputs at silent gaps
+    Silence,
+    // Split at the tracks of a "<input stem>.cue" sheet next to the input
+    CueSheet,
+    // Split at the chapters stored in the input, as podcasts and audiobooks have
+    Chapters,
+}
+
+impl SplitMode {
+    pub const ALL: [SplitMode; 4] = [SplitMode::Off, SplitMode::Silence, SplitMode::CueSheet, SplitMode::Chapters];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            SplitMode::Off => "Off",
+            SplitMode::Silence => "At silent gaps",
+            SplitMode::CueSheet => "From matching .cue file",
+            SplitMode::Chapters => "At chapters",
+        }
+    }
+}
+
+// What to do with a CD track shorter than the 4 s Red Book minimum
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ShortTrackPolicy {
+    // Write it as it is and list it as too short to burn
+    #[default]
+    Flag,
+    // Pad it with silence to 4 s
+    Pad,
+}
+
+impl ShortTrackPolicy {
+    pub const ALL: [ShortTrackPolicy; 2] = [ShortTrackPolicy::Flag, ShortTrackPolicy::Pad];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ShortTrackPolicy::Flag => "Report them",
+            ShortTrackPolicy::Pad => "Pad to 4 s",
+        }
+    }
+}
+
+// How normalization levels the tracks, after ReplayGain's track and album gain
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GainMode {
+    // Every track to the target on its own
+    #[default]
+    Track,
+    // One gain for all the tracks of an album from their combined loudness, so a quiet song stays
+    // quieter than a loud one
+    Album,
+}
+
+impl GainMode {
+    pub const ALL: [GainMode; 2] = [GainMode::Track, GainMode::Album];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            GainMode::Track => "Per track",
+            GainMode::Album => "Album",
+        }
+    }
+}
+
+// What happens to an output file that is already there and isn't kept as already converted
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ExistingOutputPolicy {
+    #[default]
+    Overwrite,
+    // Leave the file as it is and use it in place of a new conversion
+    Skip,
+    // Rename it to "<name>.~1~", "<name>.~2~"... once the new one is complete
+    NumberedBackup,
+    // Convert nothing at all if any output is there
+    Abort,
+}
+
+impl ExistingOutputPolicy {
+    pub const ALL: [ExistingOutputPolicy; 4] = [
+        ExistingOutputPolicy::Overwrite,
+        ExistingOutputPolicy::Skip,
+        ExistingOutputPolicy::NumberedBackup,
+        ExistingOutputPolicy::Abort,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ExistingOutputPolicy::Overwrite => "Overwrite",
+            ExistingOutputPolicy::Skip => "Keep it, skip the file",
+            ExistingOutputPolicy::NumberedBackup => "Keep a numbered backup",
+            ExistingOutputPolicy::Abort => "Stop the batch",
+        }
+    }
+}
+
+pub const DEFAULT_SPLIT_GAP_SECONDS: f64 = 2.0;
+pub const DEFAULT_SILENCE_THRESHOLD_DB: f64 = -60.0;
+pub const DEFAULT_PEAK_CEILING_DB: f64 = -0.1;
+// Look-ahead of the peak limiter, so transients are caught before they pass the ceiling
+const LIMITER_LOOKAHEAD_MS: f64 = 5.0;
+const LIMITER_RELEASE_MS: f64 = 50.0;
+// Corner of the DC-removal high-pass: far below any musical bass, and a single pole rolls off gently
+const DC_HIGHPASS_HZ: f64 = 10.0;
+// Decoded ahead of a range start and thrown away, so MP3's bit reservoir and decoder priming are
+// settled by the first sample kept
+const SEEK_PREROLL_SECONDS: f64 = 0.5;
+
+// Part of each input to convert instead of all of it, e.g. one song out of a long recording
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TimeRange {
+    pub start: Duration,
+    // None runs to the end of the input
+    pub end: Option<Duration>,
+}
+
+impl TimeRange {
+    fn length(&self) -> Option<f64> {
+        self.end.map(|end| end.saturating_sub(self.start).as_secs_f64())
+    }
+}
+
+pub(crate) fn check_time_range(options: &ConversionOptions) -> Result<()> {
+    let Some(range) = options.time_range else {
         return Ok(());
+    };
+    if range.end.is_some_and(|end| end <= range.start) {
+        return Err(anyhow::anyhow!("The end of the time range must come after its start"));
+    }
+    if options.split_mode != SplitMode::Off {
+        log_warn!("Converting a time range; splitting is ignored");
     }
+    Ok(())
+}
+
+#[derive(Clone, Debug)]
+pub struct ConversionOptions {
+    pub output_format: OutputFormat,
+    // Not used for disc images, which are raw CD sectors
+    pub output_container: OutputContainer,
+    // Anything but 44100 is for DAT, video or other non-CD uses and is never padded or checked as CD audio
+    pub target_rate: u32,
+    // Ignored for CD audio
+    pub channel_mode: ChannelMode,
+    pub write_cue: bool,
+    pub dither: DitherMode,
+    pub resample_quality: ResampleQuality,
+    // Level every track to loudness::TARGET_LUFS
+    pub normalize: bool,
+    // Album gain treats each output folder as one album
+    pub gain_mode: GainMode,
+    // Files converted at once; None uses every CPU core
+    pub max_parallel_files: Option<usize>,
+    // OS priority of the threads running a batch; see priority::set_current_thread
+    pub priority: WorkerPriority,
+    // Write everything here instead of a CDDA_Converted folder next to each input
+    pub output_dir: Option<PathBuf>,
+    // Cut leading and trailing audio whose peaks stay below silence_threshold_db
+    pub trim_silence: bool,
+    pub silence_threshold_db: f64,
+    // Hold the float signal under peak_ceiling_db before quantizing, so overshoot from
+    // resampling or normalization doesn't clip
+    pub limit_peaks: bool,
+    pub peak_ceiling_db: f64,
+    // High-pass each channel just above DC, for sources with an offset that eats headroom and
+    // clicks where tracks join
+    pub remove_dc: bool,
+    // dB added to the left and right channel, to even out a source that leans to one side
+    pub left_gain_db: f64,
+    pub right_gain_db: f64,
+    // Split single inputs into several tracks; silence splitting uses silence_threshold_db too
+    pub split_mode: SplitMode,
+    pub split_min_gap_seconds: f64,
+    // Seconds; zero disables the fade
+    pub fade_in_seconds: f64,
+    pub fade_out_seconds: f64,
+    pub fade_curve: FadeCurve,
+    // Image mode: CD frames of silence written between tracks; zero joins them gaplessly
+    pub gap_frames: u64,
+    // Image mode: choose each gap from the audio instead, none where a track runs into the next
+    // and two seconds between ones that start and end in silence; gap_frames is then unused
+    pub detect_gaps: bool,
+    // Silence before track 1: written into the BIN in image mode, a PREGAP line for track files.
+    // Not counted against the disc's capacity
+    pub first_track_pregap_frames: u64,
+    // Image mode: seconds by which consecutive tracks overlap with an equal-power crossfade;
+    // zero joins them as they are
+    pub crossfade_seconds: f64,
+    // CD tracks under 4 s are padded with silence or reported
+    pub short_tracks: ShortTrackPolicy,
+    // Copy embedded cover art into each output folder that has none yet
+    pub save_cover_art: bool,
+    // Plan and probe everything, report it through ProgressSink::on_planned, but write nothing
+    pub dry_run: bool,
+    // Convert only this many seconds from the start of each input, into "<name> (preview).wav",
+    // to audition settings without converting whole albums
+    pub preview_seconds: Option<f64>,
+    // Cut every input to this range. Splitting is ignored when it is set.
+    pub time_range: Option<TimeRange>,
+    // CUE sheet to split by, instead of the one named after the input
+    pub split_cue: Option<PathBuf>,
+    // Leave outputs that already exist and pass verification alone instead of reconverting them
+    pub skip_existing: bool,
+    // For outputs that exist but aren't left alone by skip_existing. Previews are always overwritten.
+    pub existing_outputs: ExistingOutputPolicy,
+    // Decode and re-encode inputs that are already CDDA WAVs instead of copying them
+    pub force_reencode: bool,
+    // Write 16-bit 44.1 kHz stereo lossless sources (FLAC, ALAC, PCM...) with no filters at all,
+    // then check the output decodes to the same samples as the input
+    pub bit_perfect: bool,
+    // Fail inputs that aren't at target_rate already instead of resampling them
+    pub no_resample: bool,
+    // Fail inputs below target_rate, which were probably made from a low-quality source, while
+    // still downsampling higher ones
+    pub no_upsample: bool,
+    // UPC/EAN for the CUE sheets' CATALOG; when None, the one in the tags is used if any
+    pub cue_catalog: Option<String>,
+    // Add the tracks to the CUE sheet already in the output folder, numbered on from its last
+    // track, instead of starting a new one
+    pub append_to_project: bool,
+    // Recreate the input folder tree under the output folder instead of writing every file into one
+    pub mirror_folders: bool,
+    // Output file names from tags, as parsed by naming::NameTemplate; None names them
+    // "01 - Artist - Title" or after the input
+    pub name_template: Option<String>,
+    // Extra attempts at reading an input after an I/O error, e.g. a network share dropping out;
+    // the delay doubles after each one
+    pub io_retries: u32,
+    pub io_retry_delay_ms: u64,
+}
+
+impl ConversionOptions {
+    // Whether the outputs are Red Book audio, so they get sector padding, CUE sheets and CDDA checks
+    pub fn writes_cdda(&self) -> bool {
+        self.output_format.is_cdda() && self.target_rate == 44100
+    }
+
+    pub fn appends_to_project(&self) -> bool {
+        self.append_to_project && self.write_cue && self.writes_cdda() && self.preview_seconds.is_none()
+    }
+}
 
-    paths.into_iter().for_each(|path| {
-        if *cancel_flag.lock().unwrap() {
-            log_info!("Conversion cancelled by user at process level.");
-            return;
+impl Default for ConversionOptions {
+    fn default() -> Self {
+        Self {
+            output_format: OutputFormat::default(),
+            output_container: OutputContainer::default(),
+            target_rate: 44100,
+            channel_mode: ChannelMode::default(),
+            write_cue: false,
+            dither: DitherMode::default(),
+            resample_quality: ResampleQuality::default(),
+            normalize: false,
+            gain_mode: GainMode::default(),
+            max_parallel_files: None,
+            priority: WorkerPriority::default(),
+            output_dir: None,
+            trim_silence: false,
+            silence_threshold_db: DEFAULT_SILENCE_THRESHOLD_DB,
+            limit_peaks: false,
+            peak_ceiling_db: DEFAULT_PEAK_CEILING_DB,
+            remove_dc: false,
+            left_gain_db: 0.0,
+            right_gain_db: 0.0,
+            split_mode: SplitMode::default(),
+            split_min_gap_seconds: DEFAULT_SPLIT_GAP_SECONDS,
+            fade_in_seconds: 0.0,
+            fade_out_seconds: 0.0,
+            fade_curve: FadeCurve::default(),
+            gap_frames: 0,
+            detect_gaps: true,
+            first_track_pregap_frames: cue::STANDARD_PREGAP_FRAMES,
+            crossfade_seconds: 0.0,
+            short_tracks: ShortTrackPolicy::default(),
+            save_cover_art: true,
+            dry_run: false,
+            preview_seconds: None,
+            time_range: None,
+            split_cue: None,
+            force_reencode: false,
+            bit_perfect: false,
+            no_resample: false,
+            no_upsample: false,
+            cue_catalog: None,
+            append_to_project: false,
+            skip_existing: true,
+            existing_outputs: ExistingOutputPolicy::default(),
+            mirror_folders: false,
+            name_template: None,
+            io_retries: 2,
+            io_retry_delay_ms: 500,
         }
+    }
+}
 
-        let files_to_process = if path.is_dir() {
-            log_info!("Processing folder: {:?}", path);
-            let mut files = Vec::new();
-            for entry in std::fs::read_dir(&path)
-                .into_iter()
-                .flatten()
-                .filter_map(|e| e.ok())
-                .filter(|e| e.file_type().is_ok_and(|ft| ft.is_file()))
-            {
-                let file_path = entry.path();
-                if file_path.extension().and_then(|s| s.to_str()) == Some("mp3") {
-                    files.push(file_path);
+// One written output track
+#[derive(Clone, Debug)]
+pub struct TrackResult {
+    pub input: PathBuf,
+    pub output_path: PathBuf,
+    // Sample frames of audio written, before any padding to a CD sector; silence padded on to
+    // reach the 4 s minimum counts. For a track kept from an earlier run this is what its file
+    // holds, padding included.
+    pub frames_written: u64,
+    pub channels: u16,
+    pub sample_rate: u32,
+    // The input's rate, before any resampling
+    pub source_rate: u32,
+    // Measured from the written audio; None if it couldn't be read back
+    pub signal: Option<SignalStats>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Resampling {
+    Down,
+    // From a lower rate, which usually means a lossy or low-bandwidth source
+    Up,
+}
+
+impl Resampling {
+    pub fn label(self) -> &'static str {
+        match self {
+            Resampling::Down => "downsampled",
+            Resampling::Up => "upsampled",
+        }
+    }
+}
+
+impl TrackResult {
+    pub fn duration_seconds(&self) -> f64 {
+        self.frames_written as f64 / f64::from(self.sample_rate)
+    }
+
+    // Length on a CD, counting the last partial sector as a whole one
+    pub fn cd_frames(&self) -> u64 {
+        self.frames_written.div_ceil(SAMPLES_PER_CD_FRAME)
+    }
+
+    pub fn resampling(&self) -> Option<Resampling> {
+        match self.source_rate.cmp(&self.sample_rate) {
+            std::cmp::Ordering::Greater => Some(Resampling::Down),
+            std::cmp::Ordering::Less => Some(Resampling::Up),
+            std::cmp::Ordering::Equal => None,
+        }
+    }
+
+    pub fn is_too_short_for_cd(&self) -> bool {
+        self.sample_rate == 44100 && self.cd_frames() < cue::MIN_TRACK_FRAMES
+    }
+
+    // Reasons to listen again before burning; empty if there are none
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = self.signal.map(|signal| signal.problems()).unwrap_or_default();
+        if self.is_too_short_for_cd() {
+            problems.push(format!("only {:.1} s, shorter than a CD track may be", self.duration_seconds()));
+        }
+        if self.resampling() == Some(Resampling::Up) {
+            problems.push(format!("upsampled from {} Hz, so probably from a low-quality source", self.source_rate));
+        }
+        problems
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ConversionSummary {
+    pub converted: usize,
+    // Every track written or kept, in the order they go on the disc
+    pub tracks: Vec<TrackResult>,
+    // Each input that failed, with the reason
+    pub failures: Vec<(PathBuf, String)>,
+    // Outputs that were already valid CDDA and were kept as they are
+    pub skipped: usize,
+    // Files never started because the batch was told to stop after the ones in progress
+    pub not_started: usize,
+}
+
+impl ConversionSummary {
+    pub fn failed(&self) -> usize {
+        self.failures.len()
+    }
+
+    pub fn total_duration_seconds(&self) -> f64 {
+        self.tracks.iter().map(TrackResult::duration_seconds).sum()
+    }
+
+    // Tracks that weren't at the output rate, downsampled or upsampled
+    pub fn resampled_tracks(&self) -> impl Iterator<Item = &TrackResult> {
+        self.tracks.iter().filter(|track| track.resampling().is_some())
+    }
+
+    // Tracks that clip, have collapsed to nearly mono, are too short to burn or were upsampled
+    pub fn flagged_tracks(&self) -> impl Iterator<Item = &TrackResult> {
+        self.tracks.iter().filter(|track| !track.problems().is_empty())
+    }
+
+    pub(crate) fn record_failure(&mut self, input: &Path, error: impl std::fmt::Display) {
+        self.failures.push((input.to_path_buf(), error.to_string()));
+    }
+}
+
+struct Job {
+    input: PathBuf,
+    output_folder: PathBuf,
+    // Top of the output tree; the same as output_folder unless folders are mirrored
+    output_root: PathBuf,
+    output_path: PathBuf,
+    stream_info: StreamInfo,
+    already_converted: bool,
+}
+
+// What a dry run reports for each input
+#[derive(Clone, Debug)]
+pub struct PlannedFile {
+    pub input: PathBuf,
+    pub output_path: PathBuf,
+    pub duration: Option<f64>,
+    pub sample_rate: u32,
+    pub target_rate: u32,
+    pub channels: u16,
+    pub already_converted: bool,
+}
+
+impl PlannedFile {
+    pub fn needs_resample(&self) -> bool {
+        self.sample_rate != self.target_rate
+    }
+}
+
+struct ConvertedTrack {
+    result: TrackResult,
+    info: TrackInfo,
+    report: Option<TrackReport>,
+    // Pregap at the start of the file, marked by INDEX 00 in the CUE sheet
+    gap_frames: u64,
+}
+
+// cancel_flag stops the batch at once, killing the files in progress. stop_flag only keeps further
+// files from starting: those in progress finish and the batch ends as if they were the last.
+pub fn convert_files(
+    paths: Vec<PathBuf>,
+    cancel_flag: Arc<AtomicBool>,
+    pause_flag: Arc<AtomicBool>,
+    stop_flag: Arc<AtomicBool>,
+    options: &ConversionOptions,
+    progress: &dyn ProgressSink,
+) -> Result<ConversionSummary> {
+    check_time_range(options)?;
+    // Checked up front so a typo fails the batch now rather than the CUE sheets at the end
+    if let (true, Some(code)) = (options.write_cue, &options.cue_catalog) {
+        cue::normalize_catalog(code)?;
+    }
+    let mut summary = ConversionSummary::default();
+    let template = options.name_template.as_deref().map(NameTemplate::parse).transpose()?;
+    let mut jobs = plan_jobs(paths, options, template.as_ref(), &mut summary, &cancel_flag, progress);
+    if cancel_flag.load(Ordering::Relaxed) {
+        log_info!("Conversion cancelled by user while scanning the inputs");
+        return Ok(summary);
+    }
+    if options.existing_outputs == ExistingOutputPolicy::Abort {
+        let existing: Vec<&Job> = jobs
+            .iter()
+            .filter(|job| !job.already_converted && options.preview_seconds.is_none() && job.output_path.exists())
+            .collect();
+        if let Some(first) = existing.first() {
+            return Err(anyhow::anyhow!(
+                "{} output files already exist, starting with {:?}; nothing was converted",
+                existing.len(),
+                first.output_path
+            ));
+        }
+    }
+    if options.dry_run {
+        for job in &jobs {
+            progress.on_planned(&PlannedFile {
+                input: job.input.clone(),
+                output_path: job.output_path.clone(),
+                duration: job.stream_info.duration,
+                sample_rate: job.stream_info.sample_rate,
+                target_rate: options.target_rate,
+                channels: job.stream_info.channels,
+                already_converted: job.already_converted,
+            });
+        }
+        log_info!("Dry run planned {} files; nothing was written", jobs.len());
+        return Ok(summary);
+    }
+    if jobs.is_empty() {
+        return Ok(summary);
+    }
+    // Every track of an album has to be measured before the first one can be written
+    if options.normalize && options.gain_mode == GainMode::Album {
+        set_album_gains(&mut jobs, &cancel_flag);
+        if cancel_flag.load(Ordering::Relaxed) {
+            log_info!("Conversion cancelled by user while measuring album loudness");
+            return Ok(summary);
+        }
+    }
+
+    progress.on_batch_start(&input_weights(&jobs));
+    let workers = options
+        .max_parallel_files
+        .unwrap_or_else(|| thread::available_parallelism().map_or(1, |n| n.get()))
+        .clamp(1, jobs.len());
+    log_info!("Converting {} files on {} worker threads", jobs.len(), workers);
+
+    // Workers pull the next job index; results are slotted back by index so the CUE keeps input order
+    let next_job = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<Result<ConvertedTrack>>>> = Mutex::new((0..jobs.len()).map(|_| None).collect());
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            let (jobs, next_job, results, cancel_flag, pause_flag, stop_flag) =
+                (&jobs, &next_job, &results, &cancel_flag, &pause_flag, &stop_flag);
+            scope.spawn(move || loop {
+                wait_while_paused(pause_flag, cancel_flag);
+                let index = next_job.fetch_add(1, Ordering::SeqCst);
+                let Some(job) = jobs.get(index) else {
+                    break;
+                };
+                if cancel_flag.load(Ordering::Relaxed) {
+                    log_info!("Conversion cancelled by user before processing file: {:?}", job.input);
+                    break;
+                }
+                // Only checked here, between files, never while one is being converted
+                if stop_flag.load(Ordering::Relaxed) {
+                    log_info!("Stopping after the files in progress; not starting: {:?}", job.input);
+                    break;
+                }
+
+                let outcome = run_job(index, jobs.len(), job, cancel_flag, pause_flag, options, progress);
+                results.lock().unwrap()[index] = Some(outcome);
+            });
+        }
+    });
+
+    let cancelled = cancel_flag.load(Ordering::Relaxed);
+    let mut converted: Vec<(PathBuf, ConvertedTrack)> = Vec::new();
+    for (job, outcome) in jobs.iter().zip(results.into_inner().unwrap()) {
+        match outcome {
+            Some(Ok(track)) => {
+                if job.already_converted {
+                    summary.skipped += 1;
+                } else {
+                    summary.converted += 1;
                 }
+                summary.tracks.push(track.result.clone());
+                converted.push((job.output_folder.clone(), track));
             }
-            files
-        } else if path.extension().and_then(|s| s.to_str()) == Some("mp3") {
-            log_info!("Processing single file: {:?}", path);
-            vec![path]
-        } else {
-            log_warn!("Skipping non-MP3 file or directory: {:?}", path);
-            return;
+            Some(Err(e)) if cancelled => {
+                log_info!("Stopped converting {} on cancel: {}", job.input.display(), e);
+            }
+            Some(Err(e)) => {
+                log_error!("Failed to convert {}: {:?}", job.input.display(), e);
+                summary.record_failure(&job.input, format!("{:#}", e));
+            }
+            None if cancelled => {} // Never started because of a cancel
+            None => summary.not_started += 1,
+        }
+    }
+
+    if cancelled {
+        remove_empty_output_folders(&jobs);
+    } else if options.preview_seconds.is_some() {
+        log_info!("Wrote previews only; skipping CUE sheets, cover art and conversion logs");
+        return Ok(summary);
+    } else if options.write_cue && !options.writes_cdda() {
+        log_warn!("Not writing CUE sheets for {} Hz {} output; they only describe CD audio", options.target_rate, options.output_format.label());
+    } else if options.write_cue {
+        write_cue_sheets(&converted, options);
+    }
+    if !cancelled && options.save_cover_art {
+        save_cover_art(&converted);
+    }
+    if !cancelled {
+        write_conversion_logs(&converted);
+    }
+
+    log_info!("Conversion process complete!");
+    Ok(summary)
+}
+
+// The usual layout of lossless downloads and rips: one audio file for the whole disc plus a CUE
+// sheet. Each of the sheet's tracks becomes its own numbered file in output_folder, with a new
+// CUE sheet there listing them, pregaps (INDEX 00) included.
+#[allow(clippy::too_many_arguments)]
+pub fn convert_with_cue(
+    audio: &Path,
+    cue_sheet: &Path,
+    output_folder: &Path,
+    cancel_flag: Arc<AtomicBool>,
+    pause_flag: Arc<AtomicBool>,
+    stop_flag: Arc<AtomicBool>,
+    options: &ConversionOptions,
+    progress: &dyn ProgressSink,
+) -> Result<ConversionSummary> {
+    if !audio.is_file() {
+        return Err(anyhow::anyhow!("{:?} is not an audio file", audio));
+    }
+    let options = ConversionOptions {
+        split_mode: SplitMode::CueSheet,
+        split_cue: Some(cue_sheet.to_path_buf()),
+        time_range: None,
+        write_cue: true,
+        output_dir: Some(output_folder.to_path_buf()),
+        mirror_folders: false,
+        ..options.clone()
+    };
+    convert_files(vec![audio.to_path_buf()], cancel_flag, pause_flag, stop_flag, &options, progress)
+}
+
+// Label for a stream converted by convert_reader, in log messages and errors
+const STREAM_INPUT: &str = "pipe:0";
+
+// Converts one stream, e.g. standard input, into output_path without the input touching the disk.
+// ffmpeg reads it through a pipe, so it can't be probed or read twice: settings that need a pass
+// of their own over the input are refused, the source rate and layout are whatever ffmpeg finds,
+// and there are no tags to copy. Returns the sample frames written.
+pub fn convert_reader(
+    reader: Box<dyn Read + Send>,
+    output_path: &Path,
+    cancel_flag: Arc<AtomicBool>,
+    pause_flag: Arc<AtomicBool>,
+    options: &ConversionOptions,
+) -> Result<u64> {
+    let needs_second_pass: Vec<&str> = [
+        (options.normalize, "normalizing"),
+        (options.trim_silence, "silence trimming"),
+        (options.fade_out_seconds > 0.0, "fading out"),
+        (options.split_mode != SplitMode::Off, "splitting"),
+        (options.time_range.is_some(), "converting part of the input"),
+        (options.bit_perfect, "bit-perfect copies"),
+    ]
+    .iter()
+    .filter(|(enabled, _)| *enabled)
+    .map(|(_, name)| *name)
+    .collect();
+    if !needs_second_pass.is_empty() {
+        return Err(anyhow::anyhow!(
+            "Can't use {} on a stream, which can only be read once",
+            needs_second_pass.join(", ")
+        ));
+    }
+    if output_path.exists() {
+        match options.existing_outputs {
+            ExistingOutputPolicy::Overwrite | ExistingOutputPolicy::NumberedBackup => {}
+            ExistingOutputPolicy::Skip => {
+                log_info!("{:?} already exists; leaving it as it is", output_path);
+                return Ok(0);
+            }
+            ExistingOutputPolicy::Abort => return Err(anyhow::anyhow!("{:?} already exists; nothing was converted", output_path)),
+        }
+    }
+    if let Some(folder) = output_path.parent().filter(|folder| !folder.as_os_str().is_empty()) {
+        fs::create_dir_all(folder).with_context(|| format!("Failed to create output folder {:?}", folder))?;
+    }
+
+    let rate = options.target_rate;
+    // The source rate isn't known up front, so the resampler gets no cutoff tuned to it
+    let mut filters = vec![format!("aresample=osr={}:osf=flt:{}", rate, options.resample_quality.swr_options(rate, rate))];
+    filters.extend(balance_filter(options, None));
+    if options.remove_dc {
+        filters.push(format!("highpass=f={}:poles=1", DC_HIGHPASS_HZ));
+    }
+    if options.fade_in_seconds > 0.0 {
+        filters.push(format!("afade=t=in:st=0:d={:.3}:curve={}", options.fade_in_seconds, options.fade_curve.ffmpeg_curve()));
+    }
+    if options.limit_peaks {
+        filters.push(limiter_filter(options));
+    }
+    // The source's bit depth isn't known up front either
+    filters.push(quantize_filter(options, true));
+    // Mono can't be told apart in advance, so "preserve mono" writes stereo here
+    let channels = if options.writes_cdda() { 2 } else { options.channel_mode.output_channels(2) };
+
+    let partial = partial_path(output_path);
+    let mut command = Command::new("ffmpeg");
+    command.args(["-fflags", "+discardcorrupt", "-i", STREAM_INPUT]);
+    command.args(["-af", &filters.join(",")]);
+    command.args([
+        "-map_metadata", "-1",
+        "-acodec", options.output_format.codec(options.output_container),
+        "-ac", &channels.to_string(),
+        "-ar", &rate.to_string(),
+        "-f", options.output_container.ffmpeg_format(),
+        "-y",
+    ]);
+    command.arg(&partial);
+    log_info!("Converting standard input into {:?}", output_path);
+    let input_label = Path::new(STREAM_INPUT);
+    let decoded = run_ffmpeg_fed(command, Some(reader), input_label, None, &cancel_flag, &pause_flag, &|_, _| {});
+    let finished = keep_partial_output(decoded, input_label, &partial, options).and_then(|()| {
+        let frames_written = wav::read_wav_info(&partial)?
+            .sample_frames()
+            .ok_or_else(|| anyhow::anyhow!("ffmpeg wrote no fmt chunk to {:?}", partial))?;
+        if frames_written == 0 {
+            return Err(anyhow::anyhow!("The stream decoded to no audio at all"));
+        }
+        if options.writes_cdda() {
+            let min_frames = if options.short_tracks == ShortTrackPolicy::Pad { cue::MIN_TRACK_FRAMES } else { 0 };
+            wav::pad_to_frames(&partial, min_frames).context("Failed to pad output to a CD sector boundary")?;
+        }
+        if options.existing_outputs == ExistingOutputPolicy::NumberedBackup {
+            backup_existing(output_path)?;
+        }
+        fs::rename(&partial, output_path).with_context(|| format!("Failed to rename {:?} to {:?}", partial, output_path))?;
+        Ok(frames_written)
+    });
+    if finished.is_err() && partial.exists() {
+        if let Err(e) = fs::remove_file(&partial) {
+            log_warn!("Failed to remove incomplete output {:?}: {}", partial, e);
+        }
+    }
+    finished
+}
+
+fn run_job(
+    index: usize,
+    total_files: usize,
+    job: &Job,
+    cancel_flag: &Arc<AtomicBool>,
+    pause_flag: &Arc<AtomicBool>,
+    options: &ConversionOptions,
+    progress: &dyn ProgressSink,
+) -> Result<ConvertedTrack> {
+    if job.already_converted {
+        // Still returned as a track so it keeps its place in the CUE sheet
+        let wav_info = wav::read_wav_info(&job.output_path)?;
+        let result = TrackResult {
+            input: job.input.clone(),
+            output_path: job.output_path.clone(),
+            frames_written: wav_info.sample_frames().unwrap_or_default(),
+            channels: wav_info.format.as_ref().map_or(2, |format| format.channels),
+            sample_rate: options.target_rate,
+            source_rate: job.stream_info.sample_rate,
+            signal: measure_signal(&job.output_path, &wav_info),
         };
+        return Ok(ConvertedTrack {
+            report: track_report(&result, &job.stream_info),
+            result,
+            info: job.stream_info.track.clone(),
+            gap_frames: job.stream_info.gap_frames(),
+        });
+    }
+    fs::create_dir_all(&job.output_folder).context("Failed to create output directory")?;
+
+    progress.on_file_start(index, total_files, &job.input);
+    let report = |done: u64, total: u64| progress.on_progress(index, done, total);
 
-        if files_to_process.is_empty() {
-            log_warn!("No MP3 files found in {:?}", path);
-            return;
+    let start_time = std::time::Instant::now();
+    log_info!("Starting conversion of: {:?}", job.input);
+    let outcome = with_disk_full_pause(&job.output_path, cancel_flag, pause_flag, progress, || {
+        with_io_retry(&job.input, options, || {
+            convert_with_ffmpeg(&job.input, &job.output_path, &job.stream_info, cancel_flag, pause_flag, options, &report)
+        })
+    });
+    if outcome.is_ok() {
+        log_info!("Conversion completed in {:.2}s: {:?}", start_time.elapsed().as_secs_f32(), job.input);
+    }
+    progress.on_file_done(index, &job.input, outcome.is_ok());
+    outcome
+}
+
+// Output folders are created when their first job starts; one whose only jobs were cancelled is left empty.
+fn remove_empty_output_folders(jobs: &[Job]) {
+    for folder in unique_folders(jobs.iter().map(|job| &job.output_folder)) {
+        // remove_dir refuses non-empty folders, which are exactly the ones to keep. Mirrored
+        // trees are pruned upwards as far as the output root.
+        let root = &jobs.iter().find(|job| &job.output_folder == folder).unwrap().output_root;
+        let mut current = Some(folder.as_path());
+        while let Some(dir) = current.filter(|dir| dir.starts_with(root)) {
+            if fs::remove_dir(dir).is_err() {
+                break;
+            }
+            log_debug!("Removed empty output folder {:?}", dir);
+            current = dir.parent();
+        }
+    }
+}
+
+pub fn output_folder_for(path: &Path, options: &ConversionOptions) -> PathBuf {
+    match &options.output_dir {
+        Some(dir) => dir.clone(),
+        None => path.parent().unwrap_or_else(|| Path::new(".")).join(OUTPUT_FOLDER_NAME),
+    }
+}
+
+const PARTIAL_SUFFIX: &str = ".tmp";
+
+// Moves an existing file out of the way to the first free "<name>.~N~", as `cp --backup=numbered` does
+pub(crate) fn backup_existing(path: &Path) -> Result<()> {
+    if !path.exists() {
+        return Ok(());
+    }
+    let backup = (1..)
+        .map(|number| {
+            let mut name = path.as_os_str().to_owned();
+            name.push(format!(".~{}~", number));
+            PathBuf::from(name)
+        })
+        .find(|backup| !backup.exists())
+        .unwrap();
+    fs::rename(path, &backup).with_context(|| format!("Failed to back up {:?} to {:?}", path, backup))?;
+    log_info!("Moved the existing {:?} to {:?}", path, backup);
+    Ok(())
+}
+
+// "01 - Title.wav" is written as "01 - Title.wav.tmp"
+pub(crate) fn partial_path(output_path: &Path) -> PathBuf {
+    let mut name = output_path.as_os_str().to_owned();
+    name.push(PARTIAL_SUFFIX);
+    PathBuf::from(name)
+}
+
+// Outputs a crashed or killed run was still writing. Only names ending in one of our output
+// extensions plus .tmp are touched, so unrelated temporary files in a shared folder survive.
+fn remove_stale_partials(folder: &Path) {
+    let Ok(entries) = fs::read_dir(folder) else {
+        return;
+    };
+    for path in entries.filter_map(|entry| entry.ok()).map(|entry| entry.path()) {
+        let name = path.file_name().unwrap_or_default().to_string_lossy().to_lowercase();
+        let is_partial = OutputContainer::ALL
+            .iter()
+            .any(|container| name.ends_with(&format!(".{}{}", container.extension(), PARTIAL_SUFFIX)));
+        if is_partial && path.is_file() {
+            match fs::remove_file(&path) {
+                Ok(()) => {
+                    log_info!("Removed {:?} left over from an interrupted conversion", path);
+                }
+                Err(e) => {
+                    log_warn!("Failed to remove leftover {:?}: {}", path, e);
+                }
+            }
+        }
+    }
+}
+
+// Each folder once, in the order first seen. Jobs from several inputs can interleave their
+// output folders, so neighbouring duplicates aren't the only ones.
+fn unique_folders<'a>(folders: impl IntoIterator<Item = &'a PathBuf>) -> Vec<&'a PathBuf> {
+    let mut unique = Vec::new();
+    for folder in folders {
+        if !unique.contains(&folder) {
+            unique.push(folder);
+        }
+    }
+    unique
+}
+
+// Case-insensitive, as the output may land on a filesystem that is. Names that aren't valid
+// Unicode are compared exactly, as a lossy conversion could make two different ones equal.
+fn same_name(a: &Path, b: &Path) -> bool {
+    match (a.to_str(), b.to_str()) {
+        (Some(a), Some(b)) => a.to_lowercase() == b.to_lowercase(),
+        _ => a == b,
+    }
+}
+
+// "<stem>[ (copy)]<suffix>.<extension>", built on the OsStr so the stem is kept byte for byte
+fn output_file_name(stem: &OsStr, copy: Option<usize>, suffix: &str, extension: &str) -> OsString {
+    let mut name = stem.to_os_string();
+    if let Some(copy) = copy {
+        name.push(format!(" ({})", copy));
+    }
+    name.push(format!("{}.{}", suffix, extension));
+    name
+}
+
+// Output folders this long leave too little room for file names under Windows' 260-character
+// MAX_PATH, which ffmpeg builds before 5.1 are held to
+#[cfg(windows)]
+pub(crate) fn needs_extended_length(folder: &Path) -> bool {
+    const LONG_FOLDER: usize = 200;
+    folder.as_os_str().len() >= LONG_FOLDER && !folder.as_os_str().to_string_lossy().starts_with(r"\\?\")
+}
+
+#[cfg(not(windows))]
+pub(crate) fn needs_extended_length(_folder: &Path) -> bool {
+    false
+}
+
+// The \\?\ form of a path, which Windows doesn't limit to MAX_PATH. It also skips the usual
+// clean-up of / . and .., so the path is made absolute, which resolves those, first.
+#[cfg(windows)]
+pub(crate) fn extended_length(path: &Path) -> PathBuf {
+    let Ok(absolute) = std::path::absolute(path) else {
+        return path.to_path_buf();
+    };
+    let mut extended = OsString::new();
+    match absolute.to_str().and_then(|path| path.strip_prefix(r"\\")) {
+        Some(share) => {
+            extended.push(r"\\?\UNC\");
+            extended.push(share);
+        }
+        None => {
+            extended.push(r"\\?\");
+            extended.push(absolute.as_os_str());
+        }
+    }
+    PathBuf::from(extended)
+}
+
+#[cfg(not(windows))]
+pub(crate) fn extended_length(path: &Path) -> PathBuf {
+    path.to_path_buf()
+}
+
+// The deepest folder containing every selected file and folder; mirrored output paths are
+// relative to it. None when the inputs share no folder at all, e.g. on different drives.
+fn mirror_root(paths: &[PathBuf]) -> Option<PathBuf> {
+    let mut folders = paths
+        .iter()
+        .map(|path| if path.is_dir() { path.as_path() } else { path.parent().unwrap_or_else(|| Path::new(".")) });
+    let mut root = folders.next()?.to_path_buf();
+    for folder in folders {
+        while !folder.starts_with(&root) {
+            if !root.pop() {
+                return None;
+            }
+        }
+    }
+    // Every path starts with the empty path, so an empty root only works if all of them are relative
+    (!root.as_os_str().is_empty() || paths.iter().all(|path| path.is_relative())).then_some(root)
+}
+
+// A selected file or folder as the list of audio files it stands for. Folders are searched
+// recursively in name order, skipping earlier conversion output; playlists give their entries in
+// playlist order.
+pub fn expand_input(path: &Path) -> Vec<PathBuf> {
+    expand_listing(path).0
+}
+
+// Also returns the entries of a playlist that don't exist, for the caller to report
+pub fn expand_listing(path: &Path) -> (Vec<PathBuf>, Vec<PathBuf>) {
+    let (files, missing) = scan_listing(path, None);
+    let files: Vec<PathBuf> = files.collect();
+    if files.is_empty() && path.is_dir() {
+        log_warn!("No audio files found in {:?}", path);
+    }
+    (files, missing)
+}
+
+// Like expand_listing, but a folder's files come as the walk reaches them, so a caller can start
+// on the first ones, or give up, before a big tree has been read to the end. `exclude` is a folder
+// in the tree to leave out along with CDDA_Converted, as reached from `path`.
+fn scan_listing(path: &Path, exclude: Option<PathBuf>) -> (Box<dyn Iterator<Item = PathBuf>>, Vec<PathBuf>) {
+    if playlist::is_playlist(path) {
+        let playlist = match playlist::read_playlist(path) {
+            Ok(playlist) => playlist,
+            Err(e) => {
+                log_error!("{:?}", e);
+                return (Box::new(std::iter::empty()), Vec::new());
+            }
+        };
+        // Folders listed in a playlist are expanded too, but playlists in playlists are not
+        let files = playlist
+            .entries
+            .into_iter()
+            .filter(|entry| !playlist::is_playlist(entry))
+            .flat_map(|entry| scan_listing(&entry, None).0);
+        return (Box::new(files), playlist.missing);
+    }
+
+    if path.is_dir() {
+        log_info!("Processing folder: {:?}", path);
+        let files = WalkDir::new(path)
+            .sort_by_file_name()
+            .into_iter()
+            .filter_entry(move |e| {
+                !(e.file_type().is_dir() && (e.file_name() == OUTPUT_FOLDER_NAME || Some(e.path()) == exclude.as_deref()))
+            })
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_type().is_file() && is_supported_audio(e.path()))
+            .map(|e| e.into_path());
+        (Box::new(files), Vec::new())
+    } else if is_supported_audio(path) {
+        log_info!("Processing single file: {:?}", path);
+        (Box::new(std::iter::once(path.to_path_buf())), Vec::new())
+    } else {
+        log_warn!("Skipping unsupported file or directory: {:?}", path);
+        (Box::new(std::iter::empty()), Vec::new())
+    }
+}
+
+// The output folder as the walk of `input_folder` would reach it, when it lies somewhere inside
+// that folder; None if it is elsewhere, is the folder itself or doesn't exist yet.
+fn nested_output(input_folder: &Path, output_folder: &Path) -> Option<PathBuf> {
+    if !input_folder.is_dir() {
+        return None;
+    }
+    let input = fs::canonicalize(input_folder).ok()?;
+    let output = fs::canonicalize(output_folder).ok()?;
+    let relative = output.strip_prefix(&input).ok()?;
+    (!relative.as_os_str().is_empty()).then(|| input_folder.join(relative))
+}
+
+// Each job's share of the input bytes. Tracks split from one input share its size, and outputs
+// that are kept as they are weigh nothing, since they finish at once.
+fn input_weights(jobs: &[Job]) -> Vec<u64> {
+    jobs.iter()
+        .map(|job| {
+            if job.already_converted {
+                return 0;
+            }
+            let parts = jobs.iter().filter(|other| other.input == job.input).count() as u64;
+            // Without the tags, or a big cover in one file would weigh like minutes of audio
+            metadata::audio_bytes(&job.input) / parts.max(1)
+        })
+        .collect()
+}
+
+// Expands the selected files and folders into the list of files to convert, in order. Each file
+// is probed as the folder walk finds it, reporting the running count, and a cancel stops the
+// walk there; the jobs planned so far are returned.
+fn plan_jobs(
+    paths: Vec<PathBuf>,
+    options: &ConversionOptions,
+    template: Option<&NameTemplate>,
+    summary: &mut ConversionSummary,
+    cancel_flag: &AtomicBool,
+    progress: &dyn ProgressSink,
+) -> Vec<Job> {
+    let mirror = if options.mirror_folders {
+        let root = mirror_root(&paths);
+        if root.is_none() {
+            log_warn!("The inputs share no common folder; writing them without mirroring the folder tree");
+        }
+        root.map(|root| {
+            let output_root = options.output_dir.clone().unwrap_or_else(|| root.join(OUTPUT_FOLDER_NAME));
+            (root, output_root)
+        })
+    } else {
+        None
+    };
+
+    let mut jobs: Vec<Job> = Vec::new();
+    // Tracks already in each output folder's CUE sheet when appending; None if it can't be read
+    let mut project_tracks: HashMap<PathBuf, Option<usize>> = HashMap::new();
+    let mut scanned = 0;
+    for path in paths {
+        let flat_output_folder = output_folder_for(&path, options);
+        // A custom output folder inside the selected one would otherwise be converted again on the next run
+        let output_top = mirror.as_ref().map_or(&flat_output_folder, |(_, output_root)| output_root);
+        let nested = nested_output(&path, output_top);
+        if let Some(nested) = &nested {
+            log_info!("Leaving the output folder {:?} out of the scan of {:?}", nested, path);
+        }
+        let (files_to_process, missing) = scan_listing(&path, nested);
+        for entry in missing {
+            summary.record_failure(&entry, format!("Listed in {:?} but not found", path));
+        }
+        let mut files_to_process = files_to_process.peekable();
+        if files_to_process.peek().is_none() {
+            if path.is_dir() {
+                log_warn!("No audio files found in {:?}", path);
+            }
+            continue;
         }
 
-        let parent_folder = path.parent().unwrap_or_else(|| Path::new("."));
-        let output_folder = parent_folder.join("CDDA_Converted");
-        if let Err(e) = fs::create_dir_all(&output_folder) {
-            log_error!("Failed to create output directory: {:?}", e);
-            return;
+        let source_folder = if path.is_dir() { path.as_path() } else { path.parent().unwrap_or_else(|| Path::new(".")) };
+        if let (Ok(source), Ok(output)) = (fs::canonicalize(source_folder), fs::canonicalize(&flat_output_folder)) {
+            if source == output {
+                log_warn!("Output folder is the same as the input folder {:?}; WAV inputs there will be skipped", source_folder);
+            }
         }
+        for input in files_to_process {
+            if cancel_flag.load(Ordering::Relaxed) {
+                log_info!("Stopped scanning the inputs after {} files", scanned);
+                return jobs;
+            }
+            scanned += 1;
+            progress.on_scanning(scanned);
+            let Some(stem) = input.file_stem() else {
+                log_error!("Invalid input filename: {:?}", input);
+                summary.record_failure(&input, "Invalid file name");
+                continue;
+            };
+            let (mut output_folder, mut output_root) = match &mirror {
+                Some((root, output_root)) => {
+                    let relative = input.parent().and_then(|parent| parent.strip_prefix(root).ok()).unwrap_or(Path::new(""));
+                    (output_root.join(relative), output_root.clone())
+                }
+                None => (flat_output_folder.clone(), flat_output_folder.clone()),
+            };
+            // Deep mirrored trees such as CDDA_Converted/Artist/Album; both change so the folder stays under its root
+            if needs_extended_length(&output_folder) {
+                output_folder = extended_length(&output_folder);
+                output_root = extended_length(&output_root);
+            }
+            let probed = with_io_retry(&input, options, || {
+                if options.dry_run {
+                    probe_headers(&input)
+                } else {
+                    probe_stream(&input)
+                }
+            });
+            if let Some(Err(e)) = probed.as_ref().ok().map(|info| check_resampling_allowed(info, options)) {
+                log_error!("Not converting {}: {:#}", input.display(), e);
+                summary.record_failure(&input, format!("{:#}", e));
+                continue;
+            }
+            let tracks = probed.and_then(|info| match options.time_range {
+                Some(range) => Ok(vec![info.for_range(range)]),
+                None => split::split_input(&input, &info, options),
+            });
+            let tracks = match tracks {
+                Ok(tracks) => tracks,
+                Err(e) => {
+                    log_error!("Failed to read {}: {:?}", input.display(), e);
+                    summary.record_failure(&input, format!("{:#}", e));
+                    continue;
+                }
+            };
+            let split = tracks.len() > 1;
+            for mut stream_info in tracks {
+                if options.appends_to_project() {
+                    let existing = *project_tracks.entry(output_folder.clone()).or_insert_with(|| {
+                        match existing_project(&output_folder) {
+                            Ok(sheet) => Some(sheet.map_or(0, |sheet| sheet.tracks.len())),
+                            Err(e) => {
+                                log_error!("Can't append to the CUE sheet in {:?}: {:?}", output_folder, e);
+                                None
+                            }
+                        }
+                    });
+                    // Not converted at all, rather than written as a new disc over the old sheet
+                    let Some(existing) = existing else {
+                        summary.record_failure(&input, "The CUE sheet to append to can't be read");
+                        continue;
+                    };
+                    let added = jobs.iter().filter(|job| job.output_folder == output_folder).count();
+                    stream_info.track.track_number = Some((existing + added + 1) as u32);
+                }
+                // Untagged inputs keep their own name exactly, even one that isn't valid Unicode
+                let mut fallback = stem.to_os_string();
+                if let Some(number) = stream_info.track.track_number.filter(|_| split) {
+                    fallback.push(format!(" - {:02}", number));
+                }
+                let output_stem: OsString = match template {
+                    Some(template) => template.render(&stream_info.track, &stem.to_string_lossy(), &fallback.to_string_lossy()).into(),
+                    None if stream_info.track.title.is_none() => fallback,
+                    None => stream_info.track.output_stem(&fallback.to_string_lossy()).into(),
+                };
+                let suffix = if options.preview_seconds.is_some() { " (preview)" } else { "" };
+                // Output names are fixed here, before any worker starts, so two files can never race for one path.
+                // Clashes, e.g. 01.mp3 from two album folders, get " (2)", " (3)" in input order, which
+                // keeps the names stable across runs over the same selection.
+                let extension = options.output_container.extension();
+                let mut output_path = output_folder.join(output_file_name(&output_stem, None, suffix, extension));
+                let mut copy = 1;
+                while jobs.iter().any(|job| same_name(&job.output_path, &output_path)) {
+                    copy += 1;
+                    output_path = output_folder.join(output_file_name(&output_stem, Some(copy), suffix, extension));
+                }
+                if copy > 1 {
+                    log_info!("Output name for {:?} is already taken in this batch; writing {:?}", input, output_path);
+                }
+                // Checked against the final name, so it matches whatever this run would write. A
+                // preview is always redone, since it exists to try out changed settings.
+                let exists = options.preview_seconds.is_none() && output_path.exists();
+                let mut already_converted = options.skip_existing
+                    && exists
+                    && options.writes_cdda()
+                    && verify::verify_cdda(&output_path).is_ok_and(|report| report.is_compliant());
+                if already_converted {
+                    log_info!("Skipping {:?}: {:?} is already converted", input, output_path);
+                } else if exists && options.existing_outputs == ExistingOutputPolicy::Skip {
+                    log_info!("Skipping {:?}: {:?} already exists", input, output_path);
+                    already_converted = true;
+                }
+                jobs.push(Job {
+                    input: input.clone(),
+                    output_folder: output_folder.clone(),
+                    output_root: output_root.clone(),
+                    output_path,
+                    stream_info,
+                    already_converted,
+                });
+            }
+        }
+    }
+    if !options.dry_run {
+        for folder in unique_folders(jobs.iter().map(|job| &job.output_folder)) {
+            remove_stale_partials(folder);
+        }
+    }
+    jobs
+}
+
+// The first embedded cover found among each folder's tracks, unless the folder already has one.
+fn save_cover_art(converted: &[(PathBuf, ConvertedTrack)]) {
+    for folder in unique_folders(converted.iter().map(|(folder, _)| folder)) {
+        if art::has_cover(folder) {
+            continue;
+        }
+        for (_, track) in converted.iter().filter(|(f, _)| f == folder) {
+            match art::extract_cover_art(&track.result.input, folder) {
+                Ok(Some(cover)) => {
+                    log_info!("Saved cover art from {:?} to {:?}", track.result.input, cover);
+                    break;
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    log_warn!("Failed to extract cover art from {:?}: {:?}", track.result.input, e);
+                }
+            }
+        }
+    }
+}
+
+fn write_conversion_logs(converted: &[(PathBuf, ConvertedTrack)]) {
+    for folder in unique_folders(converted.iter().map(|(folder, _)| folder)) {
+        let reports: Vec<&TrackReport> = converted
+            .iter()
+            .filter(|(f, _)| f == folder)
+            .filter_map(|(_, track)| track.report.as_ref())
+            .collect();
+        if let Err(e) = logging::write_conversion_report(folder, &reports) {
+            log_error!("Failed to write conversion log for {:?}: {:?}", folder, e);
+        }
+    }
+}
+
+pub(crate) fn check_resampling_allowed(stream_info: &StreamInfo, options: &ConversionOptions) -> Result<()> {
+    if options.no_resample && stream_info.sample_rate != options.target_rate {
+        return Err(anyhow::anyhow!("source is {} Hz, resampling disabled", stream_info.sample_rate));
+    }
+    if options.no_upsample && stream_info.sample_rate < options.target_rate {
+        return Err(anyhow::anyhow!(
+            "source is {} Hz, below the {} Hz output; upsampling disabled",
+            stream_info.sample_rate,
+            options.target_rate
+        ));
+    }
+    Ok(())
+}
+
+// The CUE sheet an earlier run left in `folder`, to append to; None if there is none yet.
+pub fn existing_project(folder: &Path) -> Result<Option<cue::CueSheet>> {
+    let path = folder.join(CUE_SHEET_NAME);
+    if !path.exists() {
+        return Ok(None);
+    }
+    cue::read_track_sheet(&path).map(Some)
+}
 
-        for file_path in files_to_process {
-            if *cancel_flag.lock().unwrap() {
-                log_info!("Conversion cancelled by user before processing file: {:?}", file_path);
-                return;
+// One CUE sheet per output folder, listing its tracks in conversion order, after those already
+// in it when appending.
+fn write_cue_sheets(converted: &[(PathBuf, ConvertedTrack)], options: &ConversionOptions) {
+    for folder in unique_folders(converted.iter().map(|(folder, _)| folder)) {
+        let new_tracks: Vec<CueTrack> = converted
+            .iter()
+            .filter(|(f, _)| f == folder)
+            .map(|(_, track)| CueTrack {
+                file: track.result.output_path.clone(),
+                title: track.info.title.clone(),
+                performer: track.info.artist.clone(),
+                frames: track.result.cd_frames().saturating_sub(track.gap_frames),
+                pregap_frames: 0,
+                gap_frames: track.gap_frames,
+                isrc: track.info.isrc.clone(),
+            })
+            .collect();
+        let mut tracks = Vec::new();
+        let mut project_catalog = None;
+        if options.appends_to_project() {
+            match existing_project(folder) {
+                Ok(Some(sheet)) => {
+                    // A track written again in this run is listed once, in its new place
+                    tracks.extend(
+                        sheet.tracks.into_iter().filter(|old| !new_tracks.iter().any(|new| same_name(&old.file, &new.file))),
+                    );
+                    log_info!("Appending {} tracks to the {} in {:?}", new_tracks.len(), tracks.len(), folder);
+                    project_catalog = sheet.catalog;
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    log_error!("Not updating the CUE sheet in {:?}, it can't be read: {:?}", folder, e);
+                    continue;
+                }
             }
+        }
+        tracks.extend(new_tracks);
+        // Track files hold no lead-in, so the burner generates track 1's pregap, less any the file has
+        for (index, track) in tracks.iter_mut().enumerate() {
+            track.pregap_frames = if index == 0 { options.first_track_pregap_frames.saturating_sub(track.gap_frames) } else { 0 };
+        }
+        // The one entered for the batch, else the one the disc already has, else the release's own from the tags
+        let catalog = options.cue_catalog.clone().or(project_catalog).or_else(|| {
+            converted
+                .iter()
+                .filter(|(f, _)| f == folder)
+                .find_map(|(_, track)| track.info.catalog.clone())
+        });
+
+        if let Err(e) = cue::write_cue_sheet(&tracks, catalog.as_deref(), &folder.join(CUE_SHEET_NAME)) {
+            log_error!("Failed to write CUE sheet for {:?}: {:?}", folder, e);
+        }
+    }
+}
+
+fn convert_with_ffmpeg(
+    input_path: &Path,
+    output_path: &Path,
+    stream_info: &StreamInfo,
+    cancel_flag: &Arc<AtomicBool>,
+    pause_flag: &Arc<AtomicBool>,
+    options: &ConversionOptions,
+    report_progress: &dyn Fn(u64, u64),
+) -> Result<ConvertedTrack> {
+    // A WAV input converted into its own folder would be read and overwritten by the same ffmpeg run
+    if is_same_file(input_path, output_path) {
+        return Err(anyhow::anyhow!(
+            "Output {:?} would overwrite its input; choose a different output folder",
+            output_path
+        ));
+    }
 
-            let start_time = std::time::Instant::now();
-            log_info!("Starting conversion of: {:?}", file_path);
-            if let Err(e) = convert_with_ffmpeg(&file_path, &output_folder, &cancel_flag) {
-                log_error!("Failed to convert {}: {:?}", file_path.display(), e);
-            } else {
-                log_info!("Conversion completed in {:.2}s: {:?}", start_time.elapsed().as_secs_f32(), file_path);
+    // Written under a temporary name and renamed once complete, so a crash or power cut never
+    // leaves a half-written file under the final name
+    let partial = partial_path(output_path);
+    let mut command = ffmpeg_command(input_path, stream_info, options)?;
+    // Only the tags we parsed, so INFO chunk contents don't depend on the source container
+    command.args(["-map_metadata", "-1"]);
+    command.args(stream_info.track.ffmpeg_metadata_args());
+    let channels = if options.writes_cdda() {
+        2
+    } else {
+        options.channel_mode.output_channels(stream_info.channels)
+    };
+    command.args([
+        "-acodec", options.output_format.codec(options.output_container),
+        "-ac", &channels.to_string(),
+        "-ar", &options.target_rate.to_string(),
+    ]);
+    let mut duration = stream_info.duration;
+    if let Some(seconds) = options.preview_seconds {
+        // ffmpeg stops once this much has been written and still finalizes the WAV header
+        command.args(["-t", &format!("{:.3}", seconds)]);
+        duration = Some(duration.map_or(seconds, |total| total.min(seconds)));
+    }
+    command.args([
+        "-f", options.output_container.ffmpeg_format(),
+        "-y", // Overwrite output files without asking
+    ]);
+    command.arg(&partial);
+    // Counted before padding, so it is the audio ffmpeg actually wrote
+    let decoded = if passes_through(input_path, stream_info, options) {
+        log_info!("{:?} is already CDDA; passing it through unchanged", input_path);
+        fs::copy(input_path, &partial)
+            .map(|_| ())
+            .with_context(|| format!("Failed to copy {:?} to {:?}", input_path, partial))
+    } else {
+        run_ffmpeg(command, input_path, duration, cancel_flag, pause_flag, report_progress)
+    };
+    let finished = keep_partial_output(decoded, input_path, &partial, options).and_then(|()| {
+        // Before padding, which adds samples the input doesn't have
+        if copies_bit_perfect(stream_info, options) && options.preview_seconds.is_none() {
+            check_bit_perfect(input_path, &partial)?;
+        }
+        let mut frames_written = wav::read_wav_info(&partial)?
+            .sample_frames()
+            .ok_or_else(|| anyhow::anyhow!("ffmpeg wrote no fmt chunk to {:?}", partial))?;
+        if frames_written == 0 {
+            return Err(anyhow::anyhow!("{:?} decoded to no audio at all", input_path));
+        }
+        let padding = if options.writes_cdda() {
+            // A preview is short on purpose and never burned
+            let min_frames = match options.short_tracks {
+                ShortTrackPolicy::Pad if options.preview_seconds.is_none() => cue::MIN_TRACK_FRAMES,
+                _ => 0,
+            };
+            let padding = wav::pad_to_frames(&partial, min_frames).context("Failed to pad output to a CD sector boundary")?;
+            if frames_written < min_frames * SAMPLES_PER_CD_FRAME {
+                log_info!("Padded {:?} with silence to the 4 s CD minimum", output_path);
+                frames_written = min_frames * SAMPLES_PER_CD_FRAME;
             }
+            padding
+        } else {
+            0
+        };
+        if options.existing_outputs == ExistingOutputPolicy::NumberedBackup && options.preview_seconds.is_none() {
+            backup_existing(output_path)?;
         }
+        fs::rename(&partial, output_path).with_context(|| format!("Failed to rename {:?} to {:?}", partial, output_path))?;
+        Ok((frames_written, padding))
     });
+    let (frames_written, padding) = match finished {
+        Ok(written) => written,
+        Err(e) => {
+            // Whatever was written is incomplete; an earlier output under the final name is left as it was
+            if partial.exists() {
+                match fs::remove_file(&partial) {
+                    Ok(()) => {
+                        log_info!("Removed incomplete output {:?}", partial);
+                    }
+                    Err(remove_error) => {
+                        log_warn!("Failed to remove incomplete output {:?}: {}", partial, remove_error);
+                    }
+                }
+            }
+            return Err(e);
+        }
+    };
+    log_debug!("Padded {:?} with {} bytes of silence to a whole CD sector", output_path, padding);
 
-    log_info!("Conversion process complete!");
+    let signal = wav::read_wav_info(output_path).ok().and_then(|info| measure_signal(output_path, &info));
+    let result = TrackResult {
+        input: input_path.to_path_buf(),
+        output_path: output_path.to_path_buf(),
+        frames_written,
+        channels,
+        sample_rate: options.target_rate,
+        source_rate: stream_info.sample_rate,
+        signal,
+    };
+    Ok(ConvertedTrack {
+        report: track_report(&result, stream_info),
+        result,
+        info: stream_info.track.clone(),
+        gap_frames: stream_info.gap_frames(),
+    })
+}
+
+// Also read back right after writing; a second pass over the cached data costs little next to decoding.
+fn measure_signal(output_path: &Path, info: &wav::WavInfo) -> Option<SignalStats> {
+    match wav::data_stats(output_path, info) {
+        Ok(signal) => {
+            let problems = signal.problems();
+            if !problems.is_empty() {
+                log_warn!("{:?} {}", output_path, problems.join("; "));
+            }
+            Some(signal)
+        }
+        Err(e) => {
+            log_warn!("Could not measure the level of {:?}: {:?}", output_path, e);
+            None
+        }
+    }
+}
+
+// Read back right after writing, while the data is still in the page cache.
+// A missing report only leaves the track out of conversion.log.
+fn track_report(track: &TrackResult, stream_info: &StreamInfo) -> Option<TrackReport> {
+    let output_path = &track.output_path;
+    let build = || -> Result<TrackReport> {
+        let info = wav::read_wav_info(output_path)?;
+        Ok(TrackReport {
+            source: track.input.clone(),
+            source_size: fs::metadata(&track.input)?.len(),
+            sample_rate: stream_info.sample_rate,
+            output_sample_rate: track.sample_rate,
+            output: output_path.clone(),
+            duration_seconds: track.duration_seconds(),
+            signal: track.signal,
+            crc32: wav::data_crc32(output_path, &info)?,
+        })
+    };
+    match build() {
+        Ok(report) => Some(report),
+        Err(e) => {
+            log_warn!("No conversion log entry for {:?}: {:?}", output_path, e);
+            None
+        }
+    }
+}
+
+// The decoding side of every conversion: input plus the filter chain ending in 44.1 kHz/16-bit.
+// Callers append the output format and path.
+pub(crate) fn ffmpeg_command(input_path: &Path, stream_info: &StreamInfo, options: &ConversionOptions) -> Result<Command> {
+    log_debug!(
+        "Source stream for {:?}: {} Hz, {} channels ({})",
+        input_path,
+        stream_info.sample_rate,
+        stream_info.channels,
+        stream_info.channel_layout.as_deref().unwrap_or("unknown layout")
+    );
+
+    if copies_bit_perfect(stream_info, options) {
+        log_info!("Copying {:?} bit for bit, without any filters", input_path);
+        let mut command = Command::new("ffmpeg");
+        command.args(["-fflags", "+discardcorrupt", "-i"]).arg(input_path);
+        return Ok(command);
+    }
+
+    // Peak memory: every filter below streams frame by frame, so ffmpeg holds a few hundred KB
+    // of buffers however long the track is. Analyses that need the whole signal (loudness,
+    // silence edges) run as separate decode passes that keep only their measurements.
+    let mut filters = Vec::new();
+    if let Some(downmix) = downmix_filter(stream_info.channels, stream_info.channel_layout.as_deref()) {
+        log_info!("Downmixing {} channels to stereo for: {:?}", stream_info.channels, input_path);
+        filters.push(downmix);
+    }
+    // Rate stage, in float so nothing is rounded before the final quantization
+    let rate = options.target_rate;
+    filters.push(format!(
+        "aresample=osr={}:osf=flt:{}",
+        rate,
+        options.resample_quality.swr_options(stream_info.sample_rate, rate)
+    ));
+    if let Some(balance) = balance_filter(options, Some(stream_info.channels)) {
+        log_info!("Trimming left by {:+.1} dB, right by {:+.1} dB: {:?}", options.left_gain_db, options.right_gain_db, input_path);
+        filters.push(balance);
+    }
+    // Ahead of the cuts, so the filter has settled by the first sample kept
+    if options.remove_dc {
+        filters.push(format!("highpass=f={}:poles=1", DC_HIGHPASS_HZ));
+    }
+    // Split points are whole CD frames, cut by sample so at 44.1 kHz they land on sector boundaries
+    if let Some(segment) = stream_info.segment {
+        let frame_samples = |frame: u64| frame * u64::from(rate) / cue::CD_FRAMES_PER_SECOND;
+        let end = segment
+            .end_frame
+            .map(|frame| format!(":end_sample={}", frame_samples(frame)))
+            .unwrap_or_default();
+        filters.push(format!("atrim=start_sample={}{}", frame_samples(segment.start_frame), end));
+        filters.push("asetpts=PTS-STARTPTS".to_string());
+    }
+    // Seeking only gets near the start; the exact cut is by sample, after the pre-roll has primed the decoder
+    let range_seek = stream_info.range.map(|range| (range.start.as_secs_f64() - SEEK_PREROLL_SECONDS).max(0.0));
+    if let (Some(range), Some(seek)) = (stream_info.range, range_seek) {
+        let to_sample = |seconds: f64| ((seconds - seek) * f64::from(rate)).round() as u64;
+        let end = range
+            .end
+            .map(|end| format!(":end_sample={}", to_sample(end.as_secs_f64())))
+            .unwrap_or_default();
+        filters.push(format!("atrim=start_sample={}{}", to_sample(range.start.as_secs_f64()), end));
+        filters.push("asetpts=PTS-STARTPTS".to_string());
+    }
+    filters.extend(edge_filters(input_path, stream_info, options)?);
+    // For a split track this is the gain of the whole input, so the parts keep their relative levels
+    if options.normalize {
+        let gain = match options.gain_mode {
+            GainMode::Track => normalization_gain(input_path, stream_info)?,
+            GainMode::Album => stream_info.album_gain,
+        };
+        // Measured on the source, before the channel trims, which would otherwise move the result off target
+        if let Some(gain) = gain.map(|gain| gain - balance_loudness_db(options)) {
+            log_info!("Applying {:+.2} dB {} gain to: {:?}", gain, options.gain_mode.label().to_lowercase(), input_path);
+            filters.push(format!("volume={:.2}dB", gain));
+        }
+    }
+    // Last step before quantizing so it also catches gain added above. Auto-level is off, or the
+    // limiter would raise quiet tracks up to the ceiling.
+    if options.limit_peaks {
+        filters.push(limiter_filter(options));
+    }
+    let requantizes = requantizes(stream_info, options);
+    if !requantizes && options.output_format.is_cdda() && options.dither != DitherMode::None {
+        log_info!("{:?} is already 16-bit and nothing alters its samples; not dithering it", input_path);
+    }
+    filters.push(quantize_filter(options, requantizes));
+
+    log_info!("Initiating ffmpeg conversion for: {:?}", input_path);
+    let mut command = Command::new("ffmpeg");
+    if let Some(seek) = range_seek {
+        // Before -i, so the demuxer jumps there instead of decoding the whole way
+        command.args(["-ss", &format!("{:.6}", seek)]);
+    }
+    // Packets the demuxer flags as corrupt are dropped rather than decoded into noise; frames that
+    // fail to decode are skipped by ffmpeg itself, and counted from its messages in run_ffmpeg
+    command.args(["-fflags", "+discardcorrupt"]);
+    command.arg("-i").arg(input_path);
+    command.args(["-af", &filters.join(",")]);
+    Ok(command)
+}
+
+// Scales each channel by its trim; a mono source is spread to both sides first. With no known
+// channel count (standard input) the stream is made stereo before the trims apply.
+fn balance_filter(options: &ConversionOptions, source_channels: Option<u16>) -> Option<String> {
+    if options.left_gain_db == 0.0 && options.right_gain_db == 0.0 {
+        return None;
+    }
+    let left = 10f64.powf(options.left_gain_db / 20.0);
+    let right = 10f64.powf(options.right_gain_db / 20.0);
+    Some(match source_channels {
+        Some(1) => format!("pan=stereo|c0={:.6}*c0|c1={:.6}*c0", left, right),
+        Some(_) => format!("pan=stereo|c0={:.6}*c0|c1={:.6}*c1", left, right),
+        None => format!("aformat=channel_layouts=stereo,pan=stereo|c0={:.6}*c0|c1={:.6}*c1", left, right),
+    })
+}
+
+// How much the channel trims change the loudness of a signal with equal energy on both sides
+fn balance_loudness_db(options: &ConversionOptions) -> f64 {
+    let power = |db: f64| 10f64.powf(db / 10.0);
+    10.0 * ((power(options.left_gain_db) + power(options.right_gain_db)) / 2.0).log10()
+}
+
+fn limiter_filter(options: &ConversionOptions) -> String {
+    format!(
+        "alimiter=limit={:.6}:attack={}:release={}:level=disabled",
+        10f64.powf(options.peak_ceiling_db / 20.0),
+        LIMITER_LOOKAHEAD_MS,
+        LIMITER_RELEASE_MS
+    )
+}
+
+// Quantize stage. Only 16-bit output is dithered; 24-bit and float keep the extra resolution
+// for later processing. Without `requantizes` the samples are already 16-bit ones and only
+// change format, which dither would just add noise to.
+fn quantize_filter(options: &ConversionOptions, requantizes: bool) -> String {
+    let dither = if options.output_format.is_cdda() && requantizes { options.dither } else { DitherMode::None };
+    format!("aresample=osf={}:dither_method={}", options.output_format.sample_format(), dither.ffmpeg_method())
+}
+
+// Silence trimming and fades. Where the tail is involved, its position comes from a separate
+// silence scan, so the conversion itself can cut and fade at fixed times while streaming.
+// Split tracks already start and end at the cut points, so they are only faded.
+fn edge_filters(input_path: &Path, stream_info: &StreamInfo, options: &ConversionOptions) -> Result<Vec<String>> {
+    let mut filters = Vec::new();
+    let curve = options.fade_curve.ffmpeg_curve();
+    let trim = options.trim_silence && stream_info.segment.is_none() && stream_info.range.is_none();
+    // A split track's length is known unless it runs to the end of its input
+    let known_length = match (stream_info.segment, stream_info.range) {
+        (Some(segment), _) => segment.end_seconds().map(|end| end - segment.start_seconds()),
+        (None, Some(range)) => range.length(),
+        (None, None) => None,
+    };
+    if !trim && (options.fade_out_seconds <= 0.0 || known_length.is_some()) {
+        if options.fade_in_seconds > 0.0 {
+            filters.push(format!("afade=t=in:st=0:d={:.3}:curve={}", options.fade_in_seconds, curve));
+        }
+        if let (Some(length), true) = (known_length, options.fade_out_seconds > 0.0) {
+            let fade = options.fade_out_seconds.min(length);
+            filters.push(format!("afade=t=out:st={:.6}:d={:.3}:curve={}", length - fade, fade, curve));
+        }
+        return Ok(filters);
+    }
+
+    // Without trimming the scan is only needed for the decoded length, which headers can get wrong
+    let scan = silence::scan_silence(input_path, options.silence_threshold_db, silence::EDGE_SILENCE_SECONDS)?;
+    let (start, end) = match (stream_info.segment, stream_info.range) {
+        (Some(segment), _) => (0.0, scan.duration - segment.start_seconds()),
+        (None, Some(range)) => (0.0, scan.duration - range.start.as_secs_f64()),
+        _ if trim => (scan.audio_start(), scan.audio_end()),
+        _ => (0.0, scan.duration),
+    };
+    if trim {
+        log_info!(
+            "Trimming {:.3}s of leading and {:.3}s of trailing silence from: {:?}",
+            start,
+            scan.duration - end,
+            input_path
+        );
+        filters.push(format!("atrim=start={:.6}:end={:.6}", start, end));
+        filters.push("asetpts=PTS-STARTPTS".to_string());
+    }
+    // Fades go after trimming so they start on the audio, not on the removed silence
+    if options.fade_in_seconds > 0.0 {
+        filters.push(format!("afade=t=in:st=0:d={:.3}:curve={}", options.fade_in_seconds, curve));
+    }
+    if options.fade_out_seconds > 0.0 {
+        let length = end - start;
+        let fade = options.fade_out_seconds.min(length);
+        filters.push(format!("afade=t=out:st={:.6}:d={:.3}:curve={}", length - fade, fade, curve));
+    }
+    Ok(filters)
+}
+
+// An input that is already 16-bit 44.1 kHz stereo PCM WAV would only lose a generation to another
+// dither, so it is copied as it is when nothing else asks to change the audio. It is still
+// padded to a sector afterwards like any other output.
+fn passes_through(input_path: &Path, stream_info: &StreamInfo, options: &ConversionOptions) -> bool {
+    let changes_audio = changes_audio(stream_info, options) || options.preview_seconds.is_some();
+    if options.force_reencode || changes_audio || !options.writes_cdda() || options.output_container != OutputContainer::Wav {
+        return false;
+    }
+    let is_wav = input_path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("wav"));
+    // Padding appends to the data chunk, so it has to be last unless no padding is needed
+    is_wav
+        && wav::read_wav_info(input_path).is_ok_and(|info| {
+            info.format.as_ref().is_some_and(wav::WavFormat::is_cdda)
+                && (info.data_offset + info.data_len == info.file_len || info.data_len % wav::CD_SECTOR_BYTES == 0)
+        })
+}
+
+// Whether any option alters the samples, beyond the format change every conversion makes
+fn changes_audio(stream_info: &StreamInfo, options: &ConversionOptions) -> bool {
+    options.normalize
+        || options.trim_silence
+        || options.limit_peaks
+        || options.remove_dc
+        || options.left_gain_db != 0.0
+        || options.right_gain_db != 0.0
+        || options.fade_in_seconds > 0.0
+        || options.fade_out_seconds > 0.0
+        || stream_info.segment.is_some()
+        || stream_info.range.is_some()
+}
+
+// Whether the samples reaching the quantize stage can fall between 16-bit values. A 16-bit integer
+// source at the output rate that is neither mixed nor has its level changed goes through the float
+// stages and back exactly; cutting (splits, ranges, trimming) moves no sample values.
+fn requantizes(stream_info: &StreamInfo, options: &ConversionOptions) -> bool {
+    let sixteen_bit = matches!(stream_info.sample_format.as_deref(), Some("s16" | "s16p" | "u8" | "u8p"));
+    let output_channels = if options.writes_cdda() { 2 } else { options.channel_mode.output_channels(stream_info.channels) };
+    let mixes = stream_info.channels > 2 || output_channels < stream_info.channels;
+    let changes_level = options.normalize
+        || options.limit_peaks
+        || options.remove_dc
+        || options.left_gain_db != 0.0
+        || options.right_gain_db != 0.0
+        || options.fade_in_seconds > 0.0
+        || options.fade_out_seconds > 0.0;
+    !sixteen_bit || stream_info.sample_rate != options.target_rate || mixes || changes_level
+}
+
+// Lossless codecs whose 16-bit streams decode to exactly the samples that were encoded
+const LOSSLESS_CODECS: &[&str] = &["flac", "alac", "wavpack", "tta", "ape", "pcm_s16le", "pcm_s16be"];
+
+// A 16-bit 44.1 kHz stereo lossless source is already CD audio sample for sample, so with no
+// filters ffmpeg only repacks it. Lossy decoders produce float, which never qualifies.
+fn copies_bit_perfect(stream_info: &StreamInfo, options: &ConversionOptions) -> bool {
+    options.bit_perfect
+        && options.writes_cdda()
+        && stream_info.sample_rate == 44100
+        && stream_info.channels == 2
+        && matches!(stream_info.sample_format.as_deref(), Some("s16" | "s16p"))
+        && stream_info.codec_name.as_deref().is_some_and(|codec| LOSSLESS_CODECS.contains(&codec))
+        && !changes_audio(stream_info, options)
+}
+
+// Compares the decoded audio of both files by ffmpeg's MD5 of their 16-bit samples
+fn check_bit_perfect(input_path: &Path, output_path: &Path) -> Result<()> {
+    let (input, output) = (pcm_md5(input_path)?, pcm_md5(output_path)?);
+    if input != output {
+        return Err(anyhow::anyhow!(
+            "{:?} doesn't decode to the same samples as {:?} (MD5 {} against {})",
+            output_path,
+            input_path,
+            output,
+            input
+        ));
+    }
+    log_info!("Verified {:?} is a bit-perfect copy of {:?} (MD5 {})", output_path, input_path, output);
+    Ok(())
+}
+
+fn pcm_md5(path: &Path) -> Result<String> {
+    let output = Command::new("ffmpeg")
+        .args(["-v", "error", "-i"])
+        .arg(path)
+        .args(["-map", "0:a:0", "-c:a", "pcm_s16le", "-f", "md5", "-"])
+        .output()
+        .context("Failed to run ffmpeg for an MD5 of the audio")?;
+    if !output.status.success() {
+        return Err(DecodeError::new("ffmpeg", &String::from_utf8_lossy(&output.stderr)).into());
+    }
+    String::from_utf8_lossy(&output.stdout)
+        .trim()
+        .strip_prefix("MD5=")
+        .map(str::to_string)
+        .ok_or_else(|| anyhow::anyhow!("No MD5 in ffmpeg's output for {:?}", path))
+}
+
+// A file that breaks off partway still gives the audio before the break, which is usually most of
+// a track; that beats failing it outright.
+fn keep_partial_output(decoded: Result<()>, input_path: &Path, output_path: &Path, options: &ConversionOptions) -> Result<()> {
+    let Err(e) = decoded else {
+        return Ok(());
+    };
+    let truncated = e
+        .downcast_ref::<DecodeError>()
+        .is_some_and(|failure| failure.problem == Some(DecodeProblem::Truncated));
+    let recovered = wav::read_wav_info(output_path).ok().and_then(|info| info.sample_frames()).unwrap_or(0);
+    if !truncated || recovered == 0 {
+        return Err(e);
+    }
+    log_warn!(
+        "{:?} is truncated; kept the first {} that decoded: {}",
+        input_path,
+        crate::disc::format_minutes(recovered as f64 / f64::from(options.target_rate)),
+        e
+    );
     Ok(())
 }
 
-fn convert_with_ffmpeg(input_path: &Path, output_dir: &Path, cancel_flag: &Arc<Mutex<bool>>) -> Result<()> {
-    let output_filename = input_path
-        .file_stem()
-        .ok_or_else(|| anyhow::anyhow!("Invalid input filename"))?
-        .to_string_lossy()
-        .into_owned() + ".wav";
-    let output_path = output_dir.join(output_filename);
+// What is kept of ffmpeg's messages during a conversion
+const STDERR_LOG_BYTES: usize = 64 * 1024;
+
+// Why ffmpeg or ffprobe could not read an input, worked out from the messages they print
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DecodeProblem {
+    // No decoder for the codec, or the audio is encrypted (DRM)
+    Unsupported,
+    // The audio breaks off or is corrupt partway through
+    Truncated,
+    // The container or stream header can't be parsed at all
+    MalformedHeader,
+}
+
+impl DecodeProblem {
+    pub fn reason(self) -> &'static str {
+        match self {
+            DecodeProblem::Unsupported => "Unsupported or copy-protected audio format",
+            DecodeProblem::Truncated => "File is truncated or damaged",
+            DecodeProblem::MalformedHeader => "File header is malformed or not an audio file",
+        }
+    }
+
+    // Checked in this order, as a damaged stream also fails with "Invalid data" once demuxing gives up
+    fn classify(stderr: &str) -> Option<DecodeProblem> {
+        const UNSUPPORTED: &[&str] = &["decoder (codec", "unknown codec", "unsupported codec", "could not find codec parameters", "encrypt", "drm"];
+        const TRUNCATED: &[&str] = &[
+            "header missing",
+            "packet corrupt",
+            "truncat",
+            "partial file",
+            "error while decoding",
+            "error during demuxing",
+            "invalid frame",
+            "overread",
+            "premature end",
+        ];
+        const MALFORMED: &[&str] = &["invalid data found when processing input", "moov atom not found", "detected only with low score", "could not find sync"];
+        let stderr = stderr.to_ascii_lowercase();
+        let found = |patterns: &[&str]| patterns.iter().any(|pattern| stderr.contains(pattern));
+        if found(UNSUPPORTED) {
+            Some(DecodeProblem::Unsupported)
+        } else if found(TRUNCATED) {
+            Some(DecodeProblem::Truncated)
+        } else if found(MALFORMED) {
+            Some(DecodeProblem::MalformedHeader)
+        } else {
+            None
+        }
+    }
+}
+
+// A failed ffmpeg or ffprobe run, shown as a reason a user can act on when it is recognized
+#[derive(Debug)]
+pub struct DecodeError {
+    pub problem: Option<DecodeProblem>,
+    tool: &'static str,
+    stderr: String,
+}
+
+impl DecodeError {
+    fn new(tool: &'static str, stderr: &str) -> Self {
+        Self {
+            problem: DecodeProblem::classify(stderr),
+            tool,
+            stderr: stderr.trim().to_string(),
+        }
+    }
+}
+
+impl std::fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.problem {
+            // ffmpeg's last line is the most specific; the rest is in the log
+            Some(problem) => write!(f, "{} ({})", problem.reason(), self.stderr.lines().last().unwrap_or_default().trim()),
+            None if self.stderr.is_empty() => write!(f, "{} failed", self.tool),
+            None => write!(f, "{} failed: {}", self.tool, self.stderr),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
 
-    log_info!("Initiating ffmpeg conversion for: {:?}", input_path);
-    let mut child = Command::new("ffmpeg")
-        .args(&[
-            "-i", input_path.to_str().unwrap(),
-            "-acodec", "pcm_s16le",
-            "-ac", "2",
-            "-ar", "44100",
-            "-y", // Overwrite output files without asking
-            output_path.to_str().unwrap(),
-        ])
+pub(crate) fn run_ffmpeg(
+    command: Command,
+    input_path: &Path,
+    duration: Option<f64>,
+    cancel_flag: &Arc<AtomicBool>,
+    pause_flag: &Arc<AtomicBool>,
+    report_progress: &dyn Fn(u64, u64),
+) -> Result<()> {
+    run_ffmpeg_fed(command, None, input_path, duration, cancel_flag, pause_flag, report_progress)
+}
+
+// run_ffmpeg for a command reading pipe:0, with `stdin` copied into it from a thread of its own
+fn run_ffmpeg_fed(
+    mut command: Command,
+    stdin: Option<Box<dyn Read + Send>>,
+    input_path: &Path,
+    duration: Option<f64>,
+    cancel_flag: &Arc<AtomicBool>,
+    pause_flag: &Arc<AtomicBool>,
+    report_progress: &dyn Fn(u64, u64),
+) -> Result<()> {
+    if stdin.is_some() {
+        command.stdin(std::process::Stdio::piped());
+    }
+    let mut child = command
         .stdout(std::process::Stdio::piped())
         .stderr(std::process::Stdio::piped())
         .spawn()
         .context("Failed to spawn ffmpeg process")?;
+    if let (Some(mut reader), Some(mut pipe)) = (stdin, child.stdin.take()) {
+        // Not joined: once ffmpeg exits the copy fails on the closed pipe, but it may first sit
+        // waiting on a reader that has nothing more to give
+        thread::spawn(move || {
+            if let Err(e) = std::io::copy(&mut reader, &mut pipe) {
+                // ffmpeg closing its end early, e.g. on a cancel or bad input, is reported by ffmpeg
+                log_debug!("Stopped feeding ffmpeg: {}", e);
+            }
+        });
+    }
 
     let mut progress_logged = false;
-    while !child.try_wait()?.is_some() {
-        if *cancel_flag.lock().unwrap() {
+    // Kept for classifying a failure; progress lines would grow it without bound, so only the tail stays
+    let mut stderr_log = String::new();
+    let mut position = 0.0;
+    let mut damaged_at = None;
+    let mut skipped = SkippedFrames::default();
+    while child.try_wait()?.is_none() {
+        if cancel_flag.load(Ordering::Relaxed) {
             log_info!("Cancelling ffmpeg process for: {:?}", input_path);
             child.kill().context("Failed to kill ffmpeg process")?;
             return Err(anyhow::anyhow!("Conversion cancelled for {:?}", input_path));
         }
+        if cfg!(unix) && pause_flag.load(Ordering::Relaxed) {
+            log_info!("Pausing ffmpeg process for: {:?}", input_path);
+            set_suspended(&child, true);
+            wait_while_paused(pause_flag, cancel_flag);
+            set_suspended(&child, false);
+            log_info!("Resuming ffmpeg process for: {:?}", input_path);
+            continue;
+        }
 
         if let Some(stderr) = child.stderr.as_mut() {
             let mut buffer = [0; 1024];
@@ -113,6 +2192,20 @@ fn convert_with_ffmpeg(input_path: &Path, output_dir: &Path, cancel_flag: &Arc<M
                         log_info!("ffmpeg progress for {:?}: {}", input_path, output);
                         progress_logged = true; // Log progress once to avoid spam
                     }
+                    if let Some(time) = parse_ffmpeg_time(&output) {
+                        position = time;
+                        report_progress(seconds_to_millis(position), duration.map_or(0, seconds_to_millis));
+                    }
+                    if damaged_at.is_none() && DecodeProblem::classify(&output) == Some(DecodeProblem::Truncated) {
+                        damaged_at = Some(position);
+                    }
+                    skipped.feed(&output);
+                    stderr_log.push_str(&output);
+                    if stderr_log.len() > STDERR_LOG_BYTES {
+                        let cut = stderr_log.len() - STDERR_LOG_BYTES / 2;
+                        let cut = (cut..stderr_log.len()).find(|&i| stderr_log.is_char_boundary(i)).unwrap_or(0);
+                        stderr_log.drain(..cut);
+                    }
                 }
             }
         }
@@ -120,16 +2213,1124 @@ fn convert_with_ffmpeg(input_path: &Path, output_dir: &Path, cancel_flag: &Arc<M
     }
 
     let status = child.wait().context("Failed to wait for ffmpeg process")?;
+    if let Some(mut stderr) = child.stderr.take() {
+        let mut rest = String::new();
+        let _ = stderr.read_to_string(&mut rest);
+        skipped.feed(&rest);
+        stderr_log.push_str(&rest);
+    }
+    skipped.feed("\n");
     if !status.success() {
-        let stderr = child.stderr.and_then(|mut s| {
-            let mut buf = String::new();
-            use std::io::Read;
-            let _ = s.read_to_string(&mut buf);
-            Some(buf)
-        }).unwrap_or_else(|| "No stderr available".to_string());
-        return Err(anyhow::anyhow!("ffmpeg failed: {}", stderr));
+        return Err(DecodeError::new("ffmpeg", &stderr_log).into());
+    }
+    if let Some(at) = damaged_at.or_else(|| (DecodeProblem::classify(&stderr_log) == Some(DecodeProblem::Truncated)).then_some(position)) {
+        log_warn!(
+            "{:?} is damaged from about {}; the audio there may be skipped or silent",
+            input_path,
+            crate::disc::format_minutes(at)
+        );
+    }
+    if skipped.frames > 0 {
+        log_warn!("Skipped {} damaged frames of {:?} and decoded the rest", skipped.frames, input_path);
     }
 
     log_debug!("ffmpeg output for {:?}: {:?}", input_path, child.stdout);
     Ok(())
-}
\ No newline at end of file
+}
+
+// Counts the frames and packets ffmpeg reports dropping, from its messages as they arrive in pieces
+#[derive(Default)]
+struct SkippedFrames {
+    frames: u64,
+    // Start of a line whose end hasn't arrived yet
+    partial_line: String,
+    // Whether the last complete line was a skipped frame, for ffmpeg's "Last message repeated N times"
+    last_was_skip: bool,
+}
+
+impl SkippedFrames {
+    fn feed(&mut self, output: &str) {
+        self.partial_line.push_str(output);
+        // ffmpeg ends progress lines with \r and messages with \n
+        while let Some(end) = self.partial_line.find(['\n', '\r']) {
+            let line: String = self.partial_line.drain(..=end).collect();
+            let line = line.trim().to_ascii_lowercase();
+            if line.is_empty() {
+                continue;
+            }
+            if let Some(repeats) = line
+                .strip_prefix("last message repeated ")
+                .and_then(|rest| rest.split_whitespace().next())
+                .and_then(|count| count.parse::<u64>().ok())
+            {
+                if self.last_was_skip {
+                    self.frames += repeats;
+                }
+                continue;
+            }
+            self.last_was_skip = ["error while decoding", "packet corrupt", "discarding corrupt", "header missing", "invalid frame"]
+                .iter()
+                .any(|message| line.contains(message));
+            if self.last_was_skip {
+                self.frames += 1;
+            }
+        }
+    }
+}
+
+// Runs `operation` again after errors reading `input` that may go away on their own. Decode and
+// format errors fail the same way every time, so they are returned straight away.
+pub(crate) fn with_io_retry<T>(input: &Path, options: &ConversionOptions, mut operation: impl FnMut() -> Result<T>) -> Result<T> {
+    let mut delay = std::time::Duration::from_millis(options.io_retry_delay_ms);
+    let mut retries = 0;
+    loop {
+        match operation() {
+            Err(e) if retries < options.io_retries && is_transient_io_error(&e) => {
+                retries += 1;
+                log_warn!("I/O error reading {:?}, retry {} of {} in {:?}: {:#}", input, retries, options.io_retries, delay, e);
+                std::thread::sleep(delay);
+                delay *= 2;
+            }
+            outcome => return outcome,
+        }
+    }
+}
+
+fn is_transient_io_error(error: &anyhow::Error) -> bool {
+    use std::io::ErrorKind;
+    if is_disk_full_error(error) {
+        return false;
+    }
+    error.chain().any(|cause| match cause.downcast_ref::<std::io::Error>() {
+        // These won't change by waiting
+        Some(io) => !matches!(
+            io.kind(),
+            ErrorKind::NotFound | ErrorKind::PermissionDenied | ErrorKind::InvalidData | ErrorKind::InvalidInput | ErrorKind::Unsupported
+        ),
+        // ffmpeg and ffprobe report a failed read (EIO) in their own output
+        None => cause.to_string().contains("Input/output error"),
+    })
+}
+
+// A full output volume fails every file after it too, so instead of failing this one the whole
+// batch is paused until the user has made room, then `operation` runs again from the start.
+pub(crate) fn with_disk_full_pause<T>(
+    output: &Path,
+    cancel_flag: &AtomicBool,
+    pause_flag: &AtomicBool,
+    progress: &dyn ProgressSink,
+    mut operation: impl FnMut() -> Result<T>,
+) -> Result<T> {
+    loop {
+        match operation() {
+            Err(e) if is_disk_full_error(&e) && !cancel_flag.load(Ordering::Relaxed) => {
+                // The failed attempt already removed its partial file; what is at `output` now is an
+                // earlier, complete one and stays
+                log_error!("Disk full writing {:?}; pausing until there is space: {:#}", output, e);
+                pause_flag.store(true, Ordering::Relaxed);
+                progress.on_disk_full(output);
+                wait_while_paused(pause_flag, cancel_flag);
+                if cancel_flag.load(Ordering::Relaxed) {
+                    return Err(e);
+                }
+                log_info!("Resumed; writing {:?} again", output);
+            }
+            outcome => return outcome,
+        }
+    }
+}
+
+fn is_disk_full_error(error: &anyhow::Error) -> bool {
+    error.chain().any(|cause| match cause.downcast_ref::<std::io::Error>() {
+        Some(io) => io.kind() == std::io::ErrorKind::StorageFull,
+        // ffmpeg's wording on Linux and macOS, and on Windows
+        None => {
+            let message = cause.to_string();
+            message.contains("No space left on device") || message.contains("not enough space on the disk")
+        }
+    })
+}
+
+// Parks a worker while the batch is paused. Returns early on cancel so a paused batch can still be stopped.
+pub(crate) fn wait_while_paused(pause_flag: &AtomicBool, cancel_flag: &AtomicBool) {
+    while pause_flag.load(Ordering::Relaxed) && !cancel_flag.load(Ordering::Relaxed) {
+        std::thread::sleep(std::time::Duration::from_millis(100));
+    }
+}
+
+// Stopped rather than killed, so ffmpeg carries on from the same packet with its decoder and
+// resampler state intact. A stopped process keeps its files open without locking them.
+#[cfg(unix)]
+fn set_suspended(child: &std::process::Child, suspended: bool) {
+    let signal = if suspended { libc::SIGSTOP } else { libc::SIGCONT };
+    // SAFETY: kill only sends a signal; the pid is our own child, which hasn't been waited on yet
+    if unsafe { libc::kill(child.id() as libc::pid_t, signal) } != 0 {
+        log_warn!("Failed to signal ffmpeg process {}: {}", child.id(), std::io::Error::last_os_error());
+    }
+}
+
+// Elsewhere a pause takes effect between files
+#[cfg(not(unix))]
+fn set_suspended(_child: &std::process::Child, _suspended: bool) {}
+
+#[derive(Clone)]
+pub(crate) struct StreamInfo {
+    pub(crate) sample_rate: u32,
+    pub(crate) channels: u16,
+    channel_layout: Option<String>,
+    codec_name: Option<String>,
+    // As ffmpeg decodes it, e.g. "s16" or "fltp"
+    sample_format: Option<String>,
+    // Seconds, when the container reports it
+    pub(crate) duration: Option<f64>,
+    pub(crate) track: TrackInfo,
+    replaygain_track_gain: Option<f64>,
+    replaygain_track_peak: Option<f64>,
+    replaygain_album_gain: Option<f64>,
+    replaygain_album_peak: Option<f64>,
+    // Album gain mode: the gain worked out for the whole album this track is part of
+    pub(crate) album_gain: Option<f64>,
+    // Set when this is one track split out of a longer input
+    pub(crate) segment: Option<Segment>,
+    // Set when only part of the input is converted; exclusive with segment
+    pub(crate) range: Option<TimeRange>,
+}
+
+impl StreamInfo {
+    pub(crate) fn for_segment(&self, segment: Segment, track: TrackInfo) -> StreamInfo {
+        let duration = match segment.end_seconds() {
+            Some(end) => Some(end - segment.start_seconds()),
+            None => self.duration.map(|total| total - segment.start_seconds()),
+        };
+        StreamInfo {
+            duration,
+            track,
+            // The input's ReplayGain describes the whole mix, not this part of it
+            replaygain_track_gain: None,
+            replaygain_track_peak: None,
+            segment: Some(segment),
+            ..self.clone()
+        }
+    }
+
+    fn gap_frames(&self) -> u64 {
+        self.segment.map_or(0, |segment| segment.gap_frames)
+    }
+
+    pub(crate) fn for_range(&self, range: TimeRange) -> StreamInfo {
+        let start = range.start.as_secs_f64();
+        let duration = range.length().or_else(|| self.duration.map(|total| (total - start).max(0.0)));
+        StreamInfo {
+            duration,
+            replaygain_track_gain: None,
+            replaygain_track_peak: None,
+            range: Some(range),
+            ..self.clone()
+        }
+    }
+}
+
+// Rate, channel count and length of a file's first audio stream, as its headers give them
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TrackSpec {
+    pub sample_rate: u32,
+    pub channels: u16,
+    // Sample frames per channel
+    pub frames: u64,
+}
+
+impl TrackSpec {
+    pub fn duration_seconds(&self) -> f64 {
+        self.frames as f64 / f64::from(self.sample_rate)
+    }
+}
+
+// Much quicker than probe_stream for large selections: it reads neither tags nor audio, only the
+// stream's length from the container (duration_ts), and decodes the file just to time it when
+// the container has no length at all, as raw MP3 streams without a Xing header may not.
+pub fn probe_spec(input_path: &Path) -> Result<TrackSpec> {
+    let output = Command::new("ffprobe")
+        .args(["-v", "error", "-select_streams", "a:0"])
+        .args(["-show_entries", "stream=sample_rate,channels,duration_ts,time_base:format=duration"])
+        .args(["-of", "default=noprint_wrappers=1"])
+        .arg(input_path)
+        .output()
+        .context("Failed to run ffprobe")?;
+    if !output.status.success() {
+        return Err(DecodeError::new("ffprobe", &String::from_utf8_lossy(&output.stderr)).into());
+    }
+
+    let (mut sample_rate, mut channels, mut duration_ts, mut time_base, mut duration) = (None, None, None, None, None);
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        match line.split_once('=') {
+            Some(("sample_rate", value)) => sample_rate = value.trim().parse::<u32>().ok(),
+            Some(("channels", value)) => channels = value.trim().parse::<u16>().ok(),
+            Some(("duration_ts", value)) => duration_ts = value.trim().parse::<u64>().ok(),
+            // e.g. "1/44100"
+            Some(("time_base", value)) => {
+                time_base = value
+                    .trim()
+                    .split_once('/')
+                    .and_then(|(num, den)| Some((num.parse::<u64>().ok()?, den.parse::<u64>().ok().filter(|den| *den > 0)?)))
+            }
+            Some(("duration", value)) => duration = value.trim().parse::<f64>().ok(),
+            _ => {}
+        }
+    }
+    let (Some(sample_rate), Some(channels)) = (sample_rate, channels) else {
+        return Err(anyhow::anyhow!("No audio stream found in {:?}", input_path));
+    };
+
+    let frames = match (duration_ts, time_base) {
+        (Some(ticks), Some((num, den))) => (u128::from(ticks) * u128::from(num) * u128::from(sample_rate) / u128::from(den)) as u64,
+        _ => {
+            let seconds = match duration {
+                Some(seconds) => seconds,
+                None => {
+                    log_debug!("{:?} has no length in its headers; decoding it to time it", input_path);
+                    decoded_duration(input_path)?
+                }
+            };
+            (seconds * f64::from(sample_rate)).round() as u64
+        }
+    };
+    Ok(TrackSpec { sample_rate, channels, frames })
+}
+
+// Containers without a duration header (e.g. raw MP3 streams) are decoded to the null muxer to time them.
+fn decoded_duration(input: &Path) -> Result<f64> {
+    let output = Command::new("ffmpeg")
+        .arg("-i")
+        .arg(input)
+        .args(["-vn", "-f", "null", "-"])
+        .output()
+        .context("Failed to run ffmpeg")?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!("ffmpeg could not decode {:?}", input));
+    }
+    parse_ffmpeg_time(&String::from_utf8_lossy(&output.stderr))
+        .ok_or_else(|| anyhow::anyhow!("ffmpeg reported no duration for {:?}", input))
+}
+
+pub(crate) fn probe_stream(input_path: &Path) -> Result<StreamInfo> {
+    let mut info = probe_headers(input_path)?;
+    // Mislabeled files (mostly VBR MP3s) can decode at a different rate than their header claims
+    match decoded_sample_rate(input_path) {
+        Some(decoded) if decoded != info.sample_rate => {
+            log_warn!(
+                "{:?} header says {} Hz but decodes at {} Hz; using the decoded rate",
+                input_path,
+                info.sample_rate,
+                decoded
+            );
+            info.sample_rate = decoded;
+        }
+        Some(_) => {}
+        None => {
+            log_debug!("Could not decode a frame of {:?} to check its sample rate", input_path);
+        }
+    }
+    Ok(info)
+}
+
+// probe_stream without decoding a frame to check the rate, for the dry run, which writes nothing
+fn probe_headers(input_path: &Path) -> Result<StreamInfo> {
+    let output = Command::new("ffprobe")
+        .args([
+            "-v", "error",
+            "-select_streams", "a:0",
+            "-show_entries", "stream=sample_rate,channels,channel_layout,codec_name,sample_fmt:stream_tags:format=duration:format_tags",
+            "-of", "default=noprint_wrappers=1",
+        ])
+        .arg(input_path)
+        .output()
+        .context("Failed to run ffprobe")?;
+    if !output.status.success() {
+        return Err(DecodeError::new("ffprobe", &String::from_utf8_lossy(&output.stderr)).into());
+    }
+
+    let mut sample_rate = None;
+    let mut channels = None;
+    let mut channel_layout = None;
+    let mut codec_name = None;
+    let mut sample_format = None;
+    let mut duration = None;
+    let mut track = TrackInfo::default();
+    let mut replaygain_track_gain = None;
+    let mut replaygain_track_peak = None;
+    let mut replaygain_album_gain = None;
+    let mut replaygain_album_peak = None;
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        if let Some((key, value)) = line.strip_prefix("TAG:").and_then(|tag| tag.split_once('=')) {
+            let value = value.trim();
+            match key.to_ascii_lowercase().as_str() {
+                // Stored as e.g. "-6.54 dB"
+                "replaygain_track_gain" => {
+                    replaygain_track_gain =
+                        replaygain_track_gain.or_else(|| value.trim_end_matches("dB").trim().parse().ok())
+                }
+                "replaygain_track_peak" => {
+                    replaygain_track_peak = replaygain_track_peak.or_else(|| value.parse().ok())
+                }
+                "replaygain_album_gain" => {
+                    replaygain_album_gain =
+                        replaygain_album_gain.or_else(|| value.trim_end_matches("dB").trim().parse().ok())
+                }
+                "replaygain_album_peak" => {
+                    replaygain_album_peak = replaygain_album_peak.or_else(|| value.parse().ok())
+                }
+                _ => track.apply_tag(key, value),
+            }
+            continue;
+        }
+        match line.split_once('=') {
+            Some(("sample_rate", value)) => sample_rate = value.trim().parse().ok(),
+            Some(("channels", value)) => channels = value.trim().parse().ok(),
+            Some(("duration", value)) => duration = value.trim().parse().ok(),
+            Some(("codec_name", value)) => codec_name = Some(value.trim().to_string()),
+            Some(("sample_fmt", value)) => sample_format = Some(value.trim().to_string()),
+            Some(("channel_layout", value)) if !value.trim().is_empty() && value.trim() != "unknown" => {
+                channel_layout = Some(value.trim().to_string())
+            }
+            _ => {}
+        }
+    }
+
+    let sample_rate = sample_rate.ok_or_else(|| anyhow::anyhow!("No audio stream found in {:?}", input_path))?;
+
+    Ok(StreamInfo {
+        sample_rate,
+        channels: channels.ok_or_else(|| anyhow::anyhow!("No audio stream found in {:?}", input_path))?,
+        channel_layout,
+        codec_name,
+        sample_format,
+        duration,
+        track,
+        replaygain_track_gain,
+        replaygain_track_peak,
+        replaygain_album_gain,
+        replaygain_album_peak,
+        album_gain: None,
+        segment: None,
+        range: None,
+    })
+}
+
+// Rate of the first decoded audio frame.
+fn decoded_sample_rate(input_path: &Path) -> Option<u32> {
+    let output = Command::new("ffprobe")
+        .args([
+            "-v", "error",
+            "-select_streams", "a:0",
+            "-read_intervals", "%+#1",
+            "-show_entries", "frame=sample_rate",
+            "-of", "default=noprint_wrappers=1:nokey=1",
+        ])
+        .arg(input_path)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    String::from_utf8_lossy(&output.stdout).lines().next()?.trim().parse().ok()
+}
+
+// Prefers existing ReplayGain tags and only scans the audio when they are missing.
+fn track_loudness(input_path: &Path, stream_info: &StreamInfo) -> Result<Loudness> {
+    match stream_info.replaygain_track_gain {
+        Some(gain) => {
+            log_debug!("Using ReplayGain tags for: {:?}", input_path);
+            Ok(Loudness::from_replaygain(gain, stream_info.replaygain_track_peak))
+        }
+        None => {
+            log_info!("Measuring loudness of: {:?}", input_path);
+            loudness::measure_loudness(input_path)
+        }
+    }
+}
+
+fn normalization_gain(input_path: &Path, stream_info: &StreamInfo) -> Result<Option<f64>> {
+    let loudness = track_loudness(input_path, stream_info)?;
+    let gain = loudness.gain_to(loudness::TARGET_LUFS);
+    if gain.is_none() {
+        log_warn!("Could not determine a normalization gain for {:?}; leaving it unchanged", input_path);
+    }
+    Ok(gain)
+}
+
+// Each output folder is taken as one album and all its tracks get the same gain
+fn set_album_gains(jobs: &mut [Job], cancel_flag: &AtomicBool) {
+    let folders: Vec<PathBuf> = unique_folders(jobs.iter().map(|job| &job.output_folder)).into_iter().cloned().collect();
+    for folder in folders {
+        let tracks: Vec<(&Path, &StreamInfo)> = jobs
+            .iter()
+            .filter(|job| job.output_folder == folder)
+            .map(|job| (job.input.as_path(), &job.stream_info))
+            .collect();
+        let gain = album_gain(&tracks, cancel_flag);
+        for job in jobs.iter_mut().filter(|job| job.output_folder == folder) {
+            job.stream_info.album_gain = gain;
+        }
+    }
+}
+
+// Album ReplayGain tags when every track has the same ones; otherwise the tracks' loudness,
+// combined. Tracks split out of one input share its measurement. Includes tracks that are
+// already converted, so the gain is the same as a full run would use.
+pub(crate) fn album_gain(tracks: &[(&Path, &StreamInfo)], cancel_flag: &AtomicBool) -> Option<f64> {
+    let tagged: Option<Vec<(f64, Option<f64>)>> = tracks
+        .iter()
+        .map(|(_, info)| info.replaygain_album_gain.map(|gain| (gain, info.replaygain_album_peak)))
+        .collect();
+    let loudness = match tagged {
+        Some(tags) if !tags.is_empty() && tags.iter().all(|(gain, _)| (gain - tags[0].0).abs() < 0.01) => {
+            log_debug!("Using album ReplayGain tags for {} tracks", tracks.len());
+            let peak = tags.iter().filter_map(|(_, peak)| *peak).reduce(f64::max);
+            Some(Loudness::from_replaygain(tags[0].0, peak))
+        }
+        _ => {
+            let mut inputs: Vec<(&Path, &StreamInfo, f64)> = Vec::new();
+            for (input, info) in tracks {
+                // Without a duration every input counts the same
+                let seconds = info.duration.unwrap_or(1.0);
+                match inputs.iter_mut().find(|(seen, _, _)| seen == input) {
+                    Some((_, _, total)) => *total += seconds,
+                    None => inputs.push((input, info, seconds)),
+                }
+            }
+            let mut measured = Vec::new();
+            for (input, info, seconds) in inputs {
+                if cancel_flag.load(Ordering::Relaxed) {
+                    return None;
+                }
+                match track_loudness(input, info) {
+                    Ok(loudness) => measured.push((loudness, seconds)),
+                    Err(e) => {
+                        log_warn!("Leaving {:?} out of the album loudness: {:#}", input, e);
+                    }
+                }
+            }
+            loudness::combine(&measured)
+        }
+    };
+
+    let gain = loudness.and_then(|loudness| loudness.gain_to(loudness::TARGET_LUFS));
+    match gain {
+        Some(gain) => {
+            log_info!("Album gain for {} tracks: {:+.2} dB", tracks.len(), gain);
+        }
+        None => {
+            log_warn!("Could not determine an album gain; leaving {} tracks unchanged", tracks.len());
+        }
+    }
+    gain
+}
+
+fn is_same_file(input_path: &Path, output_path: &Path) -> bool {
+    let (Some(output_dir), Some(output_name)) = (output_path.parent(), output_path.file_name()) else {
+        return false;
+    };
+    match (fs::canonicalize(input_path), fs::canonicalize(output_dir)) {
+        (Ok(input), Ok(dir)) => input == dir.join(output_name),
+        _ => false,
+    }
+}
+
+// Progress is reported in whole milliseconds
+fn seconds_to_millis(seconds: f64) -> u64 {
+    (seconds.max(0.0) * 1000.0).round() as u64
+}
+
+// Reads the last "time=HH:MM:SS.ss" stamp from an ffmpeg stats line, in seconds.
+pub(crate) fn parse_ffmpeg_time(output: &str) -> Option<f64> {
+    let stamp = output[output.rfind("time=")? + 5..].split_whitespace().next()?;
+    let mut seconds = 0.0;
+    for part in stamp.split(':') {
+        seconds = seconds * 60.0 + part.parse::<f64>().ok()?;
+    }
+    Some(seconds)
+}
+
+// Channel order of the layouts ffmpeg reports, falling back to its default layout for the channel count.
+fn layout_channels(channels: u16, layout: Option<&str>) -> Option<&'static [&'static str]> {
+    let named: Option<&'static [&'static str]> = match layout {
+        Some("3.0") => Some(&["FL", "FR", "FC"]),
+        Some("3.0(back)") => Some(&["FL", "FR", "BC"]),
+        Some("4.0") => Some(&["FL", "FR", "FC", "BC"]),
+        Some("quad") => Some(&["FL", "FR", "BL", "BR"]),
+        Some("quad(side)") => Some(&["FL", "FR", "SL", "SR"]),
+        Some("3.1") => Some(&["FL", "FR", "FC", "LFE"]),
+        Some("5.0") => Some(&["FL", "FR", "FC", "BL", "BR"]),
+        Some("5.0(side)") => Some(&["FL", "FR", "FC", "SL", "SR"]),
+        Some("4.1") => Some(&["FL", "FR", "FC", "LFE", "BC"]),
+        Some("5.1") => Some(&["FL", "FR", "FC", "LFE", "BL", "BR"]),
+        Some("5.1(side)") => Some(&["FL", "FR", "FC", "LFE", "SL", "SR"]),
+        Some("6.0") => Some(&["FL", "FR", "FC", "BC", "SL", "SR"]),
+        Some("6.1") => Some(&["FL", "FR", "FC", "LFE", "BC", "SL", "SR"]),
+        Some("7.0") => Some(&["FL", "FR", "FC", "BL", "BR", "SL", "SR"]),
+        Some("7.1") => Some(&["FL", "FR", "FC", "LFE", "BL", "BR", "SL", "SR"]),
+        _ => None,
+    };
+    if let Some(names) = named.filter(|names| names.len() == channels as usize) {
+        return Some(names);
+    }
+
+    match channels {
+        3 => Some(&["FL", "FR", "FC"]),
+        4 => Some(&["FL", "FR", "FC", "BC"]),
+        5 => Some(&["FL", "FR", "FC", "BL", "BR"]),
+        6 => Some(&["FL", "FR", "FC", "LFE", "BL", "BR"]),
+        7 => Some(&["FL", "FR", "FC", "LFE", "BC", "SL", "SR"]),
+        8 => Some(&["FL", "FR", "FC", "LFE", "BL", "BR", "SL", "SR"]),
+        _ => None,
+    }
+}
+
+// ITU-R BS.775 stereo downmix: L = FL + 0.707*C + 0.707*SL, R = FR + 0.707*C + 0.707*SR. LFE is dropped.
+pub(crate) fn downmix_filter(channels: u16, layout: Option<&str>) -> Option<String> {
+    if channels <= 2 {
+        return None;
+    }
+    let names = layout_channels(channels, layout)?;
+
+    let mut left = Vec::new();
+    let mut right = Vec::new();
+    for (index, name) in names.iter().enumerate() {
+        let (left_gain, right_gain) = match *name {
+            "FL" => (1.0, 0.0),
+            "FR" => (0.0, 1.0),
+            "FC" => (0.707, 0.707),
+            "BL" | "SL" => (0.707, 0.0),
+            "BR" | "SR" => (0.0, 0.707),
+            "BC" => (0.5, 0.5),
+            _ => (0.0, 0.0),
+        };
+        if left_gain > 0.0 {
+            left.push(format!("{}*c{}", left_gain, index));
+        }
+        if right_gain > 0.0 {
+            right.push(format!("{}*c{}", right_gain, index));
+        }
+    }
+
+    Some(format!("pan=stereo|c0={}|c1={}", left.join("+"), right.join("+")))
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    // What probing a file with these properties would report
+    fn stream(sample_rate: u32, channels: u16, sample_format: &str) -> StreamInfo {
+        StreamInfo {
+            sample_rate,
+            channels,
+            channel_layout: None,
+            codec_name: Some("flac".to_string()),
+            sample_format: Some(sample_format.to_string()),
+            duration: Some(1.0),
+            track: TrackInfo::default(),
+            replaygain_track_gain: None,
+            replaygain_track_peak: None,
+            replaygain_album_gain: None,
+            replaygain_album_peak: None,
+            album_gain: None,
+            segment: None,
+            range: None,
+        }
+    }
+
+    #[test]
+    #[ignore = "needs ffmpeg"]
+    fn bit_perfect_flac_keeps_every_sample() {
+        let dir = testing::temp_dir("bit-perfect");
+        // A sine with low-level noise on top, so any dither or rounding would show
+        let samples: Vec<i16> = testing::sine(44100, 440.0, 0.5, 44100, 2)
+            .into_iter()
+            .enumerate()
+            .map(|(index, sample)| sample.saturating_add((index * 7919 % 201) as i16 - 100))
+            .collect();
+        let source = dir.join("source.wav");
+        testing::write_wav(&source, 44100, 2, &samples);
+        let flac = dir.join("track.flac");
+        testing::ffmpeg(&["-i", source.to_str().unwrap(), "-c:a", "flac", "-sample_fmt", "s16", flac.to_str().unwrap()]);
+
+        let options = ConversionOptions {
+            bit_perfect: true,
+            ..ConversionOptions::default()
+        };
+        let summary = testing::convert_ok(vec![flac], &dir.join("out"), options);
+        let output = testing::read_wav_samples(&summary.tracks[0].output_path);
+        assert_eq!(&output[..samples.len()], samples.as_slice());
+        // Only the padding to a whole sector follows
+        assert!(output[samples.len()..].iter().all(|sample| *sample == 0));
+    }
+
+    #[test]
+    fn untouched_16_bit_source_isnt_requantized() {
+        let options = ConversionOptions::default();
+        assert_ne!(options.dither, DitherMode::None);
+        assert!(!requantizes(&stream(44100, 2, "s16"), &options));
+        assert!(!requantizes(&stream(44100, 2, "s16p"), &options));
+        // A mono source is only copied to both channels
+        assert!(!requantizes(&stream(44100, 1, "s16"), &options));
+        assert!(quantize_filter(&options, false).ends_with("dither_method=none"));
+        assert!(quantize_filter(&options, true).ends_with("dither_method=triangular"));
+    }
+
+    #[test]
+    fn changed_samples_are_requantized() {
+        let options = ConversionOptions::default();
+        assert!(requantizes(&stream(44100, 2, "s32"), &options));
+        assert!(requantizes(&stream(44100, 2, "fltp"), &options));
+        assert!(requantizes(&stream(48000, 2, "s16"), &options));
+        assert!(requantizes(&stream(44100, 6, "s16"), &options));
+        let level_changes = [
+            ConversionOptions { normalize: true, ..ConversionOptions::default() },
+            ConversionOptions { limit_peaks: true, ..ConversionOptions::default() },
+            ConversionOptions { remove_dc: true, ..ConversionOptions::default() },
+            ConversionOptions { fade_in_seconds: 1.0, ..ConversionOptions::default() },
+            ConversionOptions { left_gain_db: -1.0, ..ConversionOptions::default() },
+        ];
+        for options in &level_changes {
+            assert!(requantizes(&stream(44100, 2, "s16"), options));
+        }
+    }
+
+    #[test]
+    #[ignore = "needs ffmpeg"]
+    fn dithering_leaves_16_bit_source_unchanged() {
+        let dir = testing::temp_dir("no-dither");
+        let samples = testing::sine(44100, 1000.0, 0.25, 44100, 2);
+        let source = dir.join("source.wav");
+        testing::write_wav(&source, 44100, 2, &samples);
+        // Not a WAV, or it would be copied without going through the filters at all
+        let flac = dir.join("track.flac");
+        testing::ffmpeg(&["-i", source.to_str().unwrap(), "-c:a", "flac", "-sample_fmt", "s16", flac.to_str().unwrap()]);
+
+        let options = ConversionOptions {
+            dither: DitherMode::Tpdf,
+            ..ConversionOptions::default()
+        };
+        let summary = testing::convert_ok(vec![flac], &dir.join("out"), options);
+        let output = testing::read_wav_samples(&summary.tracks[0].output_path);
+        assert_eq!(&output[..samples.len()], samples.as_slice());
+    }
+
+    #[test]
+    fn downmixes_3_0_to_stereo() {
+        let expected = "pan=stereo|c0=1*c0+0.707*c2|c1=1*c1+0.707*c2";
+        assert_eq!(downmix_filter(3, Some("3.0")).as_deref(), Some(expected));
+        // Without a layout, three channels are taken as 3.0
+        assert_eq!(downmix_filter(3, None).as_deref(), Some(expected));
+    }
+
+    #[test]
+    fn downmixes_5_1_to_stereo_without_lfe() {
+        let expected = "pan=stereo|c0=1*c0+0.707*c2+0.707*c4|c1=1*c1+0.707*c2+0.707*c5";
+        assert_eq!(downmix_filter(6, Some("5.1")).as_deref(), Some(expected));
+        assert_eq!(downmix_filter(6, Some("5.1(side)")).as_deref(), Some(expected));
+        assert_eq!(downmix_filter(6, None).as_deref(), Some(expected));
+    }
+
+    #[test]
+    fn mono_and_stereo_need_no_downmix() {
+        assert_eq!(downmix_filter(1, Some("mono")), None);
+        assert_eq!(downmix_filter(2, Some("stereo")), None);
+        assert_eq!(downmix_filter(2, None), None);
+    }
+
+    #[test]
+    fn finds_output_folder_nested_in_input() {
+        let dir = testing::temp_dir("nested-output");
+        let input = dir.join("library");
+        let output = input.join("Rips").join("out");
+        let elsewhere = dir.join("elsewhere");
+        fs::create_dir_all(&output).unwrap();
+        fs::create_dir_all(&elsewhere).unwrap();
+
+        assert_eq!(nested_output(&input, &output), Some(input.join("Rips").join("out")));
+        // Reached the way the walk reaches it, even when the output was named another way
+        let roundabout = input.join("Rips").join("..").join("Rips").join("out");
+        assert_eq!(nested_output(&input, &roundabout), Some(input.join("Rips").join("out")));
+        assert_eq!(nested_output(&input, &input), None);
+        assert_eq!(nested_output(&input, &elsewhere), None);
+        assert_eq!(nested_output(&input, &input.join("not yet")), None);
+    }
+
+    #[test]
+    fn scan_skips_nested_output_folder() {
+        let dir = testing::temp_dir("scan-nested");
+        let output = dir.join("out");
+        fs::create_dir_all(&output).unwrap();
+        fs::write(dir.join("01.mp3"), b"").unwrap();
+        fs::write(output.join("01.wav"), b"").unwrap();
+
+        let nested = nested_output(&dir, &output);
+        let (files, _) = scan_listing(&dir, nested);
+        assert_eq!(files.collect::<Vec<_>>(), vec![dir.join("01.mp3")]);
+    }
+
+    #[test]
+    fn same_name_ignores_case() {
+        assert!(same_name(Path::new("out/01 - Intro.wav"), Path::new("out/01 - INTRO.WAV")));
+        assert!(same_name(Path::new("out/Ärger.wav"), Path::new("out/ärger.wav")));
+        assert!(!same_name(Path::new("out/01.wav"), Path::new("out/01 (2).wav")));
+        assert!(!same_name(Path::new("a/01.wav"), Path::new("b/01.wav")));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn same_name_compares_non_unicode_names_exactly() {
+        use std::os::unix::ffi::OsStrExt;
+        let latin1 = Path::new(OsStr::from_bytes(b"out/caf\xe9.wav"));
+        assert!(same_name(latin1, latin1));
+        assert!(!same_name(latin1, Path::new(OsStr::from_bytes(b"out/CAF\xe9.wav"))));
+        assert!(!same_name(latin1, Path::new("out/caf\u{fffd}.wav")));
+    }
+
+    #[test]
+    #[ignore = "needs ffmpeg"]
+    fn same_named_inputs_get_numbered_outputs() {
+        let dir = testing::temp_dir("name-clash");
+        let first = dir.join("Album A").join("01.wav");
+        let second = dir.join("Album B").join("01.wav");
+        let first_samples = testing::sine(44100, 440.0, 0.5, 44100, 2);
+        let second_samples = testing::sine(44100, 880.0, 0.25, 44100, 2);
+        for (path, samples) in [(&first, &first_samples), (&second, &second_samples)] {
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            testing::write_wav(path, 44100, 2, samples);
+        }
+
+        let out = dir.join("out");
+        let summary = testing::convert_ok(vec![first, second], &out, ConversionOptions::default());
+        assert_eq!(summary.tracks[0].output_path, out.join("01.wav"));
+        assert_eq!(summary.tracks[1].output_path, out.join("01 (2).wav"));
+        // Neither overwrote the other
+        assert_eq!(testing::read_wav_samples(&out.join("01.wav"))[..first_samples.len()], first_samples[..]);
+        assert_eq!(testing::read_wav_samples(&out.join("01 (2).wav"))[..second_samples.len()], second_samples[..]);
+    }
+
+    #[test]
+    fn balance_trims_each_channel() {
+        let options = ConversionOptions { left_gain_db: -6.0, ..ConversionOptions::default() };
+        assert_eq!(balance_filter(&options, Some(2)).unwrap(), "pan=stereo|c0=0.501187*c0|c1=1.000000*c1");
+        assert_eq!(balance_filter(&options, Some(1)).unwrap(), "pan=stereo|c0=0.501187*c0|c1=1.000000*c0");
+        assert_eq!(
+            balance_filter(&options, None).unwrap(),
+            "aformat=channel_layouts=stereo,pan=stereo|c0=0.501187*c0|c1=1.000000*c1"
+        );
+        assert_eq!(balance_filter(&ConversionOptions::default(), Some(2)), None);
+    }
+
+    #[test]
+    #[ignore = "needs ffmpeg"]
+    fn balance_lowers_the_trimmed_channel() {
+        let dir = testing::temp_dir("balance");
+        let input = dir.join("in.wav");
+        testing::write_wav(&input, 44100, 2, &testing::sine(44100, 1000.0, 0.5, 44100, 2));
+        let options = ConversionOptions {
+            left_gain_db: -6.0,
+            force_reencode: true,
+            ..ConversionOptions::default()
+        };
+        let summary = testing::convert_ok(vec![input], &dir.join("out"), options);
+
+        let samples = testing::read_wav_samples(&summary.tracks[0].output_path);
+        let peak = |channel: usize| {
+            samples.iter().skip(channel).step_by(2).map(|s| f64::from(s.unsigned_abs())).fold(0.0, f64::max) / 32767.0
+        };
+        assert!((peak(0) - 0.5 * 0.501187).abs() < 0.002, "left peak {}", peak(0));
+        assert!((peak(1) - 0.5).abs() < 0.002, "right peak {}", peak(1));
+    }
+
+    #[test]
+    #[ignore = "needs ffmpeg"]
+    fn converts_opus_to_cdda() {
+        let dir = testing::temp_dir("opus");
+        let input = dir.join("in.opus");
+        let input_arg = input.to_str().unwrap();
+        let sine = "sine=frequency=1000:sample_rate=48000:duration=2";
+        testing::ffmpeg(&["-f", "lavfi", "-i", sine, "-ac", "2", "-c:a", "libopus", input_arg]);
+
+        let summary = testing::convert_ok(vec![input], &dir.join("out"), ConversionOptions::default());
+        let track = &summary.tracks[0];
+        assert_eq!((track.source_rate, track.sample_rate), (48000, 44100));
+        let format = wav::read_wav_info(&track.output_path).unwrap().format.unwrap();
+        assert!(format.is_pcm());
+        assert_eq!((format.sample_rate, format.bits_per_sample, format.channels), (44100, 16, 2));
+    }
+
+    #[test]
+    fn zero_length_fades_add_no_filter() {
+        let info = stream(44100, 2, "s16");
+        let options = ConversionOptions::default();
+        assert!(edge_filters(Path::new("unused.wav"), &info, &options).unwrap().is_empty());
+    }
+
+    #[test]
+    fn fades_use_the_chosen_curve() {
+        let info = StreamInfo {
+            range: Some(TimeRange { start: Duration::ZERO, end: Some(Duration::from_secs(10)) }),
+            ..stream(44100, 2, "s16")
+        };
+        let options = ConversionOptions { fade_in_seconds: 1.5, fade_out_seconds: 2.0, ..ConversionOptions::default() };
+        assert_eq!(
+            edge_filters(Path::new("unused.wav"), &info, &options).unwrap(),
+            ["afade=t=in:st=0:d=1.500:curve=tri", "afade=t=out:st=8.000000:d=2.000:curve=tri"]
+        );
+        let options = ConversionOptions { fade_curve: FadeCurve::EqualPower, fade_out_seconds: 0.0, ..options };
+        assert_eq!(
+            edge_filters(Path::new("unused.wav"), &info, &options).unwrap(),
+            ["afade=t=in:st=0:d=1.500:curve=qsin"]
+        );
+    }
+
+    #[test]
+    #[ignore = "needs ffmpeg"]
+    fn fades_start_and_end_near_silence() {
+        let dir = testing::temp_dir("fades");
+        let input = dir.join("in.wav");
+        testing::write_wav(&input, 44100, 2, &testing::sine(44100, 1000.0, 0.5, 5 * 44100, 2));
+        let options = ConversionOptions {
+            fade_in_seconds: 2.0,
+            fade_out_seconds: 2.0,
+            ..ConversionOptions::default()
+        };
+        let summary = testing::convert_ok(vec![input], &dir.join("out"), options);
+
+        let track = &summary.tracks[0];
+        let samples = testing::read_wav_samples(&track.output_path);
+        let audio = &samples[..track.frames_written as usize * 2];
+        let peak = |samples: &[i16]| samples.iter().map(|s| s.unsigned_abs()).max().unwrap();
+        assert!(peak(&audio[..20]) < 50, "fade in starts at {}", peak(&audio[..20]));
+        assert!(peak(&audio[audio.len() - 20..]) < 50, "fade out ends at {}", peak(&audio[audio.len() - 20..]));
+        // The middle second is left alone
+        assert!(peak(&audio[2 * 88200..3 * 88200]) > 16000);
+    }
+
+    #[test]
+    #[ignore = "needs ffmpeg"]
+    fn limiter_holds_overshoot_under_the_ceiling() {
+        let dir = testing::temp_dir("limiter");
+        // Float samples peaking 3.5 dB over full scale; lavfi's sine is 1/8 of full scale
+        let input = dir.join("hot.wav");
+        let hot = "sine=frequency=1000:sample_rate=44100:duration=2,volume=12";
+        testing::ffmpeg(&["-f", "lavfi", "-i", hot, "-ac", "2", "-c:a", "pcm_f32le", input.to_str().unwrap()]);
+        let options = ConversionOptions {
+            limit_peaks: true,
+            peak_ceiling_db: -1.0,
+            ..ConversionOptions::default()
+        };
+        let summary = testing::convert_ok(vec![input], &dir.join("out"), options);
+
+        let output = &summary.tracks[0].output_path;
+        let stats = wav::data_stats(output, &wav::read_wav_info(output).unwrap()).unwrap();
+        // Allow a couple of steps of dither on top of the ceiling
+        let ceiling = 10f64.powf(-1.0 / 20.0) + 2.0 / 32768.0;
+        assert!(stats.peak <= ceiling, "peak {} over ceiling {}", stats.peak, ceiling);
+        assert_eq!(stats.clipped_runs, 0);
+    }
+
+    #[test]
+    #[ignore = "needs ffmpeg"]
+    fn removes_dc_offset() {
+        let dir = testing::temp_dir("dc");
+        let input = dir.join("offset.wav");
+        let offset = (0.2 * 32767.0) as i16;
+        let samples: Vec<i16> = testing::sine(44100, 440.0, 0.5, 3 * 44100, 2).into_iter().map(|s| s + offset).collect();
+        testing::write_wav(&input, 44100, 2, &samples);
+        let options = ConversionOptions { remove_dc: true, ..ConversionOptions::default() };
+        let summary = testing::convert_ok(vec![input], &dir.join("out"), options);
+
+        // Skip the first second, while the high-pass settles
+        let track = &summary.tracks[0];
+        let output = testing::read_wav_samples(&track.output_path);
+        let settled = &output[88200..track.frames_written as usize * 2];
+        let mean = settled.iter().map(|&s| f64::from(s)).sum::<f64>() / settled.len() as f64 / 32768.0;
+        assert!(mean.abs() < 0.002, "mean {} after removing an offset of 0.2", mean);
+    }
+
+    #[test]
+    fn downsampling_pulls_the_cutoff_down() {
+        assert_eq!(downsample_cutoff(44100, 44100), None);
+        assert_eq!(downsample_cutoff(22050, 44100), None);
+        assert_eq!(format!("{:.3}", downsample_cutoff(48000, 44100).unwrap()), "0.966");
+        assert_eq!(format!("{:.3}", downsample_cutoff(96000, 44100).unwrap()), "0.936");
+        assert_eq!(downsample_cutoff(768000, 44100), Some(0.85));
+        assert_eq!(ResampleQuality::Balanced.swr_options(96000, 44100), "filter_size=32:phase_shift=10:cutoff=0.936");
+        assert_eq!(ResampleQuality::Best.swr_options(48000, 44100), "filter_size=128:phase_shift=14:cutoff=0.966");
+        assert_eq!(ResampleQuality::Fast.swr_options(22050, 44100), "filter_size=16:phase_shift=8:linear_interp=1");
+    }
+
+    #[test]
+    #[ignore = "needs ffmpeg"]
+    fn downsampling_drops_tones_above_the_output_nyquist() {
+        let dir = testing::temp_dir("cutoff");
+        let out = dir.join("out");
+        let peak_after = |frequency: f64| {
+            let input = dir.join(format!("{}.wav", frequency));
+            testing::write_wav(&input, 96000, 2, &testing::sine(96000, frequency, 0.5, 2 * 96000, 2));
+            let summary = testing::convert_ok(vec![input], &out, ConversionOptions::default());
+            let track = &summary.tracks[0];
+            let samples = testing::read_wav_samples(&track.output_path);
+            samples[..track.frames_written as usize * 2].iter().map(|s| s.unsigned_abs()).max().unwrap()
+        };
+        // 30 kHz would fold back to 14.1 kHz if it got through
+        assert!(peak_after(30000.0) < 100, "30 kHz came through");
+        assert!(peak_after(1000.0) > 16000, "1 kHz was attenuated");
+    }
+
+    #[test]
+    #[ignore = "needs ffmpeg"]
+    fn short_tracks_are_flagged_or_padded() {
+        let dir = testing::temp_dir("short");
+        let input = dir.join("short.wav");
+        testing::write_wav(&input, 48000, 2, &testing::sine(48000, 440.0, 0.5, 2 * 48000, 2));
+
+        let flagged = testing::convert_ok(vec![input.clone()], &dir.join("flagged"), ConversionOptions::default());
+        assert_eq!(flagged.tracks[0].frames_written, 2 * 44100);
+        assert!(flagged.tracks[0].is_too_short_for_cd());
+
+        let options = ConversionOptions { short_tracks: ShortTrackPolicy::Pad, ..ConversionOptions::default() };
+        let padded = testing::convert_ok(vec![input], &dir.join("padded"), options);
+        let track = &padded.tracks[0];
+        assert_eq!(track.frames_written, cue::MIN_TRACK_FRAMES * SAMPLES_PER_CD_FRAME);
+        assert!(!track.is_too_short_for_cd());
+        assert_eq!(testing::read_wav_samples(&track.output_path).len() as u64, track.frames_written * 2);
+    }
+
+    #[test]
+    #[ignore = "needs ffmpeg"]
+    fn empty_input_fails_without_writing_a_file() {
+        let dir = testing::temp_dir("empty");
+        let input = dir.join("empty.wav");
+        testing::write_wav(&input, 48000, 2, &[]);
+        let out = dir.join("out");
+        let summary = testing::convert(vec![input], &testing::options_into(&out)).unwrap();
+        assert_eq!(summary.failed(), 1);
+        assert!(summary.tracks.is_empty());
+        let written: Vec<_> = fs::read_dir(&out).into_iter().flatten().flatten().map(|entry| entry.file_name()).collect();
+        assert!(!written.iter().any(|name| name.to_string_lossy().contains(".wav")), "left behind {:?}", written);
+    }
+
+    // Cancels the batch as soon as the first file reports any progress
+    struct CancelOnProgress {
+        cancel_flag: Arc<AtomicBool>,
+    }
+
+    impl ProgressSink for CancelOnProgress {
+        fn on_progress(&self, _index: usize, _done: u64, _total: u64) {
+            self.cancel_flag.store(true, Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    #[ignore = "needs ffmpeg"]
+    fn cancelling_leaves_no_output_behind() {
+        let dir = testing::temp_dir("cancel");
+        // Long enough that ffmpeg reports progress well before it finishes
+        let input = dir.join("long.flac");
+        let sine = "sine=frequency=440:sample_rate=8000:duration=1800";
+        testing::ffmpeg(&["-f", "lavfi", "-i", sine, "-c:a", "flac", input.to_str().unwrap()]);
+        let out = dir.join("out");
+        let options = ConversionOptions { resample_quality: ResampleQuality::Best, ..testing::options_into(&out) };
+        let cancel_flag = Arc::new(AtomicBool::new(false));
+        let sink = CancelOnProgress { cancel_flag: Arc::clone(&cancel_flag) };
+        let summary = testing::convert_with(vec![input], &options, &sink, Arc::clone(&cancel_flag)).unwrap();
+
+        assert!(cancel_flag.load(Ordering::Relaxed), "no progress was reported");
+        assert!(summary.tracks.is_empty());
+        let output = out.join("long.wav");
+        assert!(!output.exists());
+        assert!(!partial_path(&output).exists());
+    }
+
+    #[test]
+    #[ignore = "needs ffmpeg"]
+    fn counts_the_frames_written() {
+        let dir = testing::temp_dir("frames");
+        let cdda = dir.join("cdda.wav");
+        testing::write_wav(&cdda, 44100, 2, &testing::sine(44100, 440.0, 0.5, 5 * 44100 + 123, 2));
+        let resampled = dir.join("48k.wav");
+        testing::write_wav(&resampled, 48000, 1, &testing::sine(48000, 440.0, 0.5, 6 * 48000, 1));
+        let options = ConversionOptions { force_reencode: true, ..ConversionOptions::default() };
+        let summary = testing::convert_ok(vec![cdda, resampled], &dir.join("out"), options);
+
+        // Sector padding isn't counted
+        assert_eq!(summary.tracks[0].frames_written, 5 * 44100 + 123);
+        assert!((summary.tracks[0].duration_seconds() - (5.0 + 123.0 / 44100.0)).abs() < 1e-9);
+        assert_eq!(summary.tracks[1].frames_written, 6 * 44100);
+        assert_eq!(summary.tracks[1].duration_seconds(), 6.0);
+        assert_eq!((summary.tracks[1].channels, summary.tracks[1].sample_rate), (2, 44100));
+    }
+
+    #[test]
+    fn output_names_keep_cjk_and_emoji() {
+        assert_eq!(output_file_name(OsStr::new("曲 🎵"), None, "", "wav"), "曲 🎵.wav");
+        assert_eq!(output_file_name(OsStr::new("夜に駆ける"), Some(2), "", "wav"), "夜に駆ける (2).wav");
+        assert_eq!(output_file_name(OsStr::new("🎸🥁"), None, " (preview)", "flac"), "🎸🥁 (preview).flac");
+    }
+
+    #[test]
+    #[ignore = "needs ffmpeg"]
+    fn writes_cjk_and_emoji_file_names() {
+        let dir = testing::temp_dir("unicode");
+        let untagged = dir.join("曲 🎵.wav");
+        testing::write_wav(&untagged, 48000, 2, &testing::sine(48000, 440.0, 0.5, 48000, 2));
+        let tagged = dir.join("tagged.flac");
+        testing::ffmpeg(&[
+            "-i", untagged.to_str().unwrap(),
+            "-metadata", "title=夜に駆ける 🌙",
+            "-metadata", "artist=YOASOBI",
+            "-metadata", "track=1",
+            tagged.to_str().unwrap(),
+        ]);
+        let out = dir.join("out");
+        let summary = testing::convert_ok(vec![untagged, tagged], &out, ConversionOptions::default());
+        assert_eq!(summary.tracks[0].output_path, out.join("曲 🎵.wav"));
+        assert_eq!(summary.tracks[1].output_path, out.join("01 - YOASOBI - 夜に駆ける 🌙.wav"));
+        assert!(summary.tracks.iter().all(|track| track.output_path.is_file()));
+    }
+
+    #[test]
+    fn unique_folders_catches_interleaved_repeats() {
+        let (a, b, c) = (PathBuf::from("out/A"), PathBuf::from("out/B"), PathBuf::from("out/C"));
+        let folders = [&a, &b, &a, &a, &c, &b];
+        assert_eq!(unique_folders(folders), [&a, &b, &c]);
+    }
+
+    // Resumes the batch as soon as it is paused for a full disk, counting how often that happens
+    struct ResumeOnDiskFull {
+        pause_flag: Arc<AtomicBool>,
+        pauses: AtomicUsize,
+    }
+
+    impl ProgressSink for ResumeOnDiskFull {
+        fn on_disk_full(&self, _output: &Path) {
+            self.pauses.fetch_add(1, Ordering::Relaxed);
+            self.pause_flag.store(false, Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    fn disk_full_keeps_the_existing_output() {
+        let dir = testing::temp_dir("disk-full");
+        let output = dir.join("01.wav");
+        fs::write(&output, b"earlier output").unwrap();
+        let pause_flag = Arc::new(AtomicBool::new(false));
+        let sink = ResumeOnDiskFull { pause_flag: Arc::clone(&pause_flag), pauses: AtomicUsize::new(0) };
+        let mut attempts = 0;
+        let outcome = with_disk_full_pause(&output, &AtomicBool::new(false), &pause_flag, &sink, || {
+            attempts += 1;
+            if attempts == 1 {
+                return Err(std::io::Error::from(std::io::ErrorKind::StorageFull)).context("Failed to write");
+            }
+            Ok(attempts)
+        });
+
+        assert_eq!(outcome.unwrap(), 2);
+        assert_eq!(sink.pauses.load(Ordering::Relaxed), 1);
+        assert_eq!(fs::read(&output).unwrap(), b"earlier output");
+    }
+}
@@ -0,0 +1,329 @@
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+use anyhow::{Context, Result};
+use crate::wav;
+
+pub const CD_FRAMES_PER_SECOND: u64 = 75;
+// Red Book's shortest track, 4 seconds; burners reject anything shorter
+pub const MIN_TRACK_FRAMES: u64 = 4 * CD_FRAMES_PER_SECOND;
+// Red Book's two seconds of silence before track 1's INDEX 01
+pub const STANDARD_PREGAP_FRAMES: u64 = 2 * CD_FRAMES_PER_SECOND;
+
+pub struct CueTrack {
+    pub file: PathBuf,
+    pub title: Option<String>,
+    pub performer: Option<String>,
+    // Length of the track in CD frames (1/75 s)
+    pub frames: u64,
+    // Silence the burner generates before INDEX 01; not stored in the file
+    pub pregap_frames: u64,
+    // Silence stored in the file ahead of the track, marked by INDEX 00; counted in addition to frames
+    pub gap_frames: u64,
+    pub isrc: Option<String>,
+}
+
+// Tracks sharing a file (a concatenated BIN) get running offsets; a new file restarts at 00:00:00.
+// The codes are checked before anything is written, so a bad one never leaves a CUE sheet that
+// burners reject or, worse, burn.
+pub fn write_cue_sheet(tracks: &[CueTrack], catalog: Option<&str>, output: &Path) -> Result<()> {
+    let catalog = catalog.map(normalize_catalog).transpose()?;
+    let isrcs = tracks
+        .iter()
+        .enumerate()
+        .map(|(index, track)| {
+            track.isrc.as_deref().map(normalize_isrc).transpose().with_context(|| format!("Track {}", index + 1))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut sheet = String::new();
+    let mut current_file: Option<&Path> = None;
+    let mut offset = 0;
+    if let Some(catalog) = catalog {
+        writeln!(sheet, "CATALOG {}", catalog)?;
+    }
+
+    for (index, track) in tracks.iter().enumerate() {
+        if current_file != Some(track.file.as_path()) {
+            let file_name = track
+                .file
+                .file_name()
+                .ok_or_else(|| anyhow::anyhow!("Invalid track file {:?}", track.file))?
+                .to_str()
+                // A lossy name would point the sheet at a file that doesn't exist
+                .ok_or_else(|| anyhow::anyhow!("{:?} isn't valid Unicode, so a CUE sheet can't name it; rename it", track.file))?;
+            writeln!(sheet, "FILE \"{}\" {}", escape(file_name), file_type(&track.file))?;
+            current_file = Some(track.file.as_path());
+            offset = 0;
+        }
+
+        writeln!(sheet, "  TRACK {:02} AUDIO", index + 1)?;
+        if let Some(isrc) = &isrcs[index] {
+            writeln!(sheet, "    ISRC {}", isrc)?;
+        }
+        if let Some(title) = &track.title {
+            writeln!(sheet, "    TITLE \"{}\"", escape(title))?;
+        }
+        if let Some(performer) = &track.performer {
+            writeln!(sheet, "    PERFORMER \"{}\"", escape(performer))?;
+        }
+        if track.pregap_frames > 0 {
+            writeln!(sheet, "    PREGAP {}", format_msf(track.pregap_frames))?;
+        }
+        if track.gap_frames > 0 {
+            writeln!(sheet, "    INDEX 00 {}", format_msf(offset))?;
+            offset += track.gap_frames;
+        }
+        writeln!(sheet, "    INDEX 01 {}", format_msf(offset))?;
+        offset += track.frames;
+    }
+
+    fs::write(output, sheet).with_context(|| format!("Failed to write CUE sheet {:?}", output))?;
+    log_info!("Wrote CUE sheet with {} tracks: {:?}", tracks.len(), output);
+    Ok(())
+}
+
+// An ISRC is CC-XXX-YY-NNNNN: country, registrant, year and designation code. It is often printed
+// with hyphens or spaces, which are dropped here.
+pub fn normalize_isrc(code: &str) -> Result<String> {
+    let isrc: String = code.chars().filter(|c| *c != '-' && !c.is_whitespace()).collect::<String>().to_ascii_uppercase();
+    // Checked for ASCII first, as the slices below are by byte
+    let valid = isrc.is_ascii()
+        && isrc.len() == 12
+        && isrc[0..2].chars().all(|c| c.is_ascii_uppercase())
+        && isrc[2..5].chars().all(|c| c.is_ascii_alphanumeric())
+        && isrc[5..12].chars().all(|c| c.is_ascii_digit());
+    if !valid {
+        return Err(anyhow::anyhow!(
+            "Invalid ISRC {:?}: expected 12 characters, 2 letters, 3 letters or digits, then 7 digits",
+            code
+        ));
+    }
+    Ok(isrc)
+}
+
+// CATALOG takes a 13-digit EAN; a 12-digit UPC-A is the same number with a leading zero.
+pub fn normalize_catalog(code: &str) -> Result<String> {
+    let digits: String = code.chars().filter(|c| !c.is_whitespace() && *c != '-').collect();
+    if !digits.chars().all(|c| c.is_ascii_digit()) || !(digits.len() == 12 || digits.len() == 13) {
+        return Err(anyhow::anyhow!("Invalid catalog number {:?}: expected a 13-digit EAN or 12-digit UPC", code));
+    }
+    let ean = format!("{:0>13}", digits);
+    // Weights alternate 1 and 3 from the left, and the check digit brings the sum to a multiple of 10
+    let sum: u32 = ean[..12]
+        .bytes()
+        .enumerate()
+        .map(|(i, digit)| u32::from(digit - b'0') * if i % 2 == 0 { 1 } else { 3 })
+        .sum();
+    if (10 - sum % 10) % 10 != u32::from(ean.as_bytes()[12] - b'0') {
+        return Err(anyhow::anyhow!("Invalid catalog number {:?}: the check digit doesn't match", code));
+    }
+    Ok(ean)
+}
+
+pub struct CueSheet {
+    pub catalog: Option<String>,
+    pub tracks: Vec<CueTrack>,
+}
+
+// Reads back a sheet as write_cue_sheet writes it for a folder of track files, one FILE per
+// TRACK, so more tracks can be added to it. The track lengths come from the files themselves,
+// which are looked for next to the sheet.
+pub fn read_track_sheet(path: &Path) -> Result<CueSheet> {
+    let text = fs::read_to_string(path).with_context(|| format!("Failed to read CUE sheet {:?}", path))?;
+    let folder = path.parent().unwrap_or_else(|| Path::new("."));
+    let mut catalog = None;
+    let mut file: Option<PathBuf> = None;
+    let mut tracks: Vec<CueTrack> = Vec::new();
+    // INDEX 00 and INDEX 01 of each track, from the start of its file
+    let mut indexes: Vec<(Option<u64>, Option<u64>)> = Vec::new();
+
+    for line in text.lines() {
+        let (keyword, rest) = line.trim().split_once(' ').unwrap_or((line.trim(), ""));
+        let rest = rest.trim();
+        match keyword {
+            "CATALOG" => catalog = Some(rest.to_string()),
+            "FILE" => {
+                // The name is quoted and followed by the file type
+                let name = rest.rsplit_once(' ').map_or(rest, |(name, _)| name);
+                file = Some(folder.join(unquote(name)));
+            }
+            "TRACK" => {
+                let Some(current) = file.take() else {
+                    return Err(anyhow::anyhow!("{:?} has a TRACK without a FILE of its own", path));
+                };
+                tracks.push(CueTrack {
+                    file: current,
+                    title: None,
+                    performer: None,
+                    frames: 0,
+                    pregap_frames: 0,
+                    gap_frames: 0,
+                    isrc: None,
+                });
+                indexes.push((None, None));
+            }
+            "TITLE" if !tracks.is_empty() => tracks.last_mut().unwrap().title = Some(unquote(rest)),
+            "PERFORMER" if !tracks.is_empty() => tracks.last_mut().unwrap().performer = Some(unquote(rest)),
+            "ISRC" if !tracks.is_empty() => tracks.last_mut().unwrap().isrc = Some(rest.to_string()),
+            "PREGAP" if !tracks.is_empty() => {
+                tracks.last_mut().unwrap().pregap_frames =
+                    parse_msf(rest).ok_or_else(|| anyhow::anyhow!("Invalid PREGAP {:?} in {:?}", rest, path))?;
+            }
+            "INDEX" if !tracks.is_empty() => {
+                let (number, position) = rest.split_once(' ').unwrap_or((rest, ""));
+                let frame = parse_msf(position.trim())
+                    .ok_or_else(|| anyhow::anyhow!("Invalid INDEX position {:?} in {:?}", position, path))?;
+                match number {
+                    "00" => indexes.last_mut().unwrap().0 = Some(frame),
+                    "01" => indexes.last_mut().unwrap().1 = Some(frame),
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+
+    for (number, (track, (index_00, index_01))) in tracks.iter_mut().zip(indexes).enumerate() {
+        let index_01 = index_01.ok_or_else(|| anyhow::anyhow!("Track {} in {:?} has no INDEX 01", number + 1, path))?;
+        let file_frames = wav::read_wav_info(&track.file)
+            .with_context(|| format!("Track {} of {:?}", number + 1, path))?
+            .cd_frames();
+        track.gap_frames = index_00.map_or(0, |index_00| index_01.saturating_sub(index_00));
+        track.frames = file_frames.saturating_sub(index_01);
+    }
+    Ok(CueSheet { catalog, tracks })
+}
+
+pub struct CueEntry {
+    pub title: Option<String>,
+    pub performer: Option<String>,
+    // INDEX 01 position within the file
+    pub start_frame: u64,
+    // INDEX 00, where the track's pregap starts, if it has one
+    pub pregap_frame: Option<u64>,
+}
+
+impl CueEntry {
+    // Where the track's part of the file begins, pregap included
+    pub fn first_frame(&self) -> u64 {
+        self.pregap_frame.unwrap_or(self.start_frame)
+    }
+}
+
+// Reads the tracks of a single-file CUE sheet, as written by rippers next to an album image.
+pub fn read_cue_sheet(path: &Path) -> Result<Vec<CueEntry>> {
+    let text = fs::read_to_string(path).with_context(|| format!("Failed to read CUE sheet {:?}", path))?;
+    let mut entries: Vec<CueEntry> = Vec::new();
+    let mut indexed = Vec::new();
+    let mut files = 0;
+
+    for line in text.lines() {
+        let (keyword, rest) = line.trim().split_once(' ').unwrap_or((line.trim(), ""));
+        let rest = rest.trim();
+        match keyword {
+            "FILE" => files += 1,
+            "TRACK" => {
+                entries.push(CueEntry { title: None, performer: None, start_frame: 0, pregap_frame: None });
+                indexed.push(false);
+            }
+            // Disc-level TITLE/PERFORMER come before the first TRACK and are ignored
+            "TITLE" if !entries.is_empty() => entries.last_mut().unwrap().title = Some(unquote(rest)),
+            "PERFORMER" if !entries.is_empty() => entries.last_mut().unwrap().performer = Some(unquote(rest)),
+            "INDEX" if !entries.is_empty() => {
+                let (number, position) = rest.split_once(' ').unwrap_or((rest, ""));
+                let frame = || {
+                    parse_msf(position.trim())
+                        .ok_or_else(|| anyhow::anyhow!("Invalid INDEX position {:?} in {:?}", position, path))
+                };
+                match number {
+                    "00" => entries.last_mut().unwrap().pregap_frame = Some(frame()?),
+                    "01" => {
+                        entries.last_mut().unwrap().start_frame = frame()?;
+                        *indexed.last_mut().unwrap() = true;
+                    }
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if files > 1 {
+        return Err(anyhow::anyhow!("{:?} refers to {} files; only single-file CUE sheets are supported", path, files));
+    }
+    if entries.is_empty() {
+        return Err(anyhow::anyhow!("{:?} lists no tracks", path));
+    }
+    if let Some(track) = indexed.iter().position(|has_index| !has_index) {
+        return Err(anyhow::anyhow!("Track {} in {:?} has no INDEX 01", track + 1, path));
+    }
+    if entries.iter().any(|entry| entry.first_frame() > entry.start_frame)
+        || entries.windows(2).any(|pair| pair[1].first_frame() <= pair[0].start_frame)
+    {
+        return Err(anyhow::anyhow!("Tracks in {:?} are not in playing order", path));
+    }
+    Ok(entries)
+}
+
+pub fn format_msf(frames: u64) -> String {
+    let seconds = frames / CD_FRAMES_PER_SECOND;
+    format!("{:02}:{:02}:{:02}", seconds / 60, seconds % 60, frames % CD_FRAMES_PER_SECOND)
+}
+
+// "mm:ss:ff" to CD frames
+pub fn parse_msf(value: &str) -> Option<u64> {
+    let mut parts = value.split(':').map(|part| part.parse::<u64>().ok());
+    let (minutes, seconds, frames) = (parts.next()??, parts.next()??, parts.next()??);
+    if parts.next().is_some() || seconds >= 60 || frames >= CD_FRAMES_PER_SECOND {
+        return None;
+    }
+    Some((minutes * 60 + seconds) * CD_FRAMES_PER_SECOND + frames)
+}
+
+fn unquote(value: &str) -> String {
+    value.trim_matches('"').to_string()
+}
+
+fn file_type(path: &Path) -> &'static str {
+    match path.extension().and_then(|s| s.to_str()) {
+        Some("wav") => "WAVE",
+        Some("aif" | "aiff") => "AIFF",
+        _ => "BINARY",
+    }
+}
+
+fn escape(value: &str) -> String {
+    value.replace('"', "'")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_isrc_in_its_usual_spellings() {
+        assert_eq!(normalize_isrc("USRC17607839").unwrap(), "USRC17607839");
+        assert_eq!(normalize_isrc("US-RC1-76-07839").unwrap(), "USRC17607839");
+        assert_eq!(normalize_isrc("usrc1 76 07839").unwrap(), "USRC17607839");
+    }
+
+    #[test]
+    fn rejects_bad_isrc() {
+        assert!(normalize_isrc("USRC1760783").is_err());
+        assert!(normalize_isrc("USRC176078390").is_err());
+        assert!(normalize_isrc("1SRC17607839").is_err());
+        assert!(normalize_isrc("USRC1760783X").is_err());
+        // 12 bytes, but É is two of them
+        assert!(normalize_isrc("AÉ123456789").is_err());
+        assert!(normalize_isrc("ÉÉÉÉÉÉ").is_err());
+    }
+
+    #[test]
+    fn catalog_takes_ean_and_upc() {
+        assert_eq!(normalize_catalog("4006381333931").unwrap(), "4006381333931");
+        assert_eq!(normalize_catalog("0 36000 29145 2").unwrap(), "0036000291452");
+        assert!(normalize_catalog("4006381333932").is_err());
+        assert!(normalize_catalog("40063813339").is_err());
+    }
+}
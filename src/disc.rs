@@ -0,0 +1,92 @@
+use std::path::{Path, PathBuf};
+use crate::conversion;
+use crate::cue;
+
+// Players and burners put about two seconds between tracks unless told otherwise
+pub const TRACK_GAP_SECONDS: f64 = 2.0;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DiscCapacity {
+    Minutes74,
+    #[default]
+    Minutes80,
+}
+
+impl DiscCapacity {
+    pub const ALL: [DiscCapacity; 2] = [DiscCapacity::Minutes74, DiscCapacity::Minutes80];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            DiscCapacity::Minutes74 => "74 min",
+            DiscCapacity::Minutes80 => "80 min",
+        }
+    }
+
+    pub fn seconds(self) -> f64 {
+        match self {
+            DiscCapacity::Minutes74 => 74.0 * 60.0,
+            DiscCapacity::Minutes80 => 80.0 * 60.0,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct PlayTime {
+    pub tracks: usize,
+    pub audio_seconds: f64,
+    // Files whose length couldn't be determined and are left out of the total
+    pub unknown: usize,
+    // Tracks already on the CUE sheet being appended to, included in tracks and audio_seconds
+    pub existing_tracks: usize,
+}
+
+impl PlayTime {
+    // Audio plus the gap after every track but the last
+    pub fn disc_seconds(&self) -> f64 {
+        self.audio_seconds + TRACK_GAP_SECONDS * self.tracks.saturating_sub(1) as f64
+    }
+
+    pub fn fits(&self, capacity: DiscCapacity) -> bool {
+        self.disc_seconds() <= capacity.seconds()
+    }
+}
+
+// Reads the length of every selected track from its headers. Files without one are decoded, so
+// callers run it off the UI thread.
+// With `project`, the tracks of the CUE sheet in that folder count too, as they'll share the disc.
+pub fn estimate_play_time(paths: &[PathBuf], project: Option<&Path>) -> PlayTime {
+    let mut play_time = PlayTime::default();
+    match project.map(conversion::existing_project).transpose() {
+        Ok(sheet) => {
+            // Only the audio counts: disc_seconds already adds a gap between tracks, and track 1's
+            // pregap is deliberately left out of the capacity, as the 74/80 minutes are taken to
+            // be of music
+            for track in sheet.flatten().map(|sheet| sheet.tracks).unwrap_or_default() {
+                play_time.existing_tracks += 1;
+                play_time.tracks += 1;
+                play_time.audio_seconds += track.frames as f64 / cue::CD_FRAMES_PER_SECOND as f64;
+            }
+        }
+        Err(e) => {
+            log_warn!("Could not read the tracks already in {:?}: {:?}", project, e);
+        }
+    }
+    for input in paths.iter().flat_map(|path| conversion::expand_input(path)) {
+        match conversion::probe_spec(&input) {
+            Ok(spec) => {
+                play_time.tracks += 1;
+                play_time.audio_seconds += spec.duration_seconds();
+            }
+            Err(e) => {
+                log_warn!("Could not determine the length of {:?}: {:?}", input, e);
+                play_time.unknown += 1;
+            }
+        }
+    }
+    play_time
+}
+
+pub fn format_minutes(seconds: f64) -> String {
+    let seconds = seconds.round() as u64;
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
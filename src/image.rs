@@ -0,0 +1,460 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use anyhow::{Context, Result};
+use crate::conversion::{self, ConversionOptions, ConversionSummary, ExistingOutputPolicy, GainMode, ShortTrackPolicy, SplitMode, TrackResult};
+use crate::cue::{self, CueTrack};
+use crate::metadata;
+use crate::progress::ProgressSink;
+use crate::wav::{self, CD_SECTOR_BYTES};
+
+// What detect_gaps puts between tracks that don't run into each other, the usual two seconds
+const DISCRETE_GAP_FRAMES: u64 = 150;
+// How much of each track's start and end detect_gaps looks at; a fade-out has reached silence by then
+const EDGE_SECONDS: f64 = 0.1;
+const IMAGE_NAME: &str = "disc.bin";
+
+// Where the image goes when the user didn't name one: the folder the first input would convert into.
+pub fn default_image_path(paths: &[PathBuf], options: &ConversionOptions) -> Option<PathBuf> {
+    paths
+        .first()
+        .map(|path| conversion::output_folder_for(path, options).join(IMAGE_NAME))
+}
+
+// Decodes every input in order into one raw 44.1 kHz/16-bit/stereo little-endian BIN, each track
+// padded to a whole sector and preceded by options.gap_frames of silence (first_track_pregap_frames for track 1),
+// plus a CUE sheet with the same stem describing the track boundaries.
+pub fn convert_to_image(
+    paths: Vec<PathBuf>,
+    output: &Path,
+    cancel_flag: Arc<AtomicBool>,
+    pause_flag: Arc<AtomicBool>,
+    stop_flag: Arc<AtomicBool>,
+    options: &ConversionOptions,
+    progress: &dyn ProgressSink,
+) -> Result<ConversionSummary> {
+    let mut summary = ConversionSummary::default();
+    if !options.writes_cdda() {
+        return Err(anyhow::anyhow!(
+            "Disc images can only hold CD audio, not {} Hz {}",
+            options.target_rate,
+            options.output_format.label()
+        ));
+    }
+    conversion::check_time_range(options)?;
+    if let Some(code) = &options.cue_catalog {
+        cue::normalize_catalog(code)?;
+    }
+    let mut catalog = options.cue_catalog.clone();
+    if options.split_mode != SplitMode::Off && options.time_range.is_none() {
+        log_warn!("Splitting inputs isn't supported in image mode; each input becomes one track");
+    }
+    let mut crossfader = (options.crossfade_seconds > 0.0).then(|| Crossfader::new(options.crossfade_seconds));
+    if crossfader.is_some() && options.gap_frames > 0 && !options.detect_gaps {
+        log_warn!("Crossfaded tracks overlap, so the gap between tracks is ignored");
+    }
+    let extended;
+    let output = match output.parent() {
+        Some(folder) if conversion::needs_extended_length(folder) => {
+            extended = conversion::extended_length(output);
+            extended.as_path()
+        }
+        _ => output,
+    };
+    let inputs: Vec<PathBuf> = paths.iter().flat_map(|path| conversion::expand_input(path)).collect();
+    if inputs.is_empty() {
+        log_warn!("No audio files to write into {:?}", output);
+        return Ok(summary);
+    }
+
+    if output.exists() {
+        match options.existing_outputs {
+            // Backed up only once the new image is complete, below
+            ExistingOutputPolicy::Overwrite | ExistingOutputPolicy::NumberedBackup => {}
+            ExistingOutputPolicy::Skip => {
+                log_info!("{:?} already exists; leaving it as it is", output);
+                summary.skipped = inputs.len();
+                return Ok(summary);
+            }
+            ExistingOutputPolicy::Abort => {
+                return Err(anyhow::anyhow!("{:?} already exists; nothing was converted", output));
+            }
+        }
+    }
+
+    // The whole disc is the album. Inputs that can't be probed are left out here and fail below.
+    let album_gain = if options.normalize && options.gain_mode == GainMode::Album {
+        let probed: Vec<(&Path, conversion::StreamInfo)> = inputs
+            .iter()
+            .filter_map(|input| Some((input.as_path(), probe_for_image(input, options).ok()?)))
+            .collect();
+        let tracks: Vec<(&Path, &conversion::StreamInfo)> = probed.iter().map(|(input, info)| (*input, info)).collect();
+        let gain = conversion::album_gain(&tracks, &cancel_flag);
+        if cancel_flag.load(Ordering::Relaxed) {
+            return Err(anyhow::anyhow!("Conversion cancelled"));
+        }
+        gain
+    } else {
+        None
+    };
+
+    if let Some(folder) = output.parent() {
+        fs::create_dir_all(folder).with_context(|| format!("Failed to create output folder {:?}", folder))?;
+    }
+    let input_bytes: Vec<u64> = inputs.iter().map(|input| metadata::audio_bytes(input)).collect();
+    progress.on_batch_start(&input_bytes);
+    // Written under a temporary name, so a failed or cancelled run leaves an earlier image as it was
+    let partial = conversion::partial_path(output);
+    let mut bin = BufWriter::new(File::create(&partial).with_context(|| format!("Failed to create {:?}", partial))?);
+    // Each track is decoded here first so a failed or cancelled decode never leaves half a track in the BIN
+    let scratch = output.with_extension("track.raw");
+    let mut tracks = Vec::new();
+    // Crossfade mode only: where each track's INDEX 01 falls, in bytes after the track 1 pregap
+    let mut track_starts = Vec::new();
+    // detect_gaps only: the level, in dBFS, at the end of the last track added
+    let mut previous_tail_db = None;
+
+    for (index, input) in inputs.iter().enumerate() {
+        conversion::wait_while_paused(&pause_flag, &cancel_flag);
+        if cancel_flag.load(Ordering::Relaxed) {
+            break;
+        }
+        // Stopping between tracks still finishes the image, just with fewer tracks on it
+        if stop_flag.load(Ordering::Relaxed) {
+            log_info!("Stopping the image after track {}", index);
+            summary.not_started = inputs.len() - index;
+            break;
+        }
+
+        progress.on_file_start(index, inputs.len(), input);
+        let report = |done: u64, total: u64| progress.on_progress(index, done, total);
+
+        let decoded = conversion::with_disk_full_pause(&scratch, &cancel_flag, &pause_flag, progress, || {
+            conversion::with_io_retry(input, options, || {
+                decode_track(input, &scratch, album_gain, &cancel_flag, &pause_flag, options, &report)
+            })
+        });
+        let stream_info = match decoded {
+            Ok(stream_info) => stream_info,
+            Err(e) => {
+                log_error!("Failed to convert {:?}: {:?}", input, e);
+                summary.record_failure(input, format!("{:#}", e));
+                progress.on_file_done(index, input, false);
+                continue;
+            }
+        };
+
+        if catalog.is_none() {
+            catalog = stream_info.track.catalog.clone();
+        }
+        // Failures past this point leave the BIN inconsistent, so they abort the whole image
+        if let Some(crossfader) = crossfader.as_mut() {
+            if tracks.is_empty() {
+                bin.write_all(&vec![0; (options.first_track_pregap_frames * CD_SECTOR_BYTES) as usize])?;
+            }
+            let start = crossfader
+                .append(&scratch, &mut bin)
+                .with_context(|| format!("Failed to append {:?} to {:?}", input, output))?;
+            track_starts.push(start);
+            log_info!("Added {:?} to image as track {}, crossfaded", input, tracks.len() + 1);
+            tracks.push(CueTrack {
+                file: output.to_path_buf(),
+                title: stream_info.track.title.clone(),
+                performer: stream_info.track.artist.clone(),
+                frames: 0, // Known once the next track's crossfade places its boundary
+                pregap_frames: 0,
+                gap_frames: if tracks.is_empty() { options.first_track_pregap_frames } else { 0 },
+                isrc: stream_info.track.isrc.clone(),
+            });
+            summary.tracks.push(image_track(input, output, 0, stream_info.sample_rate));
+            summary.converted += 1;
+            progress.on_file_done(index, input, true);
+            continue;
+        }
+        let edges_db = options.detect_gaps.then(|| edge_levels_db(&scratch)).flatten();
+        let gap_frames = match (tracks.len(), previous_tail_db, edges_db) {
+            (0, ..) => options.first_track_pregap_frames,
+            (_, Some(tail_db), Some((head_db, _))) => {
+                let gap = detected_gap(tail_db, head_db, options.silence_threshold_db);
+                log_info!(
+                    "Gap before track {}: {} frames (previous track ends at {:.1} dBFS, {:?} starts at {:.1} dBFS, silence below {:.1} dB)",
+                    tracks.len() + 1,
+                    gap,
+                    tail_db,
+                    input,
+                    head_db,
+                    options.silence_threshold_db
+                );
+                gap
+            }
+            _ if options.detect_gaps => {
+                log_warn!("Could not tell whether {:?} follows on from the track before; using a {}-frame gap", input, options.gap_frames);
+                options.gap_frames
+            }
+            _ => options.gap_frames,
+        };
+        previous_tail_db = edges_db.map(|(_, tail_db)| tail_db);
+        bin.write_all(&vec![0; (gap_frames * CD_SECTOR_BYTES) as usize])?;
+        let signal = match wav::raw_cdda_stats(&scratch) {
+            Ok(signal) => Some(signal),
+            Err(e) => {
+                log_warn!("Could not measure the peak level of {:?}: {:?}", input, e);
+                None
+            }
+        };
+        let written = io::copy(&mut File::open(&scratch)?, &mut bin)
+            .with_context(|| format!("Failed to append {:?} to {:?}", input, output))?;
+        let mut frames = written.div_ceil(CD_SECTOR_BYTES);
+        let mut frames_written = written / FRAME_BYTES as u64;
+        if options.short_tracks == ShortTrackPolicy::Pad && frames < cue::MIN_TRACK_FRAMES {
+            log_info!("Padding {:?} with silence to the 4 s CD minimum", input);
+            frames = cue::MIN_TRACK_FRAMES;
+            frames_written = frames * conversion::SAMPLES_PER_CD_FRAME;
+        }
+        bin.write_all(&vec![0; (frames * CD_SECTOR_BYTES - written) as usize])?;
+        log_info!("Added {:?} to image as track {} ({} frames)", input, tracks.len() + 1, frames);
+
+        tracks.push(CueTrack {
+            file: output.to_path_buf(),
+            title: stream_info.track.title.clone(),
+            performer: stream_info.track.artist.clone(),
+            frames,
+            pregap_frames: 0,
+            gap_frames,
+            isrc: stream_info.track.isrc.clone(),
+        });
+        summary.tracks.push(TrackResult {
+            signal,
+            ..image_track(input, output, frames_written, stream_info.sample_rate)
+        });
+        summary.converted += 1;
+        progress.on_file_done(index, input, true);
+    }
+
+    let _ = fs::remove_file(&scratch);
+    // A BIN missing its last tracks would burn as a complete-looking disc
+    if cancel_flag.load(Ordering::Relaxed) {
+        log_info!("Image conversion cancelled; removing {:?}", partial);
+        drop(bin);
+        let _ = fs::remove_file(&partial);
+        return Err(anyhow::anyhow!("Conversion cancelled"));
+    }
+    if let Some(crossfader) = crossfader {
+        let length = crossfader.finish(&mut bin)?;
+        let total_frames = length.div_ceil(CD_SECTOR_BYTES);
+        bin.write_all(&vec![0; (total_frames * CD_SECTOR_BYTES - length) as usize])?;
+        // Boundaries snap to the nearest sector, as the CUE can't address anything finer
+        let mut boundaries: Vec<u64> = track_starts
+            .iter()
+            .map(|start| (start + CD_SECTOR_BYTES / 2) / CD_SECTOR_BYTES)
+            .collect();
+        boundaries.push(total_frames);
+        for ((track, result), bounds) in tracks.iter_mut().zip(&mut summary.tracks).zip(boundaries.windows(2)) {
+            track.frames = bounds[1].saturating_sub(bounds[0]);
+            // Overlapping tracks have no length of their own; what the CUE gives them is the best there is
+            result.frames_written = track.frames * conversion::SAMPLES_PER_CD_FRAME;
+        }
+    }
+    bin.flush().with_context(|| format!("Failed to write {:?}", partial))?;
+    drop(bin);
+    if tracks.is_empty() {
+        let _ = fs::remove_file(&partial);
+        return Err(anyhow::anyhow!("None of the inputs could be converted for {:?}", output));
+    }
+    if options.existing_outputs == ExistingOutputPolicy::NumberedBackup {
+        conversion::backup_existing(output)?;
+        conversion::backup_existing(&output.with_extension("cue"))?;
+    }
+    fs::rename(&partial, output).with_context(|| format!("Failed to rename {:?} to {:?}", partial, output))?;
+    cue::write_cue_sheet(&tracks, catalog.as_deref(), &output.with_extension("cue"))?;
+    Ok(summary)
+}
+
+fn image_track(input: &Path, image: &Path, frames_written: u64, source_rate: u32) -> TrackResult {
+    TrackResult {
+        input: input.to_path_buf(),
+        output_path: image.to_path_buf(),
+        frames_written,
+        channels: 2,
+        sample_rate: 44100,
+        source_rate,
+        signal: None,
+    }
+}
+
+fn decode_track(
+    input: &Path,
+    raw_output: &Path,
+    album_gain: Option<f64>,
+    cancel_flag: &Arc<AtomicBool>,
+    pause_flag: &Arc<AtomicBool>,
+    options: &ConversionOptions,
+    report_progress: &dyn Fn(u64, u64),
+) -> Result<conversion::StreamInfo> {
+    let mut stream_info = probe_for_image(input, options)?;
+    stream_info.album_gain = album_gain;
+    let mut command = conversion::ffmpeg_command(input, &stream_info, options)?;
+    command.args([
+        "-f", "s16le",
+        "-acodec", "pcm_s16le",
+        "-ac", "2",
+        "-ar", "44100",
+        "-y",
+    ]);
+    command.arg(raw_output);
+    conversion::run_ffmpeg(command, input, stream_info.duration, cancel_flag, pause_flag, report_progress)?;
+    if fs::metadata(raw_output)?.len() == 0 {
+        return Err(anyhow::anyhow!("{:?} decoded to no audio at all", input));
+    }
+    Ok(stream_info)
+}
+
+fn probe_for_image(input: &Path, options: &ConversionOptions) -> Result<conversion::StreamInfo> {
+    let stream_info = conversion::probe_stream(input)?;
+    conversion::check_resampling_allowed(&stream_info, options)?;
+    Ok(match options.time_range {
+        Some(range) => stream_info.for_range(range),
+        None => stream_info,
+    })
+}
+
+// One stereo frame of 16-bit samples
+const FRAME_BYTES: usize = 4;
+
+// Levels, in dBFS, of the first and last EDGE_SECONDS of a decoded track
+fn edge_levels_db(track: &Path) -> Option<(f64, f64)> {
+    let bytes = (EDGE_SECONDS * 44100.0).round() as u64 * FRAME_BYTES as u64;
+    match wav::raw_cdda_edge_peaks(track, bytes) {
+        Ok((head, tail)) => Some((20.0 * head.log10(), 20.0 * tail.log10())),
+        Err(e) => {
+            log_warn!("Could not measure the edges of {:?}: {:?}", track, e);
+            None
+        }
+    }
+}
+
+// Sound right up to the boundary on either side means the tracks were cut from one continuous
+// recording, as on live albums, so they are joined without a gap
+fn detected_gap(tail_db: f64, head_db: f64, threshold_db: f64) -> u64 {
+    if tail_db > threshold_db || head_db > threshold_db {
+        0
+    } else {
+        DISCRETE_GAP_FRAMES
+    }
+}
+
+// Joins tracks with overlapping equal-power crossfades. The last window of each track is held
+// back until the next track arrives to be mixed into it, so memory use is two windows (held tail
+// plus the next track's head) at 176,400 bytes per second of crossfade, about 1.8 MB for 5 s;
+// the rest of every track is streamed straight from the scratch file.
+struct Crossfader {
+    window: usize,
+    held: Vec<u8>,
+    // Bytes written to the BIN so far, not counting the pregap
+    written: u64,
+}
+
+impl Crossfader {
+    fn new(seconds: f64) -> Self {
+        Self {
+            window: (seconds * 44100.0).round() as usize * FRAME_BYTES,
+            held: Vec::new(),
+            written: 0,
+        }
+    }
+
+    // Appends one raw track and returns where its boundary with the previous track lies: the
+    // middle of their crossfade, in bytes from the start of the first track.
+    fn append(&mut self, track_path: &Path, out: &mut impl Write) -> Result<u64> {
+        let mut track = File::open(track_path)?;
+        let length = track.metadata()?.len() as usize / FRAME_BYTES * FRAME_BYTES;
+
+        // A track shorter than the window overlaps only as far as it reaches
+        let overlap = self.held.len().min(length);
+        let unmixed = self.held.len() - overlap;
+        out.write_all(&self.held[..unmixed])?;
+        self.written += unmixed as u64;
+        let start = self.written + overlap as u64 / 2;
+
+        let mut head = vec![0u8; overlap];
+        track.read_exact(&mut head)?;
+        mix_equal_power(&self.held[unmixed..], &mut head);
+        out.write_all(&head)?;
+        self.written += overlap as u64;
+
+        let rest = length - overlap;
+        let tail = self.window.min(rest);
+        self.written += io::copy(&mut (&mut track).take((rest - tail) as u64), out)?;
+        self.held = vec![0u8; tail];
+        track.read_exact(&mut self.held)?;
+        Ok(start)
+    }
+
+    // Writes the last track's held tail and returns the total audio length in bytes
+    fn finish(self, out: &mut impl Write) -> Result<u64> {
+        out.write_all(&self.held)?;
+        Ok(self.written + self.held.len() as u64)
+    }
+}
+
+// Fades `outgoing` out and `incoming` in over their common length, mixing the result into `incoming`
+fn mix_equal_power(outgoing: &[u8], incoming: &mut [u8]) {
+    let frames = incoming.len() / FRAME_BYTES;
+    for frame in 0..frames {
+        let angle = (frame as f64 + 0.5) / frames as f64 * std::f64::consts::FRAC_PI_2;
+        let (fade_out, fade_in) = (angle.cos(), angle.sin());
+        for offset in (frame * FRAME_BYTES..(frame + 1) * FRAME_BYTES).step_by(2) {
+            let a = f64::from(i16::from_le_bytes([outgoing[offset], outgoing[offset + 1]]));
+            let b = f64::from(i16::from_le_bytes([incoming[offset], incoming[offset + 1]]));
+            let mixed = (a * fade_out + b * fade_in).round().clamp(f64::from(i16::MIN), f64::from(i16::MAX));
+            incoming[offset..offset + 2].copy_from_slice(&(mixed as i16).to_le_bytes());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::progress::PlanCollector;
+    use crate::testing;
+
+    fn convert(inputs: Vec<PathBuf>, image: &Path, options: &ConversionOptions) -> Result<ConversionSummary> {
+        let unset = || Arc::new(AtomicBool::new(false));
+        convert_to_image(inputs, image, unset(), unset(), unset(), options, &PlanCollector::default())
+    }
+
+    #[test]
+    #[ignore = "needs ffmpeg"]
+    fn failed_image_leaves_the_old_one_in_place() {
+        let dir = testing::temp_dir("image-failed");
+        let image = dir.join("disc.bin");
+        fs::write(&image, b"old image").unwrap();
+        let broken = dir.join("broken.wav");
+        fs::write(&broken, b"not audio").unwrap();
+        let options = ConversionOptions { existing_outputs: ExistingOutputPolicy::NumberedBackup, ..testing::options_into(&dir) };
+
+        assert!(convert(vec![broken], &image, &options).is_err());
+        assert_eq!(fs::read(&image).unwrap(), b"old image");
+        assert!(!dir.join("disc.bin.~1~").exists());
+        assert!(!conversion::partial_path(&image).exists());
+    }
+
+    #[test]
+    #[ignore = "needs ffmpeg"]
+    fn finished_image_backs_up_the_old_one() {
+        let dir = testing::temp_dir("image-backup");
+        let image = dir.join("disc.bin");
+        fs::write(&image, b"old image").unwrap();
+        let input = dir.join("track.wav");
+        testing::write_wav(&input, 44100, 2, &testing::sine(44100, 440.0, 0.5, 44100, 2));
+        let options = ConversionOptions { existing_outputs: ExistingOutputPolicy::NumberedBackup, ..testing::options_into(&dir) };
+
+        let summary = convert(vec![input], &image, &options).unwrap();
+        assert_eq!(summary.converted, 1);
+        assert_eq!(fs::read(dir.join("disc.bin.~1~")).unwrap(), b"old image");
+        assert!(fs::metadata(&image).unwrap().len() > 44100 * 4);
+        assert!(dir.join("disc.cue").exists());
+    }
+}
@@ -0,0 +1,27 @@
+// The conversion engine behind the mp32cdda GUI and command line, usable on its own.
+// convert_files and convert_to_image are the entry points; pipeline has the same steps on
+// in-memory samples.
+
+#[macro_use]
+pub mod logging; // Declared first so its macros are in scope for the modules below
+mod art;
+pub mod burn;
+pub mod conversion;
+pub mod cue;
+pub mod disc;
+pub mod image;
+mod loudness;
+mod metadata;
+pub mod naming;
+pub mod pipeline;
+pub mod playlist;
+pub mod priority;
+pub mod progress;
+pub mod queue;
+mod silence;
+mod split;
+#[cfg(test)]
+mod testing;
+pub mod verify;
+pub mod wav;
+pub mod waveform;
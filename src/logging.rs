@@ -1,22 +1,14 @@
 use log::LevelFilter;
-use env_logger::Builder;
-use std::io::Write;
+use env_logger::{Builder, Target};
+use std::fmt::Write as _;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use anyhow::{Context, Result};
+use crate::wav::SignalStats;
 
-pub fn initialize_logger() {
-    Builder::new()
-        .format(|buf, record| {
-            writeln!(
-                buf,
-                "{} [{}] - {}: {}",
-                chrono::Utc::now().format("%Y-%m-%d %H:%M:%S"),
-                record.level(),
-                record.target(),
-                record.args()
-            )
-        })
-        .filter(None, LevelFilter::Info) // Default to Info; override with RUST_LOG
-        .init();
-}
+pub const CONVERSION_LOG_NAME: &str = "conversion.log";
 
 #[macro_export]
 macro_rules! log_info {
@@ -56,4 +48,139 @@ macro_rules! log_error {
     ($fmt:expr, $($arg:tt)*) => {
         log::error!(target: "mp32cdda", $fmt, $($arg)*);
     };
-}
\ No newline at end of file
+}
+
+// Where log lines are copied besides the console; set and cleared at runtime from the GUI
+static LOG_FILE: Mutex<Option<File>> = Mutex::new(None);
+
+// Writes every log line to the console and, when one is set, to LOG_FILE.
+struct TeeWriter {
+    console: Box<dyn Write + Send>,
+}
+
+impl Write for TeeWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if let Some(file) = LOG_FILE.lock().unwrap().as_mut() {
+            // A failing log file must not take console logging down with it
+            let _ = file.write_all(buf);
+        }
+        self.console.write_all(buf)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if let Some(file) = LOG_FILE.lock().unwrap().as_mut() {
+            let _ = file.flush();
+        }
+        self.console.flush()
+    }
+}
+
+pub fn initialize_logger(target: Target, level: LevelFilter, log_file: Option<&Path>) {
+    let console: Box<dyn Write + Send> = match target {
+        Target::Stdout => Box::new(io::stdout()),
+        _ => Box::new(io::stderr()),
+    };
+    Builder::new()
+        .target(Target::Pipe(Box::new(TeeWriter { console })))
+        .format(|buf, record| {
+            writeln!(
+                buf,
+                "{} [{}] - {}: {}",
+                chrono::Utc::now().format("%Y-%m-%d %H:%M:%S"),
+                record.level(),
+                record.target(),
+                record.args()
+            )
+        })
+        // Everything passes the builder; set_log_level does the filtering so it can change at runtime
+        .filter(None, LevelFilter::Trace)
+        .init();
+    set_log_level(level);
+
+    if let Some(path) = log_file {
+        if let Err(e) = set_log_file(Some(path)) {
+            log_error!("{:?}", e);
+        }
+    }
+}
+
+pub fn set_log_level(level: LevelFilter) {
+    log::set_max_level(level);
+}
+
+// Appends to the file so earlier sessions are kept; None stops copying logs to a file.
+pub fn set_log_file(path: Option<&Path>) -> Result<()> {
+    let file = match path {
+        Some(path) => Some(
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .with_context(|| format!("Failed to open log file {:?}", path))?,
+        ),
+        None => None,
+    };
+    *LOG_FILE.lock().unwrap() = file;
+    if let Some(path) = path {
+        log_info!("Saving log to {:?}", path);
+    }
+    Ok(())
+}
+
+// One entry in the archival conversion.log
+pub struct TrackReport {
+    pub source: PathBuf,
+    pub source_size: u64,
+    pub sample_rate: u32,
+    pub output_sample_rate: u32,
+    pub output: PathBuf,
+    pub duration_seconds: f64,
+    pub signal: Option<SignalStats>,
+    // CRC32 of the output's PCM data chunk
+    pub crc32: u32,
+}
+
+// EAC-style plain-text log, so a folder can later be checked against the CRCs it was written with.
+pub fn write_conversion_report(folder: &Path, tracks: &[&TrackReport]) -> Result<()> {
+    let mut report = String::new();
+    writeln!(report, "mp32cdda {} conversion log", env!("CARGO_PKG_VERSION"))?;
+    writeln!(report, "Written {}", chrono::Local::now().format("%Y-%m-%d %H:%M:%S"))?;
+
+    for (index, track) in tracks.iter().enumerate() {
+        let minutes = (track.duration_seconds / 60.0).floor();
+        writeln!(report)?;
+        writeln!(report, "Track {:2}", index + 1)?;
+        writeln!(report, "     Source        {} ({} bytes)", track.source.display(), track.source_size)?;
+        if track.sample_rate == track.output_sample_rate {
+            writeln!(report, "     Sample rate   {} Hz", track.sample_rate)?;
+        } else {
+            let direction = if track.sample_rate > track.output_sample_rate { "downsampled" } else { "upsampled, check the source" };
+            writeln!(report, "     Sample rate   {} Hz, {} to {} Hz", track.sample_rate, direction, track.output_sample_rate)?;
+        }
+        writeln!(report, "     Output        {}", track.output.display())?;
+        writeln!(report, "     Length        {:02}:{:06.3}", minutes, track.duration_seconds - minutes * 60.0)?;
+        if let Some(signal) = &track.signal {
+            if signal.clips() {
+                writeln!(report, "     Peak          {:.2} dBFS, clipped {} times", signal.peak_dbfs(), signal.clipped_runs)?;
+            } else {
+                writeln!(report, "     Peak          {:.2} dBFS", signal.peak_dbfs())?;
+            }
+            if let Some(correlation) = signal.stereo_correlation {
+                writeln!(report, "     L/R corr.     {:.0}%", correlation * 100.0)?;
+            }
+            if let Some(dr) = signal.dynamic_range_db {
+                let note = if signal.is_compressed() { ", heavily compressed" } else { "" };
+                writeln!(report, "     Dyn. range    {:.1} dB{}", dr, note)?;
+            }
+        }
+        writeln!(report, "     Copy CRC      {:08X}", track.crc32)?;
+    }
+
+    writeln!(report)?;
+    writeln!(report, "{} tracks", tracks.len())?;
+    let path = folder.join(CONVERSION_LOG_NAME);
+    fs::write(&path, report).with_context(|| format!("Failed to write conversion log {:?}", path))?;
+    log_info!("Wrote conversion log with {} tracks: {:?}", tracks.len(), path);
+    Ok(())
+}
@@ -0,0 +1,77 @@
+use std::path::Path;
+use std::process::Command;
+use anyhow::{Context, Result};
+
+// Common playback level for the whole disc; ReplayGain 2.0 uses the same reference.
+pub const TARGET_LUFS: f64 = -18.0;
+const REPLAYGAIN_REFERENCE_LUFS: f64 = -18.0;
+// Gain is capped so the loudest peak stays just under full scale instead of clipping
+const PEAK_CEILING_DBFS: f64 = -0.1;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Loudness {
+    pub integrated_lufs: f64,
+    pub peak_dbfs: f64,
+}
+
+impl Loudness {
+    pub fn from_replaygain(track_gain_db: f64, track_peak: Option<f64>) -> Self {
+        Loudness {
+            integrated_lufs: REPLAYGAIN_REFERENCE_LUFS - track_gain_db,
+            peak_dbfs: track_peak.map_or(0.0, |peak| 20.0 * peak.log10()),
+        }
+    }
+
+    // Returns the gain in dB that brings the track to `target`, or None for silent/unmeasurable input.
+    pub fn gain_to(&self, target: f64) -> Option<f64> {
+        let gain = (target - self.integrated_lufs).min(PEAK_CEILING_DBFS - self.peak_dbfs);
+        gain.is_finite().then_some(gain)
+    }
+}
+
+// Loudness of tracks played back to back, each given with its length in seconds. Integrated
+// loudness is mean energy over time, so every track counts in proportion to its length; the
+// peak is the loudest of them. None when there is nothing with a length to combine.
+pub fn combine(tracks: &[(Loudness, f64)]) -> Option<Loudness> {
+    let seconds: f64 = tracks.iter().map(|(_, seconds)| seconds).sum();
+    if seconds <= 0.0 {
+        return None;
+    }
+    let energy: f64 = tracks
+        .iter()
+        .map(|(loudness, seconds)| seconds * 10f64.powf(loudness.integrated_lufs / 10.0))
+        .sum();
+    Some(Loudness {
+        integrated_lufs: 10.0 * (energy / seconds).log10(),
+        peak_dbfs: tracks.iter().map(|(loudness, _)| loudness.peak_dbfs).fold(f64::NEG_INFINITY, f64::max),
+    })
+}
+
+// First pass: decode the whole file through ffmpeg's EBU R128 meter without writing anything.
+pub fn measure_loudness(path: &Path) -> Result<Loudness> {
+    let output = Command::new("ffmpeg")
+        .args(["-hide_banner", "-nostats", "-i"])
+        .arg(path)
+        .args(["-af", "loudnorm=print_format=json", "-f", "null", "-"])
+        .output()
+        .context("Failed to run ffmpeg loudness scan")?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if !output.status.success() {
+        return Err(anyhow::anyhow!("ffmpeg loudness scan failed: {}", stderr));
+    }
+
+    Ok(Loudness {
+        integrated_lufs: json_number(&stderr, "input_i")
+            .ok_or_else(|| anyhow::anyhow!("No integrated loudness in ffmpeg output"))?,
+        peak_dbfs: json_number(&stderr, "input_tp")
+            .ok_or_else(|| anyhow::anyhow!("No true peak in ffmpeg output"))?,
+    })
+}
+
+// loudnorm prints its measurements as a flat JSON object with quoted numbers.
+fn json_number(text: &str, key: &str) -> Option<f64> {
+    let pattern = format!("\"{}\" : \"", key);
+    let start = text.rfind(&pattern)? + pattern.len();
+    let end = start + text[start..].find('"')?;
+    text[start..end].trim().parse().ok()
+}
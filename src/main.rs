@@ -1,33 +1,167 @@
 #[macro_use]
-mod conversion; // Added #[macro_use] to propagate macros to conversion module
-#[macro_use]
-mod logging;
+extern crate mp32cdda; // For the log_* macros
+mod cli;
+mod settings;
 
+use clap::Parser;
+use mp32cdda::{burn, conversion, cue, disc, image, logging, naming, playlist, priority, progress, queue, verify, waveform};
+use log::LevelFilter;
 use eframe::{egui, App, Frame};
 use rfd::FileDialog;
 use single_instance::SingleInstance;
+use std::collections::HashMap;
+use std::fs;
 use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 use std::thread;
+use std::time::{Instant, SystemTime};
+
+// Length of the audition clips written by the Preview button
+const PREVIEW_SECONDS: f64 = 30.0;
+const WAVEFORM_POINTS: usize = 240;
+const WAVEFORM_SIZE: egui::Vec2 = egui::vec2(120.0, 16.0);
+
+// A file's waveform thumbnail, and the modification time of the file it was made from
+enum Thumbnail {
+    Pending,
+    // None if the file couldn't be decoded
+    Ready(Option<SystemTime>, Option<waveform::Waveform>),
+}
+
+struct FileProgress {
+    total_files: usize,
+    file_name: String,
+    fraction: f32,
+}
+
+type ThumbnailResult = (PathBuf, Option<SystemTime>, Option<waveform::Waveform>);
 
 struct ConverterApp {
     selected_files: Vec<PathBuf>,
     is_processing: bool,
     progress_message: String,
     last_error: Option<String>,
-    cancel_flag: Arc<Mutex<bool>>,
-    instance_guard: SingleInstance,
+    cancel_flag: Arc<AtomicBool>,
+    pause_flag: Arc<AtomicBool>,
+    // "Stop after this track": files in progress finish and are kept, nothing further starts
+    stop_flag: Arc<AtomicBool>,
+    // The output file being written when the volume filled up; the batch is paused until resumed
+    disk_full: Option<PathBuf>,
+    options: conversion::ConversionOptions,
+    // UPC/EAN typed for the CUE sheet; checked when a batch is queued
+    catalog_input: String,
+    // Output file name template as typed; options.name_template follows it while it parses
+    name_template_input: String,
+    // Write one BIN/CUE image instead of a WAV per track
+    build_image: bool,
+    progress_receiver: Option<Receiver<progress::ProgressUpdate>>,
+    // Latest update of the running batch, for the overall bar
+    progress: Option<progress::ProgressUpdate>,
+    // Files being converted right now, by their index in the batch; finished ones are removed
+    active_files: HashMap<usize, FileProgress>,
+    // When the running batch started, for the time-left estimate
+    batch_started: Option<Instant>,
+    // Batches waiting for the worker thread, shared with it
+    queue: Arc<Mutex<queue::Queue>>,
+    // Every batch queued since the list was last cleared, for display
+    batches: Vec<queue::QueuedBatch>,
+    next_batch_id: usize,
+    batch_events: Option<Receiver<queue::BatchEvent>>,
+    // Files that failed since the queue last went idle, with the reason
+    failures: Vec<(PathBuf, String)>,
+    // Tracks written since then that clip, are nearly mono or too short to burn, with what's wrong
+    flagged: Vec<(PathBuf, Vec<String>)>,
+    verify_reports: Option<Vec<verify::CddaReport>>,
+    disc_capacity: disc::DiscCapacity,
+    // The selection, and the folder being appended to, that the current estimate was started for;
+    // a change to either restarts it
+    play_time_for: (Vec<PathBuf>, Option<PathBuf>),
+    play_time: Option<disc::PlayTime>,
+    play_time_receiver: Option<Receiver<disc::PlayTime>>,
+    show_waveforms: bool,
+    // Made only for rows scrolled into view, one at a time on a background thread
+    thumbnails: HashMap<PathBuf, Thumbnail>,
+    thumbnail_worker: Option<(Sender<PathBuf>, Receiver<ThumbnailResult>)>,
+    // Result of the last preview (dry run)
+    plan: Option<Vec<conversion::PlannedFile>>,
+    plan_receiver: Option<Receiver<Vec<conversion::PlannedFile>>>,
+    burner: Arc<dyn burn::BurnBackend>,
+    burn_devices: Vec<burn::BurnDevice>,
+    burn_device: Option<burn::BurnDevice>,
+    // Set while a drive scan or burn is running
+    burn_receiver: Option<Receiver<burn::BurnEvent>>,
+    // Recent burner output, oldest first
+    burn_output: Vec<String>,
+    // Open the tray after a successful burn; a failed one stays in the drive to be looked at
+    eject_when_done: bool,
+    log_level: LevelFilter,
+    // Log lines are copied here as well as to stderr
+    log_file: Option<PathBuf>,
+    // Held for its lock only. None when the platform lock couldn't be created; the app then runs unguarded
+    _instance_guard: Option<SingleInstance>,
+    // Set at startup when another instance holds the lock, until the user chooses to run anyway
+    other_instance_running: bool,
+    // Last settings written to disk; compared each frame so changes are saved as they are made
+    saved_settings: settings::Settings,
 }
 
 impl Default for ConverterApp {
     fn default() -> Self {
+        let instance_guard = match SingleInstance::new("mp3_to_cdda_converter") {
+            Ok(guard) => Some(guard),
+            Err(e) => {
+                log_warn!("Could not check for other running instances, continuing anyway: {}", e);
+                None
+            }
+        };
+        let other_instance_running = instance_guard.as_ref().is_some_and(|guard| !guard.is_single());
+
         Self {
             selected_files: Vec::new(),
             is_processing: false,
-            progress_message: "Ready to convert MP3 files to CDDA".to_string(),
+            progress_message: "Ready to convert audio files to CDDA".to_string(),
             last_error: None,
-            cancel_flag: Arc::new(Mutex::new(false)),
-            instance_guard: SingleInstance::new("mp3_to_cdda_converter").unwrap(),
+            cancel_flag: Arc::new(AtomicBool::new(false)),
+            pause_flag: Arc::new(AtomicBool::new(false)),
+            stop_flag: Arc::new(AtomicBool::new(false)),
+            disk_full: None,
+            options: conversion::ConversionOptions::default(),
+            catalog_input: String::new(),
+            name_template_input: String::new(),
+            build_image: false,
+            progress_receiver: None,
+            progress: None,
+            active_files: HashMap::new(),
+            batch_started: None,
+            queue: Arc::new(Mutex::new(queue::Queue::default())),
+            batches: Vec::new(),
+            next_batch_id: 0,
+            batch_events: None,
+            failures: Vec::new(),
+            flagged: Vec::new(),
+            verify_reports: None,
+            disc_capacity: disc::DiscCapacity::default(),
+            play_time_for: (Vec::new(), None),
+            play_time: None,
+            play_time_receiver: None,
+            show_waveforms: false,
+            thumbnails: HashMap::new(),
+            thumbnail_worker: None,
+            plan: None,
+            plan_receiver: None,
+            burner: Arc::from(burn::default_backend()),
+            burn_devices: Vec::new(),
+            burn_device: None,
+            burn_receiver: None,
+            burn_output: Vec::new(),
+            eject_when_done: false,
+            log_level: LevelFilter::Info,
+            log_file: None,
+            _instance_guard: instance_guard,
+            other_instance_running,
+            saved_settings: settings::Settings::default(),
         }
     }
 }
@@ -35,59 +169,465 @@ impl Default for ConverterApp {
 impl ConverterApp {
     fn select_files(&mut self) {
         if let Some(files) = FileDialog::new()
-            .add_filter("MP3 Files", &["mp3"])
+            .add_filter("Audio Files", conversion::SUPPORTED_EXTENSIONS)
+            .add_filter("Playlists", playlist::PLAYLIST_EXTENSIONS)
             .pick_files()
         {
-            self.selected_files = files;
+            // A playlist is replaced by its entries, so the selection shows the order they'll be burned in
+            let mut missing = Vec::new();
+            self.selected_files = Vec::new();
+            for file in files {
+                if playlist::is_playlist(&file) {
+                    let (entries, not_found) = conversion::expand_listing(&file);
+                    self.selected_files.extend(entries);
+                    missing.extend(not_found);
+                } else {
+                    self.selected_files.push(file);
+                }
+            }
             self.progress_message = format!("Selected {} files", self.selected_files.len());
-            self.last_error = None;
+            self.last_error = missing_entries_error(&missing);
+        }
+    }
+
+    // Dropped files and folders add to the selection rather than replacing it
+    fn add_dropped_paths(&mut self, paths: Vec<PathBuf>) {
+        let before = self.selected_files.len();
+        let mut missing = Vec::new();
+        for path in paths {
+            let (files, not_found) = conversion::expand_listing(&path);
+            missing.extend(not_found);
+            for file in files {
+                if !self.selected_files.contains(&file) {
+                    self.selected_files.push(file);
+                }
+            }
         }
+        self.progress_message = format!(
+            "Added {} files ({} selected)",
+            self.selected_files.len() - before,
+            self.selected_files.len()
+        );
+        self.last_error = missing_entries_error(&missing);
+    }
+
+    fn select_output_folder(&mut self) {
+        if let Some(folder) = FileDialog::new().pick_folder() {
+            self.options.output_dir = Some(folder);
+        }
+    }
+
+    fn verify_output_folder(&mut self) {
+        let mut dialog = FileDialog::new();
+        if let Some(folder) = &self.options.output_dir {
+            dialog = dialog.set_directory(folder);
+        }
+        let Some(folder) = dialog.pick_folder() else {
+            return;
+        };
+
+        match verify::verify_folder(&folder) {
+            Ok(reports) => {
+                let failing = reports.iter().filter(|report| !report.is_compliant()).count();
+                self.progress_message = format!("Verified {} files, {} with problems", reports.len(), failing);
+                self.last_error = None;
+                self.verify_reports = Some(reports);
+            }
+            Err(e) => {
+                self.last_error = Some(format!("Verification failed: {}", e));
+                self.verify_reports = None;
+            }
+        }
+    }
+
+    fn start_preview(&mut self, ctx: &egui::Context) {
+        let files = self.selected_files.clone();
+        let options = conversion::ConversionOptions {
+            dry_run: true,
+            ..self.options.clone()
+        };
+        let ctx = ctx.clone();
+        let (sender, receiver) = mpsc::channel();
+        self.plan = None;
+        self.plan_receiver = Some(receiver);
+
+        thread::spawn(move || {
+            let collector = progress::PlanCollector::default();
+            let (cancel_flag, pause_flag, stop_flag) =
+                (Arc::new(AtomicBool::new(false)), Arc::new(AtomicBool::new(false)), Arc::new(AtomicBool::new(false)));
+            if let Err(e) = conversion::convert_files(files, cancel_flag, pause_flag, stop_flag, &options, &collector) {
+                log_error!("Preview failed: {:?}", e);
+            }
+            sender.send(collector.files.into_inner().unwrap()).ok();
+            ctx.request_repaint();
+        });
     }
 
-    fn start_conversion(&mut self) {
+    // Queues the selection as a batch, starting the worker if the queue was idle. A preview
+    // converts only the start of each file, so it always writes separate WAVs.
+    fn queue_conversion(&mut self, ctx: &egui::Context, preview_seconds: Option<f64>) {
         if self.selected_files.is_empty() {
             self.last_error = Some("No files selected".to_string());
             return;
         }
+        let catalog = self.catalog_input.trim();
+        if !catalog.is_empty() {
+            if let Err(e) = cue::normalize_catalog(catalog) {
+                self.last_error = Some(format!("{:#}", e));
+                return;
+            }
+        }
+        self.options.cue_catalog = (!catalog.is_empty()).then(|| catalog.to_string());
+        let template = self.name_template_input.trim();
+        if !template.is_empty() {
+            if let Err(e) = naming::NameTemplate::parse(template) {
+                self.last_error = Some(format!("{:#}", e));
+                return;
+            }
+        }
 
+        let files = if preview_seconds.is_some() {
+            // Kept selected, so the real conversion can follow once the settings sound right
+            self.selected_files.clone()
+        } else {
+            std::mem::take(&mut self.selected_files)
+        };
+        let image_path = if self.build_image && preview_seconds.is_none() {
+            image::default_image_path(&files, &self.options)
+        } else {
+            None
+        };
+        let id = self.next_batch_id;
+        self.next_batch_id += 1;
+        self.batches.push(queue::QueuedBatch {
+            id,
+            label: queue::batch_label(&files),
+            status: queue::BatchStatus::Pending,
+        });
+        self.progress_message = format!("Queued {} files", files.len());
+        self.last_error = None;
+
+        let start_worker = {
+            let mut queue = self.queue.lock().unwrap();
+            queue.pending.push_back(queue::Batch {
+                id,
+                files,
+                options: conversion::ConversionOptions {
+                    preview_seconds,
+                    ..self.options.clone()
+                },
+                image_path,
+            });
+            !std::mem::replace(&mut queue.worker_running, true)
+        };
+        if !start_worker {
+            return;
+        }
+
+        // The previous worker has sent its last event by now; read them before replacing its channel
+        self.poll_queue();
         self.is_processing = true;
-        self.progress_message = "Starting conversion...".to_string();
-        *self.cancel_flag.lock().unwrap() = false;
+        self.failures.clear();
+        self.flagged.clear();
+        let (event_sender, event_receiver) = mpsc::channel();
+        self.batch_events = Some(event_receiver);
+        let (progress_sender, progress_receiver) = mpsc::channel();
+        self.progress_receiver = Some(progress_receiver);
+        self.progress = None;
+        self.active_files.clear();
 
-        let files = self.selected_files.clone();
+        let queue = Arc::clone(&self.queue);
         let cancel_flag = Arc::clone(&self.cancel_flag);
-        let status_sender = self.create_status_sender();
-
+        let pause_flag = Arc::clone(&self.pause_flag);
+        let stop_flag = Arc::clone(&self.stop_flag);
+        let ctx = ctx.clone();
         thread::spawn(move || {
-            if let Err(e) = conversion::convert_files(files, cancel_flag) {
-                status_sender.send(Err(e.to_string())).ok();
-            } else {
-                status_sender.send(Ok("Conversion complete!".to_string())).ok();
-            }
+            queue::run_worker(queue, cancel_flag, pause_flag, stop_flag, event_sender, progress_sender, || ctx.request_repaint());
         });
     }
 
-    fn create_status_sender(&self) -> std::sync::mpsc::Sender<Result<String, String>> {
-        let (sender, receiver) = std::sync::mpsc::channel();
-        let ctx = eframe::egui::Context::default();
+    // Stops the running batch and drops the ones still waiting
+    fn cancel_queue(&mut self) {
+        let dropped: Vec<usize> = {
+            let mut queue = self.queue.lock().unwrap();
+            self.cancel_flag.store(true, Ordering::Relaxed);
+            // Otherwise the next batch would start out paused
+            self.pause_flag.store(false, Ordering::Relaxed);
+            self.disk_full = None;
+            queue.pending.drain(..).map(|batch| batch.id).collect()
+        };
+        for batch in self.batches.iter_mut().filter(|batch| dropped.contains(&batch.id)) {
+            batch.status = queue::BatchStatus::Cancelled;
+        }
+        self.progress_message = "Cancelling...".to_string();
+    }
 
-        ctx.request_repaint();
-        std::thread::spawn(move || {
-            if let Ok(result) = receiver.recv() {
-                ctx.request_repaint();
-                match result {
-                    Ok(msg) => println!("Success: {}", msg),
-                    Err(err) => eprintln!("Error: {}", err),
-                }
+    // Lets the files in progress finish, then ends the batch and drops the queued ones
+    fn stop_after_current(&mut self) {
+        let dropped: Vec<usize> = {
+            let mut queue = self.queue.lock().unwrap();
+            self.stop_flag.store(true, Ordering::Relaxed);
+            queue.pending.drain(..).map(|batch| batch.id).collect()
+        };
+        for batch in self.batches.iter_mut().filter(|batch| dropped.contains(&batch.id)) {
+            batch.status = queue::BatchStatus::Cancelled;
+        }
+        self.progress_message = "Stopping after the current file...".to_string();
+    }
+
+    // Checked every frame; the file is only written when something actually changed
+    fn save_settings_if_changed(&mut self) {
+        let current = settings::Settings::capture(&self.options, self.build_image);
+        if current != self.saved_settings {
+            if let Err(e) = current.save() {
+                log_warn!("Failed to save settings: {:?}", e);
+            }
+            self.saved_settings = current;
+        }
+    }
+
+    fn update_play_time(&mut self, ctx: &egui::Context) {
+        let project = self
+            .selected_files
+            .first()
+            .filter(|_| self.options.appends_to_project() && !self.build_image)
+            .map(|path| conversion::output_folder_for(path, &self.options));
+        if self.play_time_for != (self.selected_files.clone(), project.clone()) {
+            self.play_time_for = (self.selected_files.clone(), project.clone());
+            self.play_time = None;
+            self.play_time_receiver = None;
+            if !self.selected_files.is_empty() {
+                let files = self.selected_files.clone();
+                let ctx = ctx.clone();
+                let (sender, receiver) = mpsc::channel();
+                self.play_time_receiver = Some(receiver);
+                thread::spawn(move || {
+                    sender.send(disc::estimate_play_time(&files, project.as_deref())).ok();
+                    ctx.request_repaint();
+                });
+            }
+        }
+
+        if let Some(receiver) = &self.play_time_receiver {
+            if let Ok(play_time) = receiver.try_recv() {
+                self.play_time = Some(play_time);
+                self.play_time_receiver = None;
             }
+        }
+    }
+
+    fn poll_thumbnails(&mut self) {
+        let Some((_, results)) = &self.thumbnail_worker else {
+            return;
+        };
+        while let Ok((path, modified, waveform)) = results.try_recv() {
+            self.thumbnails.insert(path, Thumbnail::Ready(modified, waveform));
+        }
+    }
+
+    // Queues a scan unless there is a thumbnail made from the file as it is now, or one on the way
+    fn request_thumbnail(&mut self, ctx: &egui::Context, path: &PathBuf) {
+        let modified = fs::metadata(path).and_then(|metadata| metadata.modified()).ok();
+        match self.thumbnails.get(path) {
+            Some(Thumbnail::Pending) => return,
+            Some(Thumbnail::Ready(made_from, _)) if *made_from == modified => return,
+            _ => {}
+        }
+        let (requests, _) = self.thumbnail_worker.get_or_insert_with(|| {
+            let (request_sender, requests) = mpsc::channel::<PathBuf>();
+            let (result_sender, results) = mpsc::channel();
+            let ctx = ctx.clone();
+            thread::spawn(move || {
+                for path in requests {
+                    let modified = fs::metadata(&path).and_then(|metadata| metadata.modified()).ok();
+                    let waveform = match waveform::scan_waveform(&path, WAVEFORM_POINTS) {
+                        Ok(waveform) => Some(waveform),
+                        Err(e) => {
+                            log_debug!("No waveform for {:?}: {:?}", path, e);
+                            None
+                        }
+                    };
+                    if result_sender.send((path, modified, waveform)).is_err() {
+                        break;
+                    }
+                    ctx.request_repaint();
+                }
+            });
+            (request_sender, results)
         });
+        if requests.send(path.clone()).is_ok() {
+            self.thumbnails.insert(path.clone(), Thumbnail::Pending);
+        }
+    }
 
-        sender
+    fn draw_thumbnail(ui: &mut egui::Ui, thumbnail: Option<&Thumbnail>) {
+        let (rect, response) = ui.allocate_exact_size(WAVEFORM_SIZE, egui::Sense::hover());
+        let painter = ui.painter_at(rect);
+        painter.rect_filled(rect, 2.0, ui.visuals().extreme_bg_color);
+        let Some(Thumbnail::Ready(_, Some(waveform))) = thumbnail else {
+            return;
+        };
+        let middle = rect.center().y;
+        let half_height = rect.height() / 2.0;
+        let width = rect.width() / waveform.buckets.len().max(1) as f32;
+        for (index, &(low, high)) in waveform.buckets.iter().enumerate() {
+            let x = rect.left() + (index as f32 + 0.5) * width;
+            let clipped = -low >= waveform::CLIP_LEVEL || high >= waveform::CLIP_LEVEL;
+            let color = if clipped { egui::Color32::RED } else { ui.visuals().text_color() };
+            // At least a pixel, so silence still shows as a flat line
+            let (top, bottom) = (middle - high * half_height, (middle - low * half_height).max(middle - high * half_height + 1.0));
+            painter.line_segment([egui::pos2(x, top), egui::pos2(x, bottom)], egui::Stroke::new(width.max(1.0), color));
+        }
+        if waveform.is_silent() {
+            response.on_hover_text("Silent");
+        } else if waveform.clips() {
+            response.on_hover_text("Reaches full scale, may clip");
+        }
+    }
+
+    fn poll_queue(&mut self) {
+        let Some(receiver) = &self.batch_events else {
+            return;
+        };
+        let mut events = Vec::new();
+        let disconnected = loop {
+            match receiver.try_recv() {
+                Ok(event) => events.push(event),
+                Err(TryRecvError::Empty) => break false,
+                Err(TryRecvError::Disconnected) => break true,
+            }
+        };
+        for event in events {
+            self.apply_batch_event(event);
+        }
+        if !disconnected {
+            return;
+        }
+
+        self.batch_events = None;
+        self.progress_receiver = None;
+        self.progress = None;
+        self.active_files.clear();
+        self.is_processing = false;
+        // A worker that exits normally marks itself idle first; otherwise it died, e.g. it panicked
+        let died = std::mem::replace(&mut self.queue.lock().unwrap().worker_running, false);
+        if died {
+            self.queue.lock().unwrap().pending.clear();
+            for batch in self.batches.iter_mut().filter(|batch| !batch.status.is_finished()) {
+                batch.status = queue::BatchStatus::Failed("Conversion stopped unexpectedly".to_string());
+            }
+            self.progress_message = "Conversion failed".to_string();
+            self.last_error = Some("Conversion stopped unexpectedly".to_string());
+        }
+    }
+
+    fn apply_batch_event(&mut self, event: queue::BatchEvent) {
+        let (id, status) = match event {
+            queue::BatchEvent::Started(id) => {
+                self.progress = None;
+                self.active_files.clear();
+                self.batch_started = Some(Instant::now());
+                (id, queue::BatchStatus::Running)
+            }
+            queue::BatchEvent::DiskFull { id, output } => {
+                self.progress_message = "Paused: the output disk is full".to_string();
+                self.disk_full = Some(output);
+                (id, queue::BatchStatus::Running)
+            }
+            queue::BatchEvent::Finished { id, result: Ok(summary), cancelled, stopped } => {
+                self.disk_full = None;
+                let mut message = format!("{} converted", summary.converted);
+                if summary.skipped > 0 {
+                    message += &format!(", {} already converted", summary.skipped);
+                }
+                if summary.failed() > 0 {
+                    message += &format!(", {} failed", summary.failed());
+                }
+                if summary.not_started > 0 {
+                    message += &format!(", {} not started", summary.not_started);
+                }
+                let resampled: Vec<_> = summary.resampled_tracks().filter_map(|track| track.resampling()).collect();
+                if !resampled.is_empty() {
+                    let upsampled = resampled.iter().filter(|resampling| **resampling == conversion::Resampling::Up).count();
+                    message += &format!(", {} resampled ({} upsampled)", resampled.len(), upsampled);
+                }
+                let flagged: Vec<_> = summary.flagged_tracks().map(|track| (track.input.clone(), track.problems())).collect();
+                if !flagged.is_empty() {
+                    message += &format!(", {} to check", flagged.len());
+                }
+                if !summary.tracks.is_empty() {
+                    message += &format!(", {} total", disc::format_minutes(summary.total_duration_seconds()));
+                }
+                self.progress_message = if cancelled {
+                    format!("Conversion cancelled: {}", message)
+                } else if stopped {
+                    format!("Stopped after the current file: {}", message)
+                } else {
+                    message.clone()
+                };
+                self.last_error = None;
+                self.failures.extend(summary.failures);
+                self.flagged.extend(flagged);
+                (id, if cancelled { queue::BatchStatus::Cancelled } else { queue::BatchStatus::Done(message) })
+            }
+            queue::BatchEvent::Finished { id, result: Err(err), .. } => {
+                self.disk_full = None;
+                self.progress_message = "Conversion failed".to_string();
+                self.last_error = Some(err.clone());
+                (id, queue::BatchStatus::Failed(err))
+            }
+        };
+        if let Some(batch) = self.batches.iter_mut().find(|batch| batch.id == id) {
+            batch.status = status;
+        }
     }
 }
 
 impl App for ConverterApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut Frame) {
+        if let Some(receiver) = &self.progress_receiver {
+            while let Ok(update) = receiver.try_recv() {
+                if update.scanned.is_some() {
+                    self.progress = Some(update);
+                    continue;
+                }
+                if update.finished {
+                    self.active_files.remove(&update.file_index);
+                } else {
+                    self.active_files.insert(
+                        update.file_index,
+                        FileProgress {
+                            total_files: update.total_files,
+                            file_name: update.file_name.clone(),
+                            fraction: update.file_fraction,
+                        },
+                    );
+                }
+                self.progress = Some(update);
+            }
+        }
+        self.poll_queue();
+        self.poll_burn();
+        self.update_play_time(ctx);
+        self.poll_thumbnails();
+        self.save_settings_if_changed();
+        if let Some(receiver) = &self.plan_receiver {
+            if let Ok(plan) = receiver.try_recv() {
+                self.plan = Some(plan);
+                self.plan_receiver = None;
+            }
+        }
+
+        if self.other_instance_running {
+            self.show_instance_warning(ctx);
+            return;
+        }
+
+        let dropped: Vec<PathBuf> = ctx.input(|i| i.raw.dropped_files.iter().filter_map(|f| f.path.clone()).collect());
+        if !dropped.is_empty() {
+            self.add_dropped_paths(dropped);
+        }
+
         egui::CentralPanel::default().show(ctx, |ui| {
             ui.heading("MP3 to CDDA Converter");
 
@@ -95,18 +635,35 @@ impl App for ConverterApp {
                 ui.colored_label(egui::Color32::RED, err);
             }
 
-            if !self.is_processing {
-                self.show_file_selection(ui);
-            } else {
+            if self.is_processing {
                 self.show_conversion_progress(ui);
             }
+            self.show_queue(ui);
+            self.show_file_selection(ui);
 
             ui.separator();
             ui.label(&self.progress_message);
+            if !self.failures.is_empty() {
+                self.show_failures(ui);
+            }
+            if !self.flagged.is_empty() {
+                self.show_flagged(ui);
+            }
+            self.show_burn(ui);
+            self.show_log_settings(ui);
         });
 
-        if !self.instance_guard.is_single() {
-            self.last_error = Some("Another instance is already running".to_string());
+        if ctx.input(|i| !i.raw.hovered_files.is_empty()) {
+            let painter = ctx.layer_painter(egui::LayerId::new(egui::Order::Foreground, egui::Id::new("drop_overlay")));
+            let screen = ctx.screen_rect();
+            painter.rect_filled(screen, 0.0, egui::Color32::from_black_alpha(192));
+            painter.text(
+                screen.center(),
+                egui::Align2::CENTER_CENTER,
+                "Drop audio files here",
+                egui::FontId::proportional(24.0),
+                egui::Color32::WHITE,
+            );
         }
 
         ctx.request_repaint();
@@ -114,46 +671,798 @@ impl App for ConverterApp {
 }
 
 impl ConverterApp {
+    // The lock can be left behind by a crashed instance (notably on Linux), so this is only a warning
+    fn show_instance_warning(&mut self, ctx: &egui::Context) {
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.heading("MP3 to CDDA Converter");
+            ui.colored_label(egui::Color32::RED, "Another instance is already running");
+            ui.label("Two instances converting into the same folder will overwrite each other's files. If no other window is open, a previous run probably crashed and it is safe to continue.");
+            ui.horizontal(|ui| {
+                if ui.button("Run anyway").clicked() {
+                    log_warn!("Running alongside another instance at the user's request");
+                    self.other_instance_running = false;
+                }
+                if ui.button("Quit").clicked() {
+                    ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+                }
+            });
+        });
+    }
+
+    fn show_burn(&mut self, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new("Burn to disc").show(ui, |ui| {
+            let busy = self.burn_receiver.is_some();
+            ui.horizontal(|ui| {
+                if ui.add_enabled(!busy, egui::Button::new("🔍 Find drives")).clicked() {
+                    self.start_burn_task(ui.ctx(), |burner, send| {
+                        send(burn::BurnEvent::Devices(burner.list_devices().map_err(|e| format!("{:#}", e))));
+                    });
+                }
+                let selected = self.burn_device.as_ref().map_or("No drive selected".to_string(), |d| d.description.clone());
+                egui::ComboBox::from_id_source("burn_device")
+                    .selected_text(selected)
+                    .show_ui(ui, |ui| {
+                        for device in &self.burn_devices {
+                            let label = format!("{} ({})", device.description, device.id);
+                            ui.selectable_value(&mut self.burn_device, Some(device.clone()), label);
+                        }
+                    });
+            });
+
+            let can_burn = !busy && !self.is_processing && self.burn_device.is_some();
+            if ui
+                .add_enabled(can_burn, egui::Button::new("💿 Burn CUE sheet..."))
+                .on_hover_text(format!("Write a CUE sheet and its audio to the selected drive with {}", self.burner.name()))
+                .clicked()
+            {
+                self.confirm_and_burn(ui.ctx());
+            }
+            ui.checkbox(&mut self.eject_when_done, "Eject when done")
+                .on_hover_text("Open the tray once the disc burned successfully; after a failure it stays closed");
+            if busy {
+                ui.spinner();
+            }
+
+            if !self.burn_output.is_empty() {
+                egui::ScrollArea::vertical()
+                    .max_height(120.0)
+                    .stick_to_bottom(true)
+                    .show(ui, |ui| {
+                        for line in &self.burn_output {
+                            ui.monospace(line);
+                        }
+                    });
+            }
+        });
+    }
+
+    // Burning is never started without the user confirming the sheet and the drive
+    fn confirm_and_burn(&mut self, ctx: &egui::Context) {
+        let Some(device) = self.burn_device.clone() else {
+            return;
+        };
+        let mut dialog = FileDialog::new().add_filter("CUE sheet", &["cue"]);
+        if let Some(folder) = &self.options.output_dir {
+            dialog = dialog.set_directory(folder);
+        }
+        let Some(cue_sheet) = dialog.pick_file() else {
+            return;
+        };
+        let confirmed = rfd::MessageDialog::new()
+            .set_level(rfd::MessageLevel::Warning)
+            .set_title("Burn disc")
+            .set_description(&format!(
+                "Burn {} to the disc in {} ({})?",
+                cue_sheet.display(),
+                device.description,
+                device.id
+            ))
+            .set_buttons(rfd::MessageButtons::YesNo)
+            .show();
+        if !confirmed {
+            return;
+        }
+
+        self.burn_output.clear();
+        let eject = self.eject_when_done;
+        self.start_burn_task(ctx, move |burner, send| {
+            let result = burner.burn(&cue_sheet, &device, &|line| send(burn::BurnEvent::Output(line.to_string())));
+            if result.is_ok() && eject {
+                // The disc is fine either way, so a stuck tray is only mentioned
+                if let Err(e) = burner.eject(&device) {
+                    log_warn!("Burned, but could not eject: {:?}", e);
+                    send(burn::BurnEvent::Output(format!("Could not eject: {:#}", e)));
+                }
+            }
+            send(burn::BurnEvent::Finished(result.map_err(|e| format!("{:#}", e))));
+        });
+    }
+
+    // Runs a drive scan or burn on a background thread; its events arrive through poll_burn
+    fn start_burn_task(
+        &mut self,
+        ctx: &egui::Context,
+        task: impl FnOnce(&dyn burn::BurnBackend, &(dyn Fn(burn::BurnEvent) + Sync)) + Send + 'static,
+    ) {
+        let (sender, receiver) = mpsc::channel();
+        self.burn_receiver = Some(receiver);
+        let burner = Arc::clone(&self.burner);
+        let ctx = ctx.clone();
+        thread::spawn(move || {
+            let send = |event| {
+                sender.send(event).ok();
+                ctx.request_repaint();
+            };
+            task(burner.as_ref(), &send);
+        });
+    }
+
+    fn poll_burn(&mut self) {
+        let Some(receiver) = &self.burn_receiver else {
+            return;
+        };
+        loop {
+            match receiver.try_recv() {
+                Ok(burn::BurnEvent::Devices(Ok(devices))) => {
+                    if devices.is_empty() {
+                        self.last_error = Some("No CD writers found".to_string());
+                    }
+                    self.burn_device = devices.first().cloned();
+                    self.burn_devices = devices;
+                }
+                Ok(burn::BurnEvent::Devices(Err(e))) => self.last_error = Some(e),
+                Ok(burn::BurnEvent::Output(line)) => {
+                    self.burn_output.push(line);
+                    // Only the tail is shown; the full output is in the log
+                    if self.burn_output.len() > 200 {
+                        self.burn_output.remove(0);
+                    }
+                }
+                Ok(burn::BurnEvent::Finished(Ok(()))) => self.progress_message = "Disc burned".to_string(),
+                Ok(burn::BurnEvent::Finished(Err(e))) => self.last_error = Some(format!("Burning failed: {}", e)),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    self.burn_receiver = None;
+                    break;
+                }
+            }
+        }
+    }
+
+    fn show_log_settings(&mut self, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new("Logging").show(ui, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Log level:");
+                for level in [LevelFilter::Debug, LevelFilter::Info, LevelFilter::Warn] {
+                    if ui.radio_value(&mut self.log_level, level, level.as_str()).changed() {
+                        logging::set_log_level(level);
+                    }
+                }
+            });
+
+            let mut save_to_file = self.log_file.is_some();
+            if ui.checkbox(&mut save_to_file, "Save log to file").changed() {
+                let path = if save_to_file {
+                    FileDialog::new().set_file_name("mp32cdda.log").save_file()
+                } else {
+                    None
+                };
+                match logging::set_log_file(path.as_deref()) {
+                    Ok(()) => self.log_file = path,
+                    Err(e) => {
+                        self.log_file = None;
+                        self.last_error = Some(format!("{:#}", e));
+                    }
+                }
+            }
+            if let Some(path) = &self.log_file {
+                ui.label(format!("Saving to {}", path.display()));
+            }
+        });
+    }
+
     fn show_file_selection(&mut self, ui: &mut egui::Ui) {
         ui.vertical(|ui| {
-            if ui.button("📁 Select MP3 Files").clicked() {
-                self.select_files();
+            ui.horizontal(|ui| {
+                if ui.button("📁 Select Audio Files").clicked() {
+                    self.select_files();
+                }
+                if ui.button("✅ Verify output").on_hover_text("Check the WAVs in a folder are valid CD audio").clicked() {
+                    self.verify_output_folder();
+                }
+            });
+
+            if let Some(reports) = &self.verify_reports {
+                self.show_verify_reports(ui, reports);
             }
 
             if !self.selected_files.is_empty() {
                 ui.separator();
-                ui.label("Selected files:");
-                
+                ui.label("Selected files (in track order):");
+
+                self.show_selected_files(ui);
+
+                self.show_play_time(ui);
+
+                ui.horizontal(|ui| {
+                    if ui.button("📂 Choose output folder").clicked() {
+                        self.select_output_folder();
+                    }
+                    if self.options.output_dir.is_some() && ui.button("Reset").clicked() {
+                        self.options.output_dir = None;
+                    }
+                });
+                match &self.options.output_dir {
+                    Some(folder) => ui.label(format!("Output: {}", folder.display())),
+                    None => ui.label("Output: CDDA_Converted next to the input files"),
+                };
+                ui.add_enabled(!self.build_image, egui::Checkbox::new(&mut self.options.mirror_folders, "Mirror folder structure"))
+                    .on_hover_text("Keep Artist/Album subfolders in the output instead of putting every file in one folder");
+                ui.add_enabled_ui(!self.build_image, |ui| self.show_name_template(ui));
+
+                egui::ComboBox::from_label("Output format")
+                    .selected_text(self.options.output_format.label())
+                    .show_ui(ui, |ui| {
+                        for format in conversion::OutputFormat::ALL {
+                            ui.selectable_value(&mut self.options.output_format, format, format.label());
+                        }
+                    });
+                ui.add_enabled_ui(!self.build_image, |ui| {
+                    egui::ComboBox::from_label("File type")
+                        .selected_text(self.options.output_container.label())
+                        .show_ui(ui, |ui| {
+                            for container in conversion::OutputContainer::ALL {
+                                ui.selectable_value(&mut self.options.output_container, container, container.label());
+                            }
+                        });
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Sample rate");
+                    ui.add(egui::DragValue::new(&mut self.options.target_rate).range(8000..=192000).suffix(" Hz"));
+                    if self.options.target_rate != 44100 {
+                        ui.label("(not CD audio)");
+                        if ui.button("CD").clicked() {
+                            self.options.target_rate = 44100;
+                        }
+                    }
+                });
+                let cdda = self.options.writes_cdda();
+                if !cdda {
+                    // Images and the skip check only deal in CD audio
+                    self.build_image = false;
+                    egui::ComboBox::from_label("Channels")
+                        .selected_text(self.options.channel_mode.label())
+                        .show_ui(ui, |ui| {
+                            for mode in conversion::ChannelMode::ALL {
+                                ui.selectable_value(&mut self.options.channel_mode, mode, mode.label());
+                            }
+                        });
+                }
+                ui.add_enabled(cdda && !self.build_image, egui::Checkbox::new(&mut self.options.skip_existing, "Skip already-converted files"))
+                    .on_hover_text("Keep outputs that already exist and are valid CD audio");
+                egui::ComboBox::from_label("If an output exists")
+                    .selected_text(self.options.existing_outputs.label())
+                    .show_ui(ui, |ui| {
+                        for policy in conversion::ExistingOutputPolicy::ALL {
+                            ui.selectable_value(&mut self.options.existing_outputs, policy, policy.label());
+                        }
+                    })
+                    .response
+                    .on_hover_text("For outputs that aren't skipped as already converted");
+                ui.add_enabled(cdda, egui::Checkbox::new(&mut self.build_image, "Single BIN/CUE image"))
+                    .on_hover_text("Write every track back-to-back into disc.bin with a matching disc.cue for burning");
+                if self.build_image {
+                    ui.checkbox(&mut self.options.detect_gaps, "Detect gaps between tracks")
+                        .on_hover_text("No gap where a track runs into the next, as on live albums; 2 s between tracks that end and start in silence");
+                    let mut gap_seconds = self.options.gap_frames as f64 / cue::CD_FRAMES_PER_SECOND as f64;
+                    let gap = ui.add_enabled(
+                        !self.options.detect_gaps,
+                        egui::Slider::new(&mut gap_seconds, 0.0..=5.0)
+                            .step_by(1.0 / cue::CD_FRAMES_PER_SECOND as f64)
+                            .text("Gap between tracks (s)"),
+                    );
+                    if gap.changed() {
+                        self.options.gap_frames = (gap_seconds * cue::CD_FRAMES_PER_SECOND as f64).round() as u64;
+                    }
+                    ui.add(egui::Slider::new(&mut self.options.crossfade_seconds, 0.0..=10.0).text("Crossfade (s)"))
+                        .on_hover_text("Overlap consecutive tracks for mix CDs; replaces the gap");
+                }
+                ui.add_enabled(cdda && !self.build_image, egui::Checkbox::new(&mut self.options.write_cue, "Write CUE sheet"));
+                if cdda && self.options.write_cue && !self.build_image {
+                    ui.checkbox(&mut self.options.append_to_project, "Append to existing CUE sheet")
+                        .on_hover_text("Number the tracks on from those already in the output folder's CUE sheet and add them to it");
+                }
+                if cdda && (self.options.write_cue || self.build_image) {
+                    let mut pregap_seconds = self.options.first_track_pregap_frames as f64 / cue::CD_FRAMES_PER_SECOND as f64;
+                    let pregap = ui
+                        .add(
+                            egui::Slider::new(&mut pregap_seconds, 0.0..=10.0)
+                                .step_by(1.0 / cue::CD_FRAMES_PER_SECOND as f64)
+                                .text("Pregap before track 1 (s)"),
+                        )
+                        .on_hover_text("Red Book's standard is 2 s; doesn't count against the disc's capacity");
+                    if pregap.changed() {
+                        self.options.first_track_pregap_frames = (pregap_seconds * cue::CD_FRAMES_PER_SECOND as f64).round() as u64;
+                    }
+                    ui.horizontal(|ui| {
+                        ui.label("Catalog (UPC/EAN):");
+                        ui.add(egui::TextEdit::singleline(&mut self.catalog_input).desired_width(120.0))
+                            .on_hover_text("Written as the disc's CATALOG; leave empty to use the barcode tag, if any");
+                    });
+                }
+                ui.checkbox(&mut self.options.save_cover_art, "Save cover art")
+                    .on_hover_text("Copy the embedded album cover into the output folder as folder.jpg or cover.png");
+                ui.checkbox(&mut self.options.normalize, "Normalize volume")
+                    .on_hover_text("Level every track to -18 LUFS using ReplayGain tags or a loudness scan");
+                if self.options.normalize {
+                    ui.horizontal(|ui| {
+                        for mode in conversion::GainMode::ALL {
+                            ui.radio_value(&mut self.options.gain_mode, mode, mode.label());
+                        }
+                    })
+                    .response
+                    .on_hover_text("Album gives every track of an output folder the same gain, keeping their relative levels");
+                }
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut self.options.limit_peaks, "Limit peaks to")
+                        .on_hover_text("Stop hot masters clipping after resampling or normalization");
+                    ui.add_enabled(
+                        self.options.limit_peaks,
+                        egui::DragValue::new(&mut self.options.peak_ceiling_db)
+                            .range(-6.0..=0.0)
+                            .speed(0.05)
+                            .suffix(" dBFS"),
+                    );
+                });
+                ui.checkbox(&mut self.options.remove_dc, "Remove DC offset")
+                    .on_hover_text("High-pass at 10 Hz to take out a constant offset; bass is left alone");
+                ui.add(egui::Slider::new(&mut self.options.left_gain_db, -6.0..=6.0).step_by(0.1).text("Left channel (dB)"))
+                    .on_hover_text("Trim a source that leans to one side; 0 leaves the channel as it is");
+                ui.add(egui::Slider::new(&mut self.options.right_gain_db, -6.0..=6.0).step_by(0.1).text("Right channel (dB)"));
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut self.options.trim_silence, "Trim silence below")
+                        .on_hover_text("Cut leading and trailing silence, e.g. MP3 encoder delay and padding");
+                    ui.add_enabled(
+                        self.options.trim_silence,
+                        egui::DragValue::new(&mut self.options.silence_threshold_db)
+                            .range(-96.0..=-20.0)
+                            .speed(0.5)
+                            .suffix(" dBFS"),
+                    );
+                });
+                ui.horizontal(|ui| {
+                    egui::ComboBox::from_label("Split long files")
+                        .selected_text(self.options.split_mode.label())
+                        .show_ui(ui, |ui| {
+                            for mode in conversion::SplitMode::ALL {
+                                ui.selectable_value(&mut self.options.split_mode, mode, mode.label());
+                            }
+                        });
+                    if self.options.split_mode == conversion::SplitMode::Silence {
+                        ui.add(
+                            egui::DragValue::new(&mut self.options.split_min_gap_seconds)
+                                .range(0.5..=30.0)
+                                .speed(0.1)
+                                .prefix("gaps ≥ ")
+                                .suffix(" s"),
+                        );
+                    }
+                });
+                ui.add(egui::Slider::new(&mut self.options.fade_in_seconds, 0.0..=10.0).text("Fade in (s)"));
+                ui.add(egui::Slider::new(&mut self.options.fade_out_seconds, 0.0..=10.0).text("Fade out (s)"));
+                if self.options.fade_in_seconds > 0.0 || self.options.fade_out_seconds > 0.0 {
+                    egui::ComboBox::from_label("Fade curve")
+                        .selected_text(self.options.fade_curve.label())
+                        .show_ui(ui, |ui| {
+                            for curve in conversion::FadeCurve::ALL {
+                                ui.selectable_value(&mut self.options.fade_curve, curve, curve.label());
+                            }
+                        });
+                }
+                if cdda {
+                    egui::ComboBox::from_label("Tracks under 4 s")
+                        .selected_text(self.options.short_tracks.label())
+                        .show_ui(ui, |ui| {
+                            for policy in conversion::ShortTrackPolicy::ALL {
+                                ui.selectable_value(&mut self.options.short_tracks, policy, policy.label());
+                            }
+                        })
+                        .response
+                        .on_hover_text("CDs can't hold shorter tracks, and burners refuse them");
+                }
+                egui::ComboBox::from_label("Dither")
+                    .selected_text(self.options.dither.label())
+                    .show_ui(ui, |ui| {
+                        for mode in conversion::DitherMode::ALL {
+                            ui.selectable_value(&mut self.options.dither, mode, mode.label());
+                        }
+                    });
+
+                egui::ComboBox::from_label("Resampling quality")
+                    .selected_text(self.options.resample_quality.label())
+                    .show_ui(ui, |ui| {
+                        for quality in conversion::ResampleQuality::ALL {
+                            ui.selectable_value(&mut self.options.resample_quality, quality, quality.label());
+                        }
+                    });
+                egui::ComboBox::from_label("Conversion priority")
+                    .selected_text(self.options.priority.label())
+                    .show_ui(ui, |ui| {
+                        for priority in priority::WorkerPriority::ALL {
+                            ui.selectable_value(&mut self.options.priority, priority, priority.label());
+                        }
+                    })
+                    .response
+                    .on_hover_text("Background keeps this window and other programs responsive on machines with few cores");
+                ui.checkbox(&mut self.options.no_resample, "Never resample")
+                    .on_hover_text("Fail files that aren't at the output sample rate already instead of resampling them");
+                ui.add_enabled(!self.options.no_resample, egui::Checkbox::new(&mut self.options.no_upsample, "Never upsample"))
+                    .on_hover_text("Fail files below the output sample rate, usually low-quality sources, but still downsample higher rates");
+                ui.checkbox(&mut self.options.bit_perfect, "Bit-perfect copy of CD-quality lossless files")
+                    .on_hover_text("16-bit 44.1 kHz stereo FLAC, ALAC or WAV is written without any processing and checked sample for sample");
+
+                ui.horizontal(|ui| {
+                    let convert_label = if self.is_processing { "➕ Add to queue" } else { "🔃 Convert to CDDA" };
+                    if ui.button(convert_label).clicked() {
+                        self.plan = None;
+                        self.queue_conversion(ui.ctx(), None);
+                    }
+                    if ui
+                        .button(format!("🎧 Preview (first {:.0}s)", PREVIEW_SECONDS))
+                        .on_hover_text("Convert only the start of each file into \"<name> (preview).wav\" to compare settings")
+                        .clicked()
+                    {
+                        self.queue_conversion(ui.ctx(), Some(PREVIEW_SECONDS));
+                    }
+                    if ui
+                        .add_enabled(self.plan_receiver.is_none(), egui::Button::new("👁 Dry run"))
+                        .on_hover_text("List what would be converted without writing anything")
+                        .clicked()
+                    {
+                        self.start_preview(ui.ctx());
+                    }
+                    if self.plan_receiver.is_some() {
+                        ui.spinner();
+                    }
+                });
+
+                if let Some(plan) = &self.plan {
+                    Self::show_plan(ui, plan);
+                }
+            }
+        });
+    }
+
+    fn show_name_template(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("File names:");
+            ui.add(
+                egui::TextEdit::singleline(&mut self.name_template_input)
+                    .hint_text("{track:02} - {artist} - {title}")
+                    .desired_width(200.0),
+            )
+            .on_hover_text(format!(
+                "Placeholders: {}. Leave empty for the default naming",
+                naming::PLACEHOLDERS.iter().map(|name| format!("{{{}}}", name)).collect::<Vec<_>>().join(", ")
+            ));
+        });
+        let template = self.name_template_input.trim();
+        if template.is_empty() {
+            self.options.name_template = None;
+        } else {
+            match naming::NameTemplate::parse(template) {
+                Ok(_) => self.options.name_template = Some(template.to_string()),
+                Err(e) => {
+                    ui.colored_label(egui::Color32::RED, format!("{:#}", e));
+                }
+            }
+        }
+    }
+
+    fn show_selected_files(&mut self, ui: &mut egui::Ui) {
+        // Applied after the loop so the list isn't changed while it is being drawn
+        let mut move_up = None;
+        let mut remove = None;
+        let count = self.selected_files.len();
+        ui.checkbox(&mut self.show_waveforms, "Show waveforms")
+            .on_hover_text("A small overview of each file, to spot silent or clipping ones; red reaches full scale");
+        let row_height = ui.spacing().interact_size.y.max(WAVEFORM_SIZE.y);
+        // Rows scrolled into view, which are the only ones that get a thumbnail
+        let mut visible = Vec::new();
+        egui::ScrollArea::vertical()
+            .max_height(200.0)
+            .show_rows(ui, row_height, count, |ui, rows| {
+                for (index, file) in self.selected_files.iter().enumerate().skip(rows.start).take(rows.len()) {
+                    ui.horizontal(|ui| {
+                        if ui.add_enabled(index > 0, egui::Button::new("▲").small()).clicked() {
+                            move_up = Some(index);
+                        }
+                        if ui.add_enabled(index + 1 < count, egui::Button::new("▼").small()).clicked() {
+                            move_up = Some(index + 1);
+                        }
+                        if ui.add(egui::Button::new("✕").small()).on_hover_text("Remove").clicked() {
+                            remove = Some(index);
+                        }
+                        if self.show_waveforms {
+                            Self::draw_thumbnail(ui, self.thumbnails.get(file));
+                            visible.push(file.clone());
+                        }
+                        ui.label(format!("{:02}. {}", index + 1, file.file_name().unwrap_or_default().to_string_lossy()));
+                    });
+                }
+            });
+
+        for file in &visible {
+            self.request_thumbnail(ui.ctx(), file);
+        }
+        if let Some(index) = move_up {
+            self.selected_files.swap(index - 1, index);
+        }
+        if let Some(index) = remove {
+            self.selected_files.remove(index);
+            self.progress_message = format!("Selected {} files", self.selected_files.len());
+        }
+    }
+
+    fn show_play_time(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            match &self.play_time {
+                Some(play_time) => {
+                    ui.label(format!(
+                        "Total: {} of {} (incl. {:.0} s between tracks)",
+                        disc::format_minutes(play_time.disc_seconds()),
+                        disc::format_minutes(self.disc_capacity.seconds()),
+                        disc::TRACK_GAP_SECONDS
+                    ));
+                    if play_time.existing_tracks > 0 {
+                        ui.label(format!("with {} tracks already on the CUE sheet", play_time.existing_tracks));
+                    }
+                }
+                None => {
+                    ui.spinner();
+                    ui.label("Measuring total length...");
+                }
+            }
+            egui::ComboBox::from_id_source("disc_capacity")
+                .selected_text(self.disc_capacity.label())
+                .show_ui(ui, |ui| {
+                    for capacity in disc::DiscCapacity::ALL {
+                        ui.selectable_value(&mut self.disc_capacity, capacity, capacity.label());
+                    }
+                });
+        });
+
+        let Some(play_time) = &self.play_time else {
+            return;
+        };
+        if !play_time.fits(self.disc_capacity) {
+            ui.colored_label(
+                egui::Color32::RED,
+                format!(
+                    "Selection is {} longer than a {} disc",
+                    disc::format_minutes(play_time.disc_seconds() - self.disc_capacity.seconds()),
+                    self.disc_capacity.label()
+                ),
+            );
+        }
+        if play_time.unknown > 0 {
+            ui.colored_label(
+                egui::Color32::YELLOW,
+                format!("{} files have an unknown length and are not counted", play_time.unknown),
+            );
+        }
+    }
+
+    fn show_failures(&self, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new(format!("{} files failed", self.failures.len()))
+            .id_source("failures")
+            .show(ui, |ui| {
+                egui::ScrollArea::vertical()
+                    .id_source("failures_list")
+                    .max_height(150.0)
+                    .show(ui, |ui| {
+                        for (input, error) in &self.failures {
+                            ui.colored_label(egui::Color32::RED, input.file_name().unwrap_or_default().to_string_lossy())
+                                .on_hover_text(input.display().to_string());
+                            ui.label(error);
+                        }
+                    });
+            });
+    }
+
+    fn show_flagged(&self, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new(format!("{} tracks to check", self.flagged.len()))
+            .id_source("flagged")
+            .show(ui, |ui| {
+                ui.label("Clipping tracks stay under full scale when converted again with \"Limit peaks\". Nearly mono ones are often low-bitrate MP3s; a better rip is the only fix. Tracks under 4 s can be padded with silence.");
                 egui::ScrollArea::vertical()
-                    .max_height(200.0)
+                    .id_source("flagged_list")
+                    .max_height(150.0)
                     .show(ui, |ui| {
-                        for file in &self.selected_files {
-                            ui.label(file.file_name().unwrap().to_string_lossy());
+                        for (input, problems) in &self.flagged {
+                            ui.horizontal(|ui| {
+                                ui.colored_label(egui::Color32::YELLOW, input.file_name().unwrap_or_default().to_string_lossy())
+                                    .on_hover_text(input.display().to_string());
+                                ui.label(problems.join("; "));
+                            });
                         }
                     });
+            });
+    }
 
-                if ui.button("🔃 Convert to CDDA").clicked() {
-                    self.start_conversion();
+    fn show_plan(ui: &mut egui::Ui, plan: &[conversion::PlannedFile]) {
+        ui.separator();
+        ui.label(format!("{} files would be converted:", plan.len()));
+        egui::ScrollArea::both()
+            .id_source("plan")
+            .max_height(150.0)
+            .show(ui, |ui| {
+                egui::Grid::new("plan_grid").striped(true).show(ui, |ui| {
+                    for file in plan {
+                        ui.label(file.input.file_name().unwrap_or_default().to_string_lossy());
+                        ui.label(file.duration.map_or_else(|| "?".to_string(), disc::format_minutes));
+                        ui.label(format!("{} Hz", file.sample_rate));
+                        ui.label(if file.already_converted {
+                            "already converted"
+                        } else if file.needs_resample() {
+                            "resample"
+                        } else {
+                            ""
+                        });
+                        ui.label(format!("→ {}", file.output_path.display()));
+                        ui.end_row();
+                    }
+                });
+            });
+    }
+
+    fn show_verify_reports(&self, ui: &mut egui::Ui, reports: &[verify::CddaReport]) {
+        ui.separator();
+        if reports.iter().all(|report| report.is_compliant()) {
+            ui.label(format!("All {} files are CDDA compliant", reports.len()));
+            return;
+        }
+        egui::ScrollArea::vertical()
+            .id_source("verify_reports")
+            .max_height(150.0)
+            .show(ui, |ui| {
+                for report in reports.iter().filter(|report| !report.is_compliant()) {
+                    ui.colored_label(
+                        egui::Color32::RED,
+                        report.path.file_name().unwrap_or_default().to_string_lossy(),
+                    );
+                    for violation in &report.violations {
+                        ui.label(format!("  • {}", violation));
+                    }
                 }
+            });
+    }
+
+    fn show_queue(&mut self, ui: &mut egui::Ui) {
+        if self.batches.is_empty() {
+            return;
+        }
+        egui::CollapsingHeader::new("Queue").default_open(true).show(ui, |ui| {
+            for batch in &self.batches {
+                let text = format!("{}: {}", batch.label, batch.status.label());
+                match batch.status {
+                    queue::BatchStatus::Failed(_) => ui.colored_label(egui::Color32::RED, text),
+                    _ => ui.label(text),
+                };
+            }
+            if self.batches.iter().any(|batch| batch.status.is_finished()) && ui.button("Clear finished").clicked() {
+                self.batches.retain(|batch| !batch.status.is_finished());
             }
         });
     }
 
     fn show_conversion_progress(&mut self, ui: &mut egui::Ui) {
         ui.vertical_centered(|ui| {
-            ui.add(egui::Spinner::new().size(40.0));
-            ui.label("Converting files...");
+            match &self.progress {
+                Some(progress::ProgressUpdate { scanned: Some(found), .. }) => {
+                    ui.add(egui::ProgressBar::new(0.0).animate(true));
+                    ui.label(format!("Scanning... {} files found", found));
+                }
+                Some(progress) => {
+                    ui.add(
+                        egui::ProgressBar::new(progress.overall_fraction())
+                            .show_percentage()
+                            .animate(true),
+                    );
+                    // Too noisy to show until a little of the batch is done
+                    let fraction = f64::from(progress.overall_fraction());
+                    if let (Some(started), true) = (self.batch_started, fraction > 0.02) {
+                        let elapsed = started.elapsed().as_secs_f64();
+                        ui.label(format!("About {} left", disc::format_minutes(elapsed / fraction - elapsed)));
+                    }
+                    // Input order, so bars keep their places as other files finish around them
+                    let mut active: Vec<(&usize, &FileProgress)> = self.active_files.iter().collect();
+                    active.sort_by_key(|(index, _)| **index);
+                    for (index, file) in active {
+                        ui.label(format!("File {} of {}: {}", index + 1, file.total_files, file.file_name));
+                        ui.add(egui::ProgressBar::new(file.fraction).show_percentage());
+                    }
+                }
+                None => {
+                    ui.add(egui::ProgressBar::new(0.0).animate(true));
+                    ui.label("Converting files...");
+                }
+            }
             
-            if ui.button("❌ Cancel").clicked() {
-                *self.cancel_flag.lock().unwrap() = true;
-                self.progress_message = "Cancelling...".to_string();
+            if let Some(output) = &self.disk_full {
+                ui.colored_label(
+                    egui::Color32::RED,
+                    format!(
+                        "Disk full while writing {}. Free some space and resume; that file will be written again.",
+                        output.file_name().unwrap_or_default().to_string_lossy()
+                    ),
+                );
             }
+            ui.horizontal(|ui| {
+                let paused = self.pause_flag.load(Ordering::Relaxed);
+                if ui.button(if paused { "▶ Resume" } else { "⏸ Pause" }).clicked() {
+                    if paused {
+                        self.disk_full = None;
+                    }
+                    self.pause_flag.store(!paused, Ordering::Relaxed);
+                    self.progress_message = if paused { "Resumed" } else { "Paused" }.to_string();
+                }
+                let stopping = self.stop_flag.load(Ordering::Relaxed);
+                if ui
+                    .add_enabled(!stopping, egui::Button::new("⏭ Stop after this track"))
+                    .on_hover_text("Finish and keep the files being converted, then stop; queued batches are dropped")
+                    .clicked()
+                {
+                    self.stop_after_current();
+                }
+                if ui
+                    .button("❌ Cancel now")
+                    .on_hover_text("Stop this batch immediately, discarding the files in progress, and drop the queued ones")
+                    .clicked()
+                {
+                    self.cancel_queue();
+                }
+            });
         });
     }
 }
 
+// Playlist entries that point at nothing don't stop the rest loading, but the user should know
+fn missing_entries_error(missing: &[PathBuf]) -> Option<String> {
+    match missing {
+        [] => None,
+        [entry] => Some(format!("Playlist entry not found: {}", entry.display())),
+        [first, rest @ ..] => Some(format!("{} playlist entries not found, e.g. {}", rest.len() + 1, first.display())),
+    }
+}
+
 fn main() {
-    logging::initialize_logger(); // Initialize custom logger
+    // Any arguments switch to the command line; without --no-gui they just preselect files in the GUI
+    let mut initial_files = Vec::new();
+    let mut initial_options = None;
+    let mut log_level = LevelFilter::Info;
+    let mut log_file = None;
+    if std::env::args_os().len() > 1 {
+        let args = cli::Cli::parse();
+        if args.no_gui || args.stdin {
+            // stdout carries nothing but the events in JSON mode
+            let target = if args.json { env_logger::Target::Stderr } else { env_logger::Target::Stdout };
+            logging::initialize_logger(target, args.log_level(), args.log_file.as_deref());
+            std::process::exit(cli::run(args));
+        }
+        initial_options = Some(args.conversion_options());
+        log_level = args.log_level();
+        log_file = args.log_file.clone();
+        initial_files = args.input;
+    }
+
+    logging::initialize_logger(env_logger::Target::Stderr, log_level, log_file.as_deref());
+    // Options given on the command line win over the saved ones for this session
+    let saved = settings::Settings::load();
+    let (initial_options, build_image) = match initial_options {
+        Some(options) => (options, false),
+        None => (saved.to_options(), saved.build_image),
+    };
 
     let options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
@@ -165,7 +1474,22 @@ fn main() {
     if let Err(e) = eframe::run_native(
         "MP3 to CDDA Converter",
         options,
-        Box::new(|_cc| Ok(Box::new(ConverterApp::default()))),
+        Box::new(move |_cc| {
+            let mut app = ConverterApp {
+                saved_settings: settings::Settings::capture(&initial_options, build_image),
+                name_template_input: initial_options.name_template.clone().unwrap_or_default(),
+                options: initial_options,
+                build_image,
+                log_level,
+                log_file,
+                ..Default::default()
+            };
+            if !initial_files.is_empty() {
+                app.progress_message = format!("Selected {} files", initial_files.len());
+                app.selected_files = initial_files;
+            }
+            Ok(Box::new(app))
+        }),
     ) {
         log_error!("Failed to run eframe application: {:?}", e);
         std::process::exit(1);
@@ -0,0 +1,211 @@
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+#[derive(Clone, Debug, Default)]
+pub struct TrackInfo {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub track_number: Option<u32>,
+    pub isrc: Option<String>,
+    // UPC/EAN of the release, for the CUE sheet's CATALOG
+    pub catalog: Option<String>,
+}
+
+impl TrackInfo {
+    // Takes a tag as printed by ffprobe. Container and stream tags may both be present; the first one wins.
+    pub fn apply_tag(&mut self, key: &str, value: &str) {
+        let value = value.trim();
+        if value.is_empty() {
+            return;
+        }
+        match key.to_ascii_lowercase().as_str() {
+            "title" => fill(&mut self.title, value),
+            "artist" => fill(&mut self.artist, value),
+            "album" => fill(&mut self.album, value),
+            // ID3's frame is TSRC, which ffmpeg doesn't rename
+            "isrc" | "tsrc" => fill(&mut self.isrc, value),
+            "barcode" | "upc" | "ean" | "catalog" => fill(&mut self.catalog, value),
+            // ID3 stores "3/12", Vorbis comments just "3"
+            "track" | "tracknumber" if self.track_number.is_none() => {
+                self.track_number = value.split('/').next().and_then(|n| n.trim().parse().ok());
+            }
+            _ => {}
+        }
+    }
+
+    // "01 - Artist - Title" from whatever tags are present, or the input file stem without a title.
+    pub fn output_stem(&self, fallback: &str) -> String {
+        let Some(title) = &self.title else {
+            return fallback.to_string();
+        };
+
+        let mut parts = Vec::new();
+        if let Some(number) = self.track_number {
+            parts.push(format!("{:02}", number));
+        }
+        if let Some(artist) = &self.artist {
+            parts.push(artist.clone());
+        }
+        parts.push(title.clone());
+        sanitize_filename(&parts.join(" - "))
+    }
+
+    // Written by ffmpeg into the WAV LIST/INFO chunk (INAM, IART, IPRD, IPRT).
+    pub fn ffmpeg_metadata_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        let fields = [
+            ("title", self.title.clone()),
+            ("artist", self.artist.clone()),
+            ("album", self.album.clone()),
+            ("track", self.track_number.map(|n| n.to_string())),
+        ];
+        for (key, value) in fields {
+            if let Some(value) = value {
+                args.push("-metadata".to_string());
+                args.push(format!("{}={}", key, value));
+            }
+        }
+        args
+    }
+}
+
+fn fill(field: &mut Option<String>, value: &str) {
+    if field.is_none() {
+        *field = Some(value.to_string());
+    }
+}
+
+// Replaces characters that are illegal in file names on Windows, macOS or Linux.
+pub fn sanitize_filename(name: &str) -> String {
+    let cleaned: String = name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    // Windows also rejects trailing dots and spaces
+    cleaned.trim_end_matches(['.', ' ']).to_string()
+}
+
+// Bytes of the ID3v2 tag at the start of an MP3 (or any file carrying one), header and footer
+// included; 0 without one. Embedded cover art can make this several megabytes.
+fn id3v2_size(header: &[u8; 10]) -> u64 {
+    let [b'I', b'D', b'3', _version, _revision, flags, size @ ..] = *header else {
+        return 0;
+    };
+    // Synchsafe: seven bits per byte, so no byte of the size can look like a frame sync
+    let size = size.iter().fold(0u64, |size, &byte| (size << 7) | u64::from(byte & 0x7f));
+    let footer = if flags & 0x10 != 0 { 10 } else { 0 };
+    10 + size + footer
+}
+
+// The file's size without the tags around the audio: a leading ID3v2 tag and a trailing 128-byte
+// ID3v1 one. This is what the audio's length is in proportion to.
+pub fn audio_bytes(path: &Path) -> u64 {
+    let read = || -> std::io::Result<u64> {
+        let mut file = File::open(path)?;
+        let len = file.metadata()?.len();
+        let mut header = [0u8; 10];
+        let leading = match file.read_exact(&mut header) {
+            Ok(()) => id3v2_size(&header),
+            Err(_) => 0,
+        };
+        let mut trailer = [0u8; 3];
+        let trailing = if len >= leading + 128 {
+            file.seek(SeekFrom::End(-128))?;
+            file.read_exact(&mut trailer)?;
+            if &trailer == b"TAG" { 128 } else { 0 }
+        } else {
+            0
+        };
+        Ok(len.saturating_sub(leading + trailing))
+    };
+    read().unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use super::*;
+    use crate::testing;
+
+    // ID3v2.4 header for a tag body of `size` bytes
+    fn id3_header(size: u32, flags: u8) -> [u8; 10] {
+        let synchsafe = |shift: u32| ((size >> shift) & 0x7f) as u8;
+        [b'I', b'D', b'3', 4, 0, flags, synchsafe(21), synchsafe(14), synchsafe(7), synchsafe(0)]
+    }
+
+    fn id3v1_tag() -> Vec<u8> {
+        let mut tag = b"TAG".to_vec();
+        tag.resize(128, b' ');
+        tag
+    }
+
+    #[test]
+    fn reads_synchsafe_id3v2_size() {
+        assert_eq!(id3_header(257, 0)[6..], [0, 0, 2, 1]);
+        assert_eq!(id3v2_size(&id3_header(257, 0)), 267);
+        // A footer repeats the 10-byte header at the end of the tag
+        assert_eq!(id3v2_size(&id3_header(257, 0x10)), 277);
+        assert_eq!(id3v2_size(&id3_header(4_194_304, 0)), 4_194_314);
+        assert_eq!(id3v2_size(b"RIFF\0\0\0\0WA"), 0);
+    }
+
+    #[test]
+    fn leaves_tags_out_of_audio_bytes() {
+        let dir = testing::temp_dir("audio-bytes");
+        let audio = vec![0x55u8; 1000];
+
+        let both = dir.join("both.mp3");
+        let mut bytes = id3_header(20, 0).to_vec();
+        bytes.extend([0u8; 20]);
+        bytes.extend(&audio);
+        bytes.extend(id3v1_tag());
+        fs::write(&both, &bytes).unwrap();
+        assert_eq!(audio_bytes(&both), 1000);
+
+        let footer = dir.join("footer.mp3");
+        let mut bytes = id3_header(20, 0x10).to_vec();
+        bytes.extend([0u8; 30]);
+        bytes.extend(&audio);
+        fs::write(&footer, &bytes).unwrap();
+        assert_eq!(audio_bytes(&footer), 1000);
+
+        let trailing = dir.join("trailing.mp3");
+        let mut bytes = audio.clone();
+        bytes.extend(id3v1_tag());
+        fs::write(&trailing, &bytes).unwrap();
+        assert_eq!(audio_bytes(&trailing), 1000);
+
+        let untagged = dir.join("untagged.mp3");
+        fs::write(&untagged, &audio).unwrap();
+        assert_eq!(audio_bytes(&untagged), 1000);
+    }
+
+    #[test]
+    fn short_files_dont_underflow() {
+        let dir = testing::temp_dir("audio-bytes-short");
+        // Too short to hold an ID3v1 tag after the ID3v2 one, so the "TAG" is taken as audio
+        let short = dir.join("short.mp3");
+        let mut bytes = id3_header(0, 0).to_vec();
+        bytes.extend(&id3v1_tag()[..100]);
+        fs::write(&short, &bytes).unwrap();
+        assert_eq!(audio_bytes(&short), 100);
+
+        // A header claiming more than the file holds
+        let truncated = dir.join("truncated.mp3");
+        let mut bytes = id3_header(5000, 0).to_vec();
+        bytes.extend([0u8; 40]);
+        fs::write(&truncated, &bytes).unwrap();
+        assert_eq!(audio_bytes(&truncated), 0);
+
+        let tiny = dir.join("tiny.mp3");
+        fs::write(&tiny, b"ID3").unwrap();
+        assert_eq!(audio_bytes(&tiny), 3);
+        assert_eq!(audio_bytes(&dir.join("missing.mp3")), 0);
+    }
+}
@@ -0,0 +1,151 @@
+use anyhow::Result;
+use crate::metadata::{sanitize_filename, TrackInfo};
+
+pub const PLACEHOLDERS: &[&str] = &["track", "artist", "title", "album", "stem"];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Field {
+    Track,
+    Artist,
+    Title,
+    Album,
+    // The input's file name without its extension
+    Stem,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Part {
+    Text(String),
+    // {track:02} zero-pads to the width; only numbers take one
+    Field(Field, Option<usize>),
+}
+
+// An output file name pattern such as "{track:02} - {artist} - {title}", checked when it is
+// parsed so a typo fails before anything is converted. {{ and }} are literal braces.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NameTemplate {
+    parts: Vec<Part>,
+}
+
+impl NameTemplate {
+    pub fn parse(template: &str) -> Result<NameTemplate> {
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        let mut chars = template.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '{' => {
+                    let mut placeholder = String::new();
+                    let mut closed = false;
+                    for c in chars.by_ref() {
+                        if c == '}' {
+                            closed = true;
+                            break;
+                        }
+                        placeholder.push(c);
+                    }
+                    if !closed {
+                        return Err(anyhow::anyhow!("Unclosed {{ in name template {:?}", template));
+                    }
+                    if !text.is_empty() {
+                        parts.push(Part::Text(std::mem::take(&mut text)));
+                    }
+                    parts.push(parse_placeholder(&placeholder, template)?);
+                }
+                '}' => return Err(anyhow::anyhow!("Unmatched }} in name template {:?}; write }}}} for a brace", template)),
+                c => text.push(c),
+            }
+        }
+        if !text.is_empty() {
+            parts.push(Part::Text(text));
+        }
+        if !parts.iter().any(|part| matches!(part, Part::Field(..))) {
+            return Err(anyhow::anyhow!(
+                "Name template {:?} has no placeholders, so every track would get the same name",
+                template
+            ));
+        }
+        Ok(NameTemplate { parts })
+    }
+
+    // Tags missing from the track are left out together with the separator after them, except the
+    // title, which falls back to `fallback` (the input's name) like the built-in naming does.
+    pub fn render(&self, track: &TrackInfo, stem: &str, fallback: &str) -> String {
+        let mut name = String::new();
+        let mut skip_separator = false;
+        for part in &self.parts {
+            match part {
+                Part::Text(text) if skip_separator && is_separator(text) => {}
+                Part::Text(text) => name.push_str(text),
+                Part::Field(field, width) => {
+                    let value = match field {
+                        Field::Track => track.track_number.map(|number| format!("{:0width$}", number, width = width.unwrap_or(0))),
+                        Field::Artist => track.artist.clone(),
+                        Field::Title => Some(track.title.clone().unwrap_or_else(|| fallback.to_string())),
+                        Field::Album => track.album.clone(),
+                        Field::Stem => Some(stem.to_string()),
+                    };
+                    skip_separator = value.is_none();
+                    if let Some(value) = value {
+                        name.push_str(&sanitize_filename(&value));
+                    }
+                    continue;
+                }
+            }
+            skip_separator = false;
+        }
+        let name = sanitize_filename(name.trim_matches(is_separator_char));
+        if name.is_empty() {
+            sanitize_filename(fallback)
+        } else {
+            name
+        }
+    }
+}
+
+fn parse_placeholder(placeholder: &str, template: &str) -> Result<Part> {
+    let (name, format) = match placeholder.split_once(':') {
+        Some((name, format)) => (name.trim(), Some(format.trim())),
+        None => (placeholder.trim(), None),
+    };
+    let field = match name.to_ascii_lowercase().as_str() {
+        "track" => Field::Track,
+        "artist" => Field::Artist,
+        "title" => Field::Title,
+        "album" => Field::Album,
+        "stem" => Field::Stem,
+        _ => {
+            return Err(anyhow::anyhow!(
+                "Unknown placeholder {{{}}} in name template {:?}; use one of {}",
+                name,
+                template,
+                PLACEHOLDERS.iter().map(|name| format!("{{{}}}", name)).collect::<Vec<_>>().join(", ")
+            ))
+        }
+    };
+    let width = match format {
+        None => None,
+        Some(format) if field == Field::Track => Some(format.parse::<usize>().ok().filter(|width| *width <= 9).ok_or_else(|| {
+            anyhow::anyhow!("Invalid width {:?} for {{track}} in name template {:?}; write e.g. {{track:02}}", format, template)
+        })?),
+        Some(_) => return Err(anyhow::anyhow!("Only {{track}} takes a width, in name template {:?}", template)),
+    };
+    Ok(Part::Field(field, width))
+}
+
+// Text that only separates placeholders, such as " - " or ". "
+fn is_separator(text: &str) -> bool {
+    text.chars().all(is_separator_char)
+}
+
+fn is_separator_char(c: char) -> bool {
+    c.is_whitespace() || matches!(c, '-' | '_' | '.' | ',' | '–')
+}
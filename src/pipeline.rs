@@ -0,0 +1,188 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::thread;
+use anyhow::{Context, Result};
+use crate::conversion::{self, DitherMode, ResampleQuality};
+use crate::wav::CD_SECTOR_BYTES;
+
+// The conversion as three in-memory steps, for callers and tests that want samples rather than
+// files. ffmpeg still does the decoding and resampling with the same filters as convert_files,
+// but the whole track is held in memory: about 10 MB per minute of CD audio.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SignalSpec {
+    pub sample_rate: u32,
+    pub channels: u16,
+}
+
+pub const CDDA_SPEC: SignalSpec = SignalSpec {
+    sample_rate: 44100,
+    channels: 2,
+};
+
+// Interleaved 16-bit samples at the input's own rate and channel count
+pub fn decode_to_samples(path: &Path) -> Result<(Vec<i16>, SignalSpec)> {
+    let info = conversion::probe_stream(path)?;
+    let output = Command::new("ffmpeg")
+        .args(["-v", "error", "-i"])
+        .arg(path)
+        .args(["-map", "0:a:0", "-f", "s16le", "-acodec", "pcm_s16le", "-"])
+        .output()
+        .context("Failed to run ffmpeg")?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!("ffmpeg failed to decode {:?}: {}", path, String::from_utf8_lossy(&output.stderr)));
+    }
+    let spec = SignalSpec {
+        sample_rate: info.sample_rate,
+        channels: info.channels,
+    };
+    Ok((from_le_bytes(&output.stdout), spec))
+}
+
+// Interleaved samples in any layout to 44.1 kHz stereo, with the default resampling quality and
+// dither. More than two channels are downmixed the same way as in a file conversion.
+pub fn resample_to_cdda(samples: &[i16], spec: SignalSpec) -> Result<Vec<i16>> {
+    if spec.channels == 0 || !samples.len().is_multiple_of(usize::from(spec.channels)) {
+        return Err(anyhow::anyhow!("{} samples don't divide into {} channels", samples.len(), spec.channels));
+    }
+    let mut filters = Vec::new();
+    filters.extend(conversion::downmix_filter(spec.channels, None));
+    filters.push(format!("aresample=osr=44100:osf=flt:{}", ResampleQuality::default().swr_options(spec.sample_rate, CDDA_SPEC.sample_rate)));
+    filters.push(format!("aresample=osf=s16:dither_method={}", DitherMode::default().ffmpeg_method()));
+
+    let mut child = Command::new("ffmpeg")
+        .args(["-v", "error", "-f", "s16le", "-ar", &spec.sample_rate.to_string(), "-ac", &spec.channels.to_string()])
+        .args(["-i", "-", "-af", &filters.join(",")])
+        .args(["-ac", "2", "-ar", "44100", "-f", "s16le", "-acodec", "pcm_s16le", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("Failed to spawn ffmpeg process")?;
+
+    // Fed from another thread so ffmpeg never blocks on a full stdout while we are still writing
+    let mut stdin = child.stdin.take().context("ffmpeg has no stdin")?;
+    let input: Vec<u8> = samples.iter().flat_map(|sample| sample.to_le_bytes()).collect();
+    let writer = thread::spawn(move || stdin.write_all(&input));
+    let output = child.wait_with_output().context("Failed to wait for ffmpeg process")?;
+    let written = writer.join().map_err(|_| anyhow::anyhow!("ffmpeg input thread panicked"))?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!("ffmpeg failed to resample: {}", String::from_utf8_lossy(&output.stderr)));
+    }
+    written.context("Failed to pass samples to ffmpeg")?;
+    Ok(from_le_bytes(&output.stdout))
+}
+
+// A 44.1 kHz/16-bit stereo WAV with the data padded to a whole CD sector, as convert_files writes.
+pub fn write_cdda_wav(samples: &[i16], path: &Path) -> Result<()> {
+    if !samples.len().is_multiple_of(2) {
+        return Err(anyhow::anyhow!("CD audio is stereo, but {} samples can't be split into pairs", samples.len()));
+    }
+    let audio_len = samples.len() as u64 * 2;
+    let data_len = audio_len.div_ceil(CD_SECTOR_BYTES) * CD_SECTOR_BYTES;
+    let data_len_u32 = u32::try_from(data_len).context("Audio is too long for a WAV file")?;
+
+    let mut file = BufWriter::new(File::create(path).with_context(|| format!("Failed to create {:?}", path))?);
+    file.write_all(b"RIFF")?;
+    file.write_all(&(36 + data_len_u32).to_le_bytes())?;
+    file.write_all(b"WAVEfmt ")?;
+    file.write_all(&16u32.to_le_bytes())?;
+    file.write_all(&1u16.to_le_bytes())?; // PCM
+    file.write_all(&CDDA_SPEC.channels.to_le_bytes())?;
+    file.write_all(&CDDA_SPEC.sample_rate.to_le_bytes())?;
+    file.write_all(&(CDDA_SPEC.sample_rate * 4).to_le_bytes())?; // Byte rate
+    file.write_all(&4u16.to_le_bytes())?; // Block align
+    file.write_all(&16u16.to_le_bytes())?;
+    file.write_all(b"data")?;
+    file.write_all(&data_len_u32.to_le_bytes())?;
+    for sample in samples {
+        file.write_all(&sample.to_le_bytes())?;
+    }
+    file.write_all(&vec![0u8; (data_len - audio_len) as usize])?;
+    file.flush().with_context(|| format!("Failed to write {:?}", path))?;
+    Ok(())
+}
+
+fn from_le_bytes(bytes: &[u8]) -> Vec<i16> {
+    bytes.chunks_exact(2).map(|pair| i16::from_le_bytes([pair[0], pair[1]])).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+    use crate::wav;
+
+    #[test]
+    fn cdda_wav_round_trip() {
+        let dir = testing::temp_dir("cdda-wav");
+        let path = dir.join("track.wav");
+        // 1000 frames is 4000 bytes, short of the second sector
+        let samples = testing::sine(44100, 440.0, 0.5, 1000, 2);
+        write_cdda_wav(&samples, &path).unwrap();
+
+        let info = wav::read_wav_info(&path).unwrap();
+        let format = info.format.as_ref().unwrap();
+        assert_eq!(format.sample_rate, 44100);
+        assert_eq!(format.channels, 2);
+        assert_eq!(format.bits_per_sample, 16);
+        assert_eq!(info.data_len, 2 * CD_SECTOR_BYTES);
+        assert_eq!(info.data_offset + info.data_len, info.file_len);
+        let written = testing::read_wav_samples(&path);
+        assert_eq!(&written[..samples.len()], samples.as_slice());
+        assert!(written[samples.len()..].iter().all(|sample| *sample == 0));
+    }
+
+    #[test]
+    fn cdda_wav_refuses_odd_sample_count() {
+        let dir = testing::temp_dir("cdda-wav-odd");
+        assert!(write_cdda_wav(&[0, 0, 0], &dir.join("track.wav")).is_err());
+    }
+
+    #[test]
+    #[ignore = "needs ffmpeg"]
+    fn resamples_48k_to_cdda() {
+        let spec = SignalSpec { sample_rate: 48000, channels: 2 };
+        let samples = testing::sine(48000, 1000.0, 0.5, 48000, 2);
+        let output = resample_to_cdda(&samples, spec).unwrap();
+        assert_eq!(output.len() % 2, 0);
+        // One second in, one second out, give or take the resampler's edges
+        let frames = output.len() / 2;
+        assert!(frames.abs_diff(44100) <= 64, "{} frames", frames);
+        let peak = output.iter().map(|sample| sample.unsigned_abs()).max().unwrap();
+        assert!((15000..=17500).contains(&peak), "peak {}", peak);
+    }
+
+    #[test]
+    #[ignore = "needs ffmpeg"]
+    fn mono_cdda_rate_doubles_into_stereo() {
+        let spec = SignalSpec { sample_rate: 44100, channels: 1 };
+        let samples = testing::sine(44100, 1000.0, 0.5, 12345, 1);
+        let output = resample_to_cdda(&samples, spec).unwrap();
+        // No rate change, so no frames are gained or lost; each one is just sent to both sides
+        assert_eq!(output.len(), 2 * samples.len());
+        assert!(output.chunks_exact(2).all(|frame| frame[0].abs_diff(frame[1]) <= 2));
+    }
+
+    #[test]
+    #[ignore = "needs ffmpeg"]
+    fn full_scale_resampling_clips_instead_of_wrapping() {
+        let spec = SignalSpec { sample_rate: 48000, channels: 2 };
+        let samples = testing::sine(48000, 1000.0, 1.0, 48000, 2);
+        let output = resample_to_cdda(&samples, spec).unwrap();
+        // A 1 kHz sine at 44.1 kHz moves at most 2π·1000/44100 of full scale per sample; wrapping
+        // past full scale would jump most of the way across the range instead
+        let max_step = (2.0 * std::f64::consts::PI * 1000.0 / 44100.0 * 32767.0 * 1.2) as u32;
+        for channel in 0..2 {
+            let left_or_right: Vec<i16> = output.iter().skip(channel).step_by(2).copied().collect();
+            for pair in left_or_right.windows(2) {
+                let step = u32::from(pair[0].abs_diff(pair[1]));
+                assert!(step <= max_step, "{} to {} in channel {}", pair[0], pair[1], channel);
+            }
+        }
+        let peak = output.iter().map(|sample| sample.unsigned_abs()).max().unwrap();
+        assert!(peak >= 32000, "peak {}", peak);
+    }
+}
@@ -0,0 +1,121 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use anyhow::{Context, Result};
+
+pub const PLAYLIST_EXTENSIONS: &[&str] = &["m3u", "m3u8", "pls"];
+
+pub fn is_playlist(path: &Path) -> bool {
+    path.extension()
+        .and_then(|s| s.to_str())
+        .is_some_and(|ext| PLAYLIST_EXTENSIONS.iter().any(|playlist| ext.eq_ignore_ascii_case(playlist)))
+}
+
+pub struct Playlist {
+    // Entries that exist, in playlist order
+    pub entries: Vec<PathBuf>,
+    // Entries that point at nothing, e.g. files moved since the playlist was made
+    pub missing: Vec<PathBuf>,
+}
+
+// Reads an M3U/M3U8 or PLS playlist. Relative entries are relative to the playlist's folder;
+// streams (http:// and the like) are skipped, as there is no file to convert.
+pub fn read_playlist(path: &Path) -> Result<Playlist> {
+    let bytes = fs::read(path).with_context(|| format!("Failed to read playlist {:?}", path))?;
+    let text = decode_text(&bytes);
+    let is_pls = path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("pls"));
+    let locations = if is_pls { pls_entries(&text) } else { m3u_entries(&text) };
+
+    let folder = path.parent().unwrap_or_else(|| Path::new("."));
+    let mut playlist = Playlist {
+        entries: Vec::new(),
+        missing: Vec::new(),
+    };
+    for location in locations {
+        let Some(entry) = entry_path(&location) else {
+            log_warn!("Skipping {:?} in {:?}: not a local file", location, path);
+            continue;
+        };
+        let entry = folder.join(entry);
+        if entry.exists() {
+            playlist.entries.push(entry);
+        } else {
+            log_warn!("{:?} is listed in {:?} but doesn't exist", entry, path);
+            playlist.missing.push(entry);
+        }
+    }
+    log_info!("Read {} entries from playlist {:?}", playlist.entries.len(), path);
+    Ok(playlist)
+}
+
+// .m3u8 is UTF-8 by definition, but plain .m3u files are often in the system's 8-bit code page;
+// Latin-1 gets the common accented names right where UTF-8 fails.
+fn decode_text(bytes: &[u8]) -> String {
+    let bytes = bytes.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(bytes);
+    match std::str::from_utf8(bytes) {
+        Ok(text) => text.to_string(),
+        Err(_) => bytes.iter().map(|&byte| char::from(byte)).collect(),
+    }
+}
+
+// Every line that isn't blank or a #EXTINF-style comment is an entry
+fn m3u_entries(text: &str) -> Vec<String> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect()
+}
+
+// File1=..., File2=... in [playlist]; ordered by their number, not by where they appear
+fn pls_entries(text: &str) -> Vec<String> {
+    let mut entries: Vec<(u32, String)> = text
+        .lines()
+        .filter_map(|line| line.trim().split_once('='))
+        .filter_map(|(key, value)| {
+            let number = key.trim().strip_prefix("File")?.parse().ok()?;
+            Some((number, value.trim().to_string()))
+        })
+        .collect();
+    entries.sort_by_key(|(number, _)| *number);
+    entries.into_iter().map(|(_, value)| value).collect()
+}
+
+// A file path or file:// URL as a path; None for anything remote
+fn entry_path(location: &str) -> Option<PathBuf> {
+    if let Some(url_path) = location.strip_prefix("file://") {
+        // file:///C:/Music/x.mp3 has the drive after the third slash
+        let url_path = url_path.strip_prefix("localhost").unwrap_or(url_path);
+        let decoded = percent_decode(url_path);
+        let decoded = if cfg!(windows) { decoded.trim_start_matches('/').to_string() } else { decoded };
+        return Some(PathBuf::from(decoded));
+    }
+    if location.contains("://") {
+        return None;
+    }
+    // Playlists written on Windows use backslashes, which other systems take as part of the name
+    if cfg!(windows) {
+        Some(PathBuf::from(location))
+    } else {
+        Some(PathBuf::from(location.replace('\\', "/")))
+    }
+}
+
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes.get(i + 1..i + 3).and_then(|hex| std::str::from_utf8(hex).ok());
+        match (bytes[i], hex.and_then(|hex| u8::from_str_radix(hex, 16).ok())) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
@@ -0,0 +1,86 @@
+use serde::{Deserialize, Serialize};
+
+// OS scheduling priority for the threads doing a conversion, so on one or two cores the UI can
+// still get a word in. Most of the work happens in ffmpeg, which on Linux inherits it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WorkerPriority {
+    Background,
+    #[default]
+    Normal,
+    High,
+}
+
+impl WorkerPriority {
+    pub const ALL: [WorkerPriority; 3] = [WorkerPriority::Background, WorkerPriority::Normal, WorkerPriority::High];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            WorkerPriority::Background => "Background",
+            WorkerPriority::Normal => "Normal",
+            WorkerPriority::High => "High",
+        }
+    }
+}
+
+// Applies to the calling thread only, and to the threads and processes it starts afterwards where
+// the OS passes it on. Lowering it can't always be undone without privileges, so call it on a
+// thread of its own rather than one that outlives the conversion.
+pub fn set_current_thread(priority: WorkerPriority) {
+    if priority == WorkerPriority::Normal {
+        return;
+    }
+    if let Err(e) = set_native(priority) {
+        log_warn!("Could not set the conversion thread to {} priority: {}", priority.label(), e);
+    }
+}
+
+// Linux keeps a nice value per thread, which new threads and child processes inherit. Raising it
+// above normal needs CAP_SYS_NICE, so High usually fails there for ordinary users.
+#[cfg(target_os = "linux")]
+fn set_native(priority: WorkerPriority) -> std::io::Result<()> {
+    let nice = match priority {
+        WorkerPriority::Background => 10,
+        WorkerPriority::Normal => 0,
+        WorkerPriority::High => -5,
+    };
+    // SAFETY: gettid and setpriority only read and change this thread's scheduling attributes
+    let result = unsafe {
+        let thread = libc::syscall(libc::SYS_gettid) as libc::id_t;
+        libc::setpriority(libc::PRIO_PROCESS, thread, nice)
+    };
+    if result == 0 {
+        Ok(())
+    } else {
+        Err(std::io::Error::last_os_error())
+    }
+}
+
+// Windows sets priority per thread; processes started from it keep their own normal class
+#[cfg(windows)]
+fn set_native(priority: WorkerPriority) -> std::io::Result<()> {
+    use std::ffi::c_void;
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetCurrentThread() -> *mut c_void;
+        fn SetThreadPriority(thread: *mut c_void, priority: i32) -> i32;
+    }
+    const THREAD_PRIORITY_BELOW_NORMAL: i32 = -1;
+    const THREAD_PRIORITY_ABOVE_NORMAL: i32 = 1;
+    let level = match priority {
+        WorkerPriority::Background => THREAD_PRIORITY_BELOW_NORMAL,
+        WorkerPriority::Normal => 0,
+        WorkerPriority::High => THREAD_PRIORITY_ABOVE_NORMAL,
+    };
+    // SAFETY: GetCurrentThread returns a pseudo-handle that needs no closing
+    if unsafe { SetThreadPriority(GetCurrentThread(), level) } != 0 {
+        Ok(())
+    } else {
+        Err(std::io::Error::last_os_error())
+    }
+}
+
+// Elsewhere, e.g. macOS, per-process priority would slow the UI down with the conversion
+#[cfg(not(any(target_os = "linux", windows)))]
+fn set_native(_priority: WorkerPriority) -> std::io::Result<()> {
+    Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "not supported on this platform"))
+}
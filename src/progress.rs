@@ -0,0 +1,195 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Sender;
+use std::sync::Mutex;
+use crate::conversion::PlannedFile;
+
+// Observer for a conversion run. Workers call it from several threads at once; every method
+// defaults to doing nothing, so implementors only override what they display.
+pub trait ProgressSink: Sync {
+    // Before any file starts: how much of the batch each file is, by input size, indexed like
+    // on_file_start. Files that won't be converted weigh 0.
+    fn on_batch_start(&self, _input_bytes: &[u64]) {}
+
+    // While the inputs are being found and probed, before on_batch_start: the files read so far
+    fn on_scanning(&self, _files_found: usize) {}
+
+    fn on_file_start(&self, _index: usize, _total_files: usize, _input: &Path) {}
+
+    // Milliseconds of audio converted so far out of the track's length; total is 0 when unknown
+    fn on_progress(&self, _index: usize, _done: u64, _total: u64) {}
+
+    fn on_file_done(&self, _index: usize, _input: &Path, _succeeded: bool) {}
+
+    // Dry runs only: one call per file that would be converted, in order
+    fn on_planned(&self, _file: &PlannedFile) {}
+
+    // The output volume filled up while writing `output`. The batch is paused by now; the file is
+    // written again from the start once it is resumed.
+    fn on_disk_full(&self, _output: &Path) {}
+}
+
+#[derive(Default)]
+pub struct PlanCollector {
+    pub files: Mutex<Vec<PlannedFile>>,
+}
+
+impl ProgressSink for PlanCollector {
+    fn on_planned(&self, file: &PlannedFile) {
+        self.files.lock().unwrap().push(file.clone());
+    }
+}
+
+// One file's progress, sent whenever it moves. With parallel workers, updates for several files
+// arrive interleaved; file_index tells them apart.
+#[derive(Clone, Debug)]
+pub struct ProgressUpdate {
+    pub file_index: usize,
+    pub total_files: usize,
+    pub input: PathBuf,
+    pub file_name: String,
+    // Set on the last update for the file, once it is done (or failed)
+    pub finished: bool,
+    // Share of the current file converted so far, 0.0 to 1.0
+    pub file_fraction: f32,
+    // Input bytes of the batch converted so far, counting files in progress by their share done
+    pub bytes_done: u64,
+    // 0 when the sizes aren't known
+    pub bytes_total: u64,
+    // Set only while the inputs are still being scanned, to the files found so far; the other
+    // fields are then empty
+    pub scanned: Option<usize>,
+}
+
+impl ProgressUpdate {
+    // By input size where known, so a 20-minute track moves the bar ten times as far as a 2-minute one
+    pub fn overall_fraction(&self) -> f32 {
+        if self.bytes_total > 0 {
+            return (self.bytes_done as f64 / self.bytes_total as f64).min(1.0) as f32;
+        }
+        (self.file_index as f32 + self.file_fraction) / self.total_files.max(1) as f32
+    }
+}
+
+// Turns progress events into ProgressUpdates for a UI thread polling the receiver.
+pub struct ChannelProgress {
+    sender: Sender<ProgressUpdate>,
+    state: Mutex<BatchState>,
+}
+
+#[derive(Default)]
+struct BatchState {
+    // Files in progress, to label the updates that follow their start
+    started: HashMap<usize, StartedFile>,
+    input_bytes: Vec<u64>,
+    finished_bytes: u64,
+}
+
+struct StartedFile {
+    total_files: usize,
+    input: PathBuf,
+    fraction: f32,
+}
+
+impl BatchState {
+    fn bytes_done(&self) -> u64 {
+        let in_progress: f64 = self
+            .started
+            .iter()
+            .map(|(index, file)| self.weight(*index) as f64 * f64::from(file.fraction))
+            .sum();
+        self.finished_bytes + in_progress as u64
+    }
+
+    fn update(&self, index: usize, file: &StartedFile, finished: bool) -> ProgressUpdate {
+        ProgressUpdate {
+            file_index: index,
+            total_files: file.total_files,
+            input: file.input.clone(),
+            file_name: file.input.file_name().unwrap_or_default().to_string_lossy().into_owned(),
+            finished,
+            file_fraction: file.fraction,
+            bytes_done: self.bytes_done(),
+            bytes_total: self.input_bytes.iter().sum(),
+            scanned: None,
+        }
+    }
+
+    fn weight(&self, index: usize) -> u64 {
+        self.input_bytes.get(index).copied().unwrap_or(0)
+    }
+}
+
+impl ChannelProgress {
+    pub fn new(sender: Sender<ProgressUpdate>) -> Self {
+        Self {
+            sender,
+            state: Mutex::new(BatchState::default()),
+        }
+    }
+
+    fn send(&self, index: usize, file_fraction: f32) {
+        let update = {
+            let mut state = self.state.lock().unwrap();
+            let Some(file) = state.started.get_mut(&index) else {
+                return;
+            };
+            file.fraction = file_fraction;
+            state.update(index, &state.started[&index], false)
+        };
+        self.sender.send(update).ok();
+    }
+}
+
+impl ProgressSink for ChannelProgress {
+    fn on_scanning(&self, files_found: usize) {
+        let update = ProgressUpdate {
+            file_index: 0,
+            total_files: 0,
+            input: PathBuf::new(),
+            file_name: String::new(),
+            finished: false,
+            file_fraction: 0.0,
+            bytes_done: 0,
+            bytes_total: 0,
+            scanned: Some(files_found),
+        };
+        self.sender.send(update).ok();
+    }
+
+    fn on_batch_start(&self, input_bytes: &[u64]) {
+        *self.state.lock().unwrap() = BatchState {
+            input_bytes: input_bytes.to_vec(),
+            ..BatchState::default()
+        };
+    }
+
+    fn on_file_start(&self, index: usize, total_files: usize, input: &Path) {
+        let file = StartedFile {
+            total_files,
+            input: input.to_path_buf(),
+            fraction: 0.0,
+        };
+        self.state.lock().unwrap().started.insert(index, file);
+        self.send(index, 0.0);
+    }
+
+    fn on_progress(&self, index: usize, done: u64, total: u64) {
+        if total > 0 {
+            self.send(index, (done as f32 / total as f32).min(1.0));
+        }
+    }
+
+    fn on_file_done(&self, index: usize, _input: &Path, _succeeded: bool) {
+        let update = {
+            let mut state = self.state.lock().unwrap();
+            let Some(mut file) = state.started.remove(&index) else {
+                return;
+            };
+            state.finished_bytes += state.weight(index);
+            file.fraction = 1.0;
+            state.update(index, &file, true)
+        };
+        self.sender.send(update).ok();
+    }
+}
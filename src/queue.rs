@@ -0,0 +1,191 @@
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
+use crate::conversion::{self, ConversionOptions, ConversionSummary};
+use crate::image;
+use crate::priority;
+use crate::progress::{ChannelProgress, ProgressSink, ProgressUpdate};
+
+// One press of "Convert": the files and settings at that moment, so later changes in the UI
+// only affect batches queued after them.
+pub struct Batch {
+    pub id: usize,
+    pub files: Vec<PathBuf>,
+    pub options: ConversionOptions,
+    // Some to write a single BIN/CUE image instead of a WAV per track
+    pub image_path: Option<PathBuf>,
+}
+
+#[derive(Default)]
+pub struct Queue {
+    pub pending: VecDeque<Batch>,
+    // Whether a worker thread is draining `pending`; only changed with the lock held
+    pub worker_running: bool,
+}
+
+pub enum BatchEvent {
+    Started(usize),
+    // The batch paused itself because the output volume is full
+    DiskFull {
+        id: usize,
+        output: PathBuf,
+    },
+    Finished {
+        id: usize,
+        result: Result<ConversionSummary, String>,
+        cancelled: bool,
+        // Ended early by "stop after this track" rather than by a cancel
+        stopped: bool,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum BatchStatus {
+    Pending,
+    Running,
+    // Result summary, e.g. "12 converted, 1 failed"
+    Done(String),
+    Failed(String),
+    Cancelled,
+}
+
+impl BatchStatus {
+    pub fn label(&self) -> String {
+        match self {
+            BatchStatus::Pending => "pending".to_string(),
+            BatchStatus::Running => "running".to_string(),
+            BatchStatus::Done(summary) => format!("done ({})", summary),
+            BatchStatus::Failed(error) => format!("failed: {}", error),
+            BatchStatus::Cancelled => "cancelled".to_string(),
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        !matches!(self, BatchStatus::Pending | BatchStatus::Running)
+    }
+}
+
+// What the UI lists for each batch it queued
+pub struct QueuedBatch {
+    pub id: usize,
+    pub label: String,
+    pub status: BatchStatus,
+}
+
+// Names a batch after the folder of its first file, which is usually the album
+pub fn batch_label(files: &[PathBuf]) -> String {
+    let folder = files
+        .first()
+        .and_then(|file| file.parent())
+        .and_then(|folder| folder.file_name())
+        .map(|name| name.to_string_lossy().into_owned());
+    match folder {
+        Some(folder) => format!("{} ({} files)", folder, files.len()),
+        None => format!("{} files", files.len()),
+    }
+}
+
+// Passes progress on to the UI, and the disk-full pause as a batch event
+struct BatchProgress<'a, W> {
+    id: usize,
+    channel: ChannelProgress,
+    events: &'a Sender<BatchEvent>,
+    wake: &'a W,
+}
+
+impl<W: Fn() + Sync> ProgressSink for BatchProgress<'_, W> {
+    fn on_scanning(&self, files_found: usize) {
+        self.channel.on_scanning(files_found);
+    }
+
+    fn on_batch_start(&self, input_bytes: &[u64]) {
+        self.channel.on_batch_start(input_bytes);
+    }
+
+    fn on_file_start(&self, index: usize, total_files: usize, input: &Path) {
+        self.channel.on_file_start(index, total_files, input);
+    }
+
+    fn on_progress(&self, index: usize, done: u64, total: u64) {
+        self.channel.on_progress(index, done, total);
+    }
+
+    fn on_file_done(&self, index: usize, input: &Path, succeeded: bool) {
+        self.channel.on_file_done(index, input, succeeded);
+    }
+
+    fn on_disk_full(&self, output: &Path) {
+        self.events
+            .send(BatchEvent::DiskFull {
+                id: self.id,
+                output: output.to_path_buf(),
+            })
+            .ok();
+        (self.wake)();
+    }
+}
+
+// Converts batches until the queue is empty. The caller sets worker_running before spawning it.
+pub fn run_worker(
+    queue: Arc<Mutex<Queue>>,
+    cancel_flag: Arc<AtomicBool>,
+    pause_flag: Arc<AtomicBool>,
+    stop_flag: Arc<AtomicBool>,
+    events: Sender<BatchEvent>,
+    progress: Sender<ProgressUpdate>,
+    wake: impl Fn() + Sync,
+) {
+    loop {
+        let batch = {
+            let mut queue = queue.lock().unwrap();
+            match queue.pending.pop_front() {
+                Some(batch) => {
+                    // Cancelling or stopping empties the queue under this lock, so a batch still here was queued after it
+                    cancel_flag.store(false, Ordering::Relaxed);
+                    stop_flag.store(false, Ordering::Relaxed);
+                    batch
+                }
+                None => {
+                    queue.worker_running = false;
+                    break;
+                }
+            }
+        };
+
+        log_info!("Starting queued batch of {} files", batch.files.len());
+        events.send(BatchEvent::Started(batch.id)).ok();
+        wake();
+        let sink = BatchProgress {
+            id: batch.id,
+            channel: ChannelProgress::new(progress.clone()),
+            events: &events,
+            wake: &wake,
+        };
+        let (cancel, pause, stop) = (Arc::clone(&cancel_flag), Arc::clone(&pause_flag), Arc::clone(&stop_flag));
+        // A thread per batch, as a lowered priority can't always be raised again for the next one
+        let result = std::thread::scope(|scope| {
+            scope
+                .spawn(|| {
+                    priority::set_current_thread(batch.options.priority);
+                    match &batch.image_path {
+                        Some(image_path) => image::convert_to_image(batch.files, image_path, cancel, pause, stop, &batch.options, &sink),
+                        None => conversion::convert_files(batch.files, cancel, pause, stop, &batch.options, &sink),
+                    }
+                })
+                .join()
+                .unwrap_or_else(|_| Err(anyhow::anyhow!("The conversion thread panicked")))
+        });
+        events
+            .send(BatchEvent::Finished {
+                id: batch.id,
+                result: result.map_err(|e| format!("{:#}", e)),
+                cancelled: cancel_flag.load(Ordering::Relaxed),
+                stopped: stop_flag.load(Ordering::Relaxed),
+            })
+            .ok();
+        wake();
+    }
+    wake(); // So the UI notices the queue has gone idle
+}
@@ -0,0 +1,192 @@
+use std::fs;
+use std::path::PathBuf;
+use anyhow::{Context, Result};
+use directories::ProjectDirs;
+use serde::{Deserialize, Serialize};
+use mp32cdda::conversion::{
+    ChannelMode, ConversionOptions, DitherMode, ExistingOutputPolicy, FadeCurve, GainMode, OutputContainer, OutputFormat, ResampleQuality, ShortTrackPolicy,
+    SplitMode,
+};
+use mp32cdda::priority::WorkerPriority;
+
+const SETTINGS_FILE_NAME: &str = "settings.toml";
+
+// What the GUI remembers between launches. Fields missing from the file take their defaults, so
+// a file from an older version still loads.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub output_dir: Option<PathBuf>,
+    pub output_format: OutputFormat,
+    pub output_container: OutputContainer,
+    pub target_rate: u32,
+    pub channel_mode: ChannelMode,
+    pub resample_quality: ResampleQuality,
+    pub no_resample: bool,
+    pub no_upsample: bool,
+    pub bit_perfect: bool,
+    pub priority: WorkerPriority,
+    pub dither: DitherMode,
+    pub normalize: bool,
+    pub gain_mode: GainMode,
+    pub write_cue: bool,
+    pub append_to_project: bool,
+    pub trim_silence: bool,
+    pub silence_threshold_db: f64,
+    pub limit_peaks: bool,
+    pub peak_ceiling_db: f64,
+    pub remove_dc: bool,
+    pub left_gain_db: f64,
+    pub right_gain_db: f64,
+    pub split_mode: SplitMode,
+    pub split_min_gap_seconds: f64,
+    pub fade_in_seconds: f64,
+    pub fade_out_seconds: f64,
+    pub fade_curve: FadeCurve,
+    pub save_cover_art: bool,
+    pub skip_existing: bool,
+    pub existing_outputs: ExistingOutputPolicy,
+    pub mirror_folders: bool,
+    pub name_template: Option<String>,
+    pub build_image: bool,
+    pub gap_frames: u64,
+    pub detect_gaps: bool,
+    pub first_track_pregap_frames: u64,
+    pub crossfade_seconds: f64,
+    pub short_tracks: ShortTrackPolicy,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self::capture(&ConversionOptions::default(), false)
+    }
+}
+
+impl Settings {
+    pub fn capture(options: &ConversionOptions, build_image: bool) -> Self {
+        Self {
+            output_dir: options.output_dir.clone(),
+            output_format: options.output_format,
+            output_container: options.output_container,
+            target_rate: options.target_rate,
+            channel_mode: options.channel_mode,
+            resample_quality: options.resample_quality,
+            no_resample: options.no_resample,
+            no_upsample: options.no_upsample,
+            bit_perfect: options.bit_perfect,
+            priority: options.priority,
+            dither: options.dither,
+            normalize: options.normalize,
+            gain_mode: options.gain_mode,
+            write_cue: options.write_cue,
+            append_to_project: options.append_to_project,
+            trim_silence: options.trim_silence,
+            silence_threshold_db: options.silence_threshold_db,
+            limit_peaks: options.limit_peaks,
+            peak_ceiling_db: options.peak_ceiling_db,
+            remove_dc: options.remove_dc,
+            left_gain_db: options.left_gain_db,
+            right_gain_db: options.right_gain_db,
+            split_mode: options.split_mode,
+            split_min_gap_seconds: options.split_min_gap_seconds,
+            fade_in_seconds: options.fade_in_seconds,
+            fade_out_seconds: options.fade_out_seconds,
+            fade_curve: options.fade_curve,
+            save_cover_art: options.save_cover_art,
+            skip_existing: options.skip_existing,
+            existing_outputs: options.existing_outputs,
+            mirror_folders: options.mirror_folders,
+            name_template: options.name_template.clone(),
+            build_image,
+            gap_frames: options.gap_frames,
+            detect_gaps: options.detect_gaps,
+            first_track_pregap_frames: options.first_track_pregap_frames,
+            crossfade_seconds: options.crossfade_seconds,
+            short_tracks: options.short_tracks,
+        }
+    }
+
+    pub fn to_options(&self) -> ConversionOptions {
+        ConversionOptions {
+            output_dir: self.output_dir.clone(),
+            output_format: self.output_format,
+            output_container: self.output_container,
+            target_rate: self.target_rate,
+            channel_mode: self.channel_mode,
+            resample_quality: self.resample_quality,
+            no_resample: self.no_resample,
+            no_upsample: self.no_upsample,
+            bit_perfect: self.bit_perfect,
+            priority: self.priority,
+            dither: self.dither,
+            normalize: self.normalize,
+            gain_mode: self.gain_mode,
+            write_cue: self.write_cue,
+            append_to_project: self.append_to_project,
+            trim_silence: self.trim_silence,
+            silence_threshold_db: self.silence_threshold_db,
+            limit_peaks: self.limit_peaks,
+            peak_ceiling_db: self.peak_ceiling_db,
+            remove_dc: self.remove_dc,
+            left_gain_db: self.left_gain_db,
+            right_gain_db: self.right_gain_db,
+            split_mode: self.split_mode,
+            split_min_gap_seconds: self.split_min_gap_seconds,
+            fade_in_seconds: self.fade_in_seconds,
+            fade_out_seconds: self.fade_out_seconds,
+            fade_curve: self.fade_curve,
+            save_cover_art: self.save_cover_art,
+            skip_existing: self.skip_existing,
+            existing_outputs: self.existing_outputs,
+            mirror_folders: self.mirror_folders,
+            name_template: self.name_template.clone(),
+            gap_frames: self.gap_frames,
+            detect_gaps: self.detect_gaps,
+            first_track_pregap_frames: self.first_track_pregap_frames,
+            crossfade_seconds: self.crossfade_seconds,
+            short_tracks: self.short_tracks,
+            ..ConversionOptions::default()
+        }
+    }
+
+    // Never fails: a missing file is a first launch, and a broken one is replaced on the next save
+    pub fn load() -> Settings {
+        let Some(path) = settings_path() else {
+            return Settings::default();
+        };
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Settings::default(),
+            Err(e) => {
+                log_warn!("Could not read settings from {:?}, using defaults: {}", path, e);
+                return Settings::default();
+            }
+        };
+        match toml::from_str(&text) {
+            Ok(settings) => {
+                log_debug!("Loaded settings from {:?}", path);
+                settings
+            }
+            Err(e) => {
+                log_warn!("Settings in {:?} are invalid, using defaults: {}", path, e);
+                Settings::default()
+            }
+        }
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = settings_path().context("No configuration folder for this user")?;
+        if let Some(folder) = path.parent() {
+            fs::create_dir_all(folder).with_context(|| format!("Failed to create {:?}", folder))?;
+        }
+        let text = toml::to_string_pretty(self).context("Failed to serialize settings")?;
+        fs::write(&path, text).with_context(|| format!("Failed to write {:?}", path))?;
+        log_debug!("Saved settings to {:?}", path);
+        Ok(())
+    }
+}
+
+// E.g. ~/.config/mp3-2-cdda/settings.toml, or %APPDATA%\mp3-2-cdda\config\settings.toml on Windows
+fn settings_path() -> Option<PathBuf> {
+    ProjectDirs::from("", "", "mp3-2-cdda").map(|dirs| dirs.config_dir().join(SETTINGS_FILE_NAME))
+}
@@ -0,0 +1,79 @@
+use std::path::Path;
+use std::process::Command;
+use anyhow::{Context, Result};
+use crate::conversion;
+
+// Shortest run of quiet samples worth trimming from a track's edges; shorter dips are treated as audio
+pub const EDGE_SILENCE_SECONDS: f64 = 0.01;
+// silencedetect timestamps are rounded, so edges this close to 0 or the end count as touching them
+const EDGE_TOLERANCE_SECONDS: f64 = 0.005;
+
+pub struct SilenceScan {
+    // (start, end) in seconds; end is None for silence running to the end of the file
+    pub silences: Vec<(f64, Option<f64>)>,
+    // Decoded length in seconds
+    pub duration: f64,
+}
+
+impl SilenceScan {
+    // Where the audio starts once leading silence is cut
+    pub fn audio_start(&self) -> f64 {
+        match self.silences.first() {
+            Some(&(start, Some(end))) if start <= EDGE_TOLERANCE_SECONDS => end,
+            Some(&(start, None)) if start <= EDGE_TOLERANCE_SECONDS => self.duration, // All silence
+            _ => 0.0,
+        }
+    }
+
+    // Where the audio ends once trailing silence is cut
+    pub fn audio_end(&self) -> f64 {
+        match self.silences.last() {
+            Some(&(start, None)) => start,
+            Some(&(start, Some(end))) if end >= self.duration - EDGE_TOLERANCE_SECONDS => start,
+            _ => self.duration,
+        }
+        .max(self.audio_start())
+    }
+}
+
+// Decodes the whole file once to the null muxer, keeping only silencedetect's log lines, so memory
+// use doesn't depend on the track length. A stretch counts as silent when every channel's samples
+// stay below threshold_db for at least min_silence_seconds. The scan runs on the 44.1 kHz stream
+// the conversion produces, so its timestamps match the output.
+pub fn scan_silence(input: &Path, threshold_db: f64, min_silence_seconds: f64) -> Result<SilenceScan> {
+    let output = Command::new("ffmpeg")
+        .args(["-hide_banner", "-i"])
+        .arg(input)
+        .args([
+            "-af",
+            &format!("aresample=44100,silencedetect=noise={:.1}dB:d={}", threshold_db, min_silence_seconds),
+            "-f", "null",
+            "-",
+        ])
+        .output()
+        .context("Failed to run ffmpeg silence scan")?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if !output.status.success() {
+        return Err(anyhow::anyhow!("ffmpeg silence scan failed: {}", stderr));
+    }
+
+    let mut silences: Vec<(f64, Option<f64>)> = Vec::new();
+    for line in stderr.lines() {
+        if let Some(start) = log_value(line, "silence_start:") {
+            silences.push((start, None));
+        } else if let Some(end) = log_value(line, "silence_end:") {
+            if let Some(last) = silences.last_mut() {
+                last.1 = Some(end);
+            }
+        }
+    }
+    let duration = conversion::parse_ffmpeg_time(&stderr)
+        .ok_or_else(|| anyhow::anyhow!("ffmpeg reported no duration for {:?}", input))?;
+
+    Ok(SilenceScan { silences, duration })
+}
+
+// "[silencedetect @ 0x...] silence_end: 12.345 | silence_duration: 1.2" -> 12.345
+fn log_value(line: &str, key: &str) -> Option<f64> {
+    line[line.find(key)? + key.len()..].split_whitespace().next()?.parse().ok()
+}
@@ -0,0 +1,167 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use anyhow::{Context, Result};
+use crate::conversion::{ConversionOptions, SplitMode, StreamInfo};
+use crate::cue::{self, CD_FRAMES_PER_SECOND};
+use crate::metadata::TrackInfo;
+use crate::silence;
+
+// Part of one input that becomes its own CD track. Boundaries are whole CD frames (588 samples
+// at 44.1 kHz), so every split lands on a sector boundary.
+#[derive(Clone, Copy, Debug)]
+pub struct Segment {
+    pub start_frame: u64,
+    // None runs to the end of the input
+    pub end_frame: Option<u64>,
+    // Frames at the start that are the track's pregap (INDEX 00 to INDEX 01 in a CUE sheet)
+    pub gap_frames: u64,
+}
+
+impl Segment {
+    pub fn start_seconds(&self) -> f64 {
+        self.start_frame as f64 / CD_FRAMES_PER_SECOND as f64
+    }
+
+    pub fn end_seconds(&self) -> Option<f64> {
+        self.end_frame.map(|frame| frame as f64 / CD_FRAMES_PER_SECOND as f64)
+    }
+}
+
+// Splits one input into numbered tracks according to options.split_mode. Each returned
+// StreamInfo carries its segment and tags numbered from 1 in playing order.
+pub fn split_input(input: &Path, stream_info: &StreamInfo, options: &ConversionOptions) -> Result<Vec<StreamInfo>> {
+    let segments = match options.split_mode {
+        SplitMode::Off => return Ok(vec![stream_info.clone()]),
+        SplitMode::Silence => silence_segments(input, options)?,
+        SplitMode::CueSheet => cue_segments(input, options.split_cue.as_deref())?,
+        SplitMode::Chapters => match chapter_segments(input)? {
+            Some(segments) => segments,
+            None => {
+                log_info!("{:?} has no chapters; converting it as one track", input);
+                return Ok(vec![stream_info.clone()]);
+            }
+        },
+    };
+    log_info!("Splitting {:?} into {} tracks", input, segments.len());
+
+    let source = &stream_info.track;
+    Ok(segments
+        .into_iter()
+        .enumerate()
+        .map(|(index, (segment, title, performer))| {
+            let track = TrackInfo {
+                title,
+                artist: performer.or_else(|| source.artist.clone()),
+                // The input's own title names the whole mix, which is the best album name available
+                album: source.album.clone().or_else(|| source.title.clone()),
+                track_number: Some(index as u32 + 1),
+                // The recording code of the whole mix doesn't identify any one part of it
+                isrc: None,
+                catalog: source.catalog.clone(),
+            };
+            stream_info.for_segment(segment, track)
+        })
+        .collect())
+}
+
+type NamedSegment = (Segment, Option<String>, Option<String>);
+
+// Splits in the middle of every interior gap at least split_min_gap_seconds long.
+fn silence_segments(input: &Path, options: &ConversionOptions) -> Result<Vec<NamedSegment>> {
+    let scan = silence::scan_silence(input, options.silence_threshold_db, options.split_min_gap_seconds)?;
+    let mut boundaries = vec![0];
+    for &(start, end) in &scan.silences {
+        let Some(end) = end else {
+            continue; // Trailing silence stays with the last track
+        };
+        if start > 0.0 && end < scan.duration {
+            let middle = ((start + end) / 2.0 * CD_FRAMES_PER_SECOND as f64).round() as u64;
+            if middle > *boundaries.last().unwrap() {
+                boundaries.push(middle);
+            }
+        }
+    }
+
+    let mut segments: Vec<NamedSegment> = boundaries
+        .windows(2)
+        .map(|pair| (Segment { start_frame: pair[0], end_frame: Some(pair[1]), gap_frames: 0 }, None, None))
+        .collect();
+    segments.push((Segment { start_frame: *boundaries.last().unwrap(), end_frame: None, gap_frames: 0 }, None, None));
+    Ok(segments)
+}
+
+// Reads the track list from cue_sheet, or "<input stem>.cue" next to the input. A track's pregap
+// (INDEX 00) is kept at the start of its own file, not at the end of the track before it.
+fn cue_segments(input: &Path, cue_sheet: Option<&Path>) -> Result<Vec<NamedSegment>> {
+    let cue_path = cue_sheet.map_or_else(|| input.with_extension("cue"), PathBuf::from);
+    let entries = cue::read_cue_sheet(&cue_path).with_context(|| format!("No usable CUE sheet for {:?}", input))?;
+
+    let mut segments = Vec::new();
+    for (index, entry) in entries.iter().enumerate() {
+        let end_frame = entries.get(index + 1).map(|next| next.first_frame());
+        segments.push((
+            Segment {
+                start_frame: entry.first_frame(),
+                end_frame,
+                gap_frames: entry.start_frame - entry.first_frame(),
+            },
+            entry.title.clone(),
+            entry.performer.clone(),
+        ));
+    }
+    Ok(segments)
+}
+
+// One track per chapter of the input (ID3 CHAP frames in an MP3, chapter atoms in an M4B), named
+// after the chapter. Each runs to the start of the next, which closes any gap between them; audio
+// ahead of the first chapter goes with it. None if there are fewer than two chapters.
+fn chapter_segments(input: &Path) -> Result<Option<Vec<NamedSegment>>> {
+    let output = Command::new("ffprobe")
+        .args(["-v", "error", "-show_entries", "chapter=start_time:chapter_tags=title", "-of", "default"])
+        .arg(input)
+        .output()
+        .context("Failed to run ffprobe")?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "ffprobe could not read the chapters of {:?}: {}",
+            input,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    // Start in CD frames and title of each chapter, in the order ffprobe lists them
+    let mut chapters: Vec<(u64, Option<String>)> = Vec::new();
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        match line.trim().split_once('=') {
+            None if line.trim() == "[CHAPTER]" => chapters.push((0, None)),
+            Some(("start_time", value)) => {
+                if let (Some(chapter), Ok(seconds)) = (chapters.last_mut(), value.trim().parse::<f64>()) {
+                    chapter.0 = (seconds.max(0.0) * CD_FRAMES_PER_SECOND as f64).round() as u64;
+                }
+            }
+            Some((key, value)) if key.eq_ignore_ascii_case("TAG:title") => {
+                if let Some(chapter) = chapters.last_mut() {
+                    chapter.1 = Some(value.trim().to_string()).filter(|title| !title.is_empty());
+                }
+            }
+            _ => {}
+        }
+    }
+    chapters.sort_by_key(|(start, _)| *start);
+    // Chapters that start together would make empty tracks; the first title is kept
+    chapters.dedup_by_key(|(start, _)| *start);
+    if chapters.len() < 2 {
+        return Ok(None);
+    }
+    chapters[0].0 = 0;
+
+    let segments = chapters
+        .iter()
+        .enumerate()
+        .map(|(index, (start, title))| {
+            let end_frame = chapters.get(index + 1).map(|(next, _)| *next);
+            (Segment { start_frame: *start, end_frame, gap_frames: 0 }, title.clone(), None)
+        })
+        .collect();
+    Ok(Some(segments))
+}
@@ -0,0 +1,125 @@
+// Helpers for the unit tests: scratch folders, synthetic audio and a way to run a conversion.
+// Tests that need ffmpeg and ffprobe are marked #[ignore = "needs ffmpeg"], so they show up as
+// ignored rather than passing when skipped; `cargo test -- --include-ignored` runs them.
+use std::fs::{self, File};
+use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use anyhow::Result;
+use crate::conversion::{self, ConversionOptions, ConversionSummary};
+use crate::progress::{PlanCollector, ProgressSink};
+use crate::wav;
+
+// A scratch folder, removed with everything in it when the test is done with it
+pub struct TempDir(PathBuf);
+
+impl Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+// A new, empty folder under the system's temporary folder
+pub fn temp_dir(name: &str) -> TempDir {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    let dir = std::env::temp_dir().join(format!(
+        "mp32cdda-test-{}-{}-{}",
+        std::process::id(),
+        name,
+        NEXT.fetch_add(1, Ordering::Relaxed)
+    ));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    TempDir(dir)
+}
+
+// Runs ffmpeg quietly, failing the test with its message if it fails
+pub fn ffmpeg(args: &[&str]) {
+    let output = Command::new("ffmpeg").args(["-v", "error", "-y"]).args(args).output().unwrap();
+    assert!(output.status.success(), "ffmpeg {:?} failed: {}", args, String::from_utf8_lossy(&output.stderr));
+}
+
+// Interleaved samples of a sine at the same level on every channel
+pub fn sine(sample_rate: u32, frequency: f64, amplitude: f64, frames: usize, channels: u16) -> Vec<i16> {
+    (0..frames)
+        .flat_map(|frame| {
+            let value = amplitude * (2.0 * std::f64::consts::PI * frequency * frame as f64 / f64::from(sample_rate)).sin();
+            std::iter::repeat_n((value * 32767.0).round() as i16, usize::from(channels))
+        })
+        .collect()
+}
+
+// A plain 16-bit PCM WAV of interleaved samples
+pub fn write_wav(path: &Path, sample_rate: u32, channels: u16, samples: &[i16]) {
+    let data_len = samples.len() as u32 * 2;
+    let block_align = channels * 2;
+    let mut file = BufWriter::new(File::create(path).unwrap());
+    file.write_all(b"RIFF").unwrap();
+    file.write_all(&(36 + data_len).to_le_bytes()).unwrap();
+    file.write_all(b"WAVEfmt ").unwrap();
+    file.write_all(&16u32.to_le_bytes()).unwrap();
+    file.write_all(&1u16.to_le_bytes()).unwrap();
+    file.write_all(&channels.to_le_bytes()).unwrap();
+    file.write_all(&sample_rate.to_le_bytes()).unwrap();
+    file.write_all(&(sample_rate * u32::from(block_align)).to_le_bytes()).unwrap();
+    file.write_all(&block_align.to_le_bytes()).unwrap();
+    file.write_all(&16u16.to_le_bytes()).unwrap();
+    file.write_all(b"data").unwrap();
+    file.write_all(&data_len.to_le_bytes()).unwrap();
+    for sample in samples {
+        file.write_all(&sample.to_le_bytes()).unwrap();
+    }
+    file.flush().unwrap();
+}
+
+// The interleaved samples of a 16-bit WAV's data chunk, sector padding included
+pub fn read_wav_samples(path: &Path) -> Vec<i16> {
+    let info = wav::read_wav_info(path).unwrap();
+    let mut data = vec![0u8; info.data_len as usize];
+    let mut file = File::open(path).unwrap();
+    file.seek(SeekFrom::Start(info.data_offset)).unwrap();
+    file.read_exact(&mut data).unwrap();
+    data.chunks_exact(2).map(|pair| i16::from_le_bytes([pair[0], pair[1]])).collect()
+}
+
+// Options that write into `output` and nothing besides the audio and its log
+pub fn options_into(output: &Path) -> ConversionOptions {
+    ConversionOptions {
+        output_dir: Some(output.to_path_buf()),
+        save_cover_art: false,
+        ..ConversionOptions::default()
+    }
+}
+
+// Converts into `output` with `options`, failing the test unless every input converts
+pub fn convert_ok(inputs: Vec<PathBuf>, output: &Path, options: ConversionOptions) -> ConversionSummary {
+    let summary = convert(inputs, &ConversionOptions { output_dir: Some(output.to_path_buf()), save_cover_art: false, ..options })
+        .unwrap();
+    assert_eq!(summary.failed(), 0, "{:?}", summary.failures);
+    summary
+}
+
+pub fn convert(inputs: Vec<PathBuf>, options: &ConversionOptions) -> Result<ConversionSummary> {
+    convert_with(inputs, options, &PlanCollector::default(), Arc::new(AtomicBool::new(false)))
+}
+
+pub fn convert_with(
+    inputs: Vec<PathBuf>,
+    options: &ConversionOptions,
+    progress: &dyn ProgressSink,
+    cancel_flag: Arc<AtomicBool>,
+) -> Result<ConversionSummary> {
+    let unset = || Arc::new(AtomicBool::new(false));
+    conversion::convert_files(inputs, cancel_flag, unset(), unset(), options, progress)
+}
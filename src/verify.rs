@@ -0,0 +1,89 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use anyhow::{Context, Result};
+use crate::cue::CD_FRAMES_PER_SECOND;
+use crate::wav::{self, CD_SECTOR_BYTES};
+
+// Red Book minimum track length
+const MIN_TRACK_FRAMES: u64 = 4 * CD_FRAMES_PER_SECOND;
+
+pub struct CddaReport {
+    pub path: PathBuf,
+    pub violations: Vec<String>,
+}
+
+impl CddaReport {
+    pub fn is_compliant(&self) -> bool {
+        self.violations.is_empty()
+    }
+}
+
+// Errors only when the file can't be read as a WAV at all; everything else is listed as a violation.
+pub fn verify_cdda(path: &Path) -> Result<CddaReport> {
+    let info = wav::read_wav_info(path)?;
+    let mut violations = Vec::new();
+
+    match &info.format {
+        Some(format) => {
+            if !format.is_pcm() {
+                violations.push(format!("not PCM (format tag {:#06x})", format.format_tag));
+            }
+            if format.sample_rate != 44100 {
+                violations.push(format!("sample rate is {} Hz, expected 44100 Hz", format.sample_rate));
+            }
+            if format.bits_per_sample != 16 {
+                violations.push(format!("{} bits per sample, expected 16", format.bits_per_sample));
+            }
+            if format.channels != 2 {
+                violations.push(format!("{} channels, expected 2", format.channels));
+            }
+        }
+        None => violations.push("no fmt chunk before the audio data".to_string()),
+    }
+    if info.data_len % CD_SECTOR_BYTES != 0 {
+        violations.push(format!(
+            "data length {} bytes is not a whole number of {}-byte sectors",
+            info.data_len, CD_SECTOR_BYTES
+        ));
+    }
+    if info.cd_frames() < MIN_TRACK_FRAMES {
+        violations.push(format!(
+            "{:.2} s long, shorter than the 4 s CD minimum",
+            info.cd_frames() as f64 / CD_FRAMES_PER_SECOND as f64
+        ));
+    }
+
+    Ok(CddaReport {
+        path: path.to_path_buf(),
+        violations,
+    })
+}
+
+// Checks every WAV and AIFF in the folder, in name order. Unreadable files are reported rather than skipped.
+pub fn verify_folder(folder: &Path) -> Result<Vec<CddaReport>> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(folder)
+        .with_context(|| format!("Failed to read {:?}", folder))?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.extension()
+                .and_then(|ext| ext.to_str())
+                .is_some_and(|ext| ["wav", "aif", "aiff"].iter().any(|known| ext.eq_ignore_ascii_case(known)))
+        })
+        .collect();
+    paths.sort();
+
+    let reports: Vec<CddaReport> = paths
+        .into_iter()
+        .map(|path| {
+            verify_cdda(&path).unwrap_or_else(|e| CddaReport {
+                violations: vec![format!("unreadable: {}", e)],
+                path,
+            })
+        })
+        .collect();
+
+    let failing = reports.iter().filter(|report| !report.is_compliant()).count();
+    log_info!("Verified {} audio files in {:?}: {} not CDDA compliant", reports.len(), folder, failing);
+    Ok(reports)
+}
@@ -0,0 +1,576 @@
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use anyhow::{Context, Result};
+
+// One Red Book sector holds 588 stereo 16-bit frames.
+pub const CD_SECTOR_BYTES: u64 = 2352;
+
+const WAVE_FORMAT_PCM: u16 = 1;
+const WAVE_FORMAT_IEEE_FLOAT: u16 = 3;
+const WAVE_FORMAT_EXTENSIBLE: u16 = 0xFFFE;
+
+pub struct WavFormat {
+    pub format_tag: u16,
+    pub channels: u16,
+    pub sample_rate: u32,
+    pub bits_per_sample: u16,
+}
+
+impl WavFormat {
+    pub fn is_pcm(&self) -> bool {
+        self.format_tag == WAVE_FORMAT_PCM || self.format_tag == WAVE_FORMAT_EXTENSIBLE
+    }
+
+    pub fn is_cdda(&self) -> bool {
+        self.is_pcm() && self.sample_rate == 44100 && self.bits_per_sample == 16 && self.channels == 2
+    }
+}
+
+pub struct WavInfo {
+    // None when the fmt chunk is missing or comes after the data chunk
+    pub format: Option<WavFormat>,
+    pub data_offset: u64,
+    pub data_len: u64,
+    pub file_len: u64,
+    layout: Layout,
+}
+
+// Where the size fields are that padding has to update
+enum Layout {
+    Riff,
+    // Big-endian, and the sample frame count in COMM has to match the SSND chunk
+    Aiff { ssnd_size_offset: u64, frame_count_offset: u64 },
+}
+
+impl WavInfo {
+    pub fn cd_frames(&self) -> u64 {
+        self.data_len.div_ceil(CD_SECTOR_BYTES)
+    }
+
+    // Sample frames (one sample per channel) in the data chunk
+    pub fn sample_frames(&self) -> Option<u64> {
+        self.frame_bytes().map(|frame_bytes| self.data_len / frame_bytes)
+    }
+
+    fn frame_bytes(&self) -> Option<u64> {
+        let format = self.format.as_ref()?;
+        let frame_bytes = u64::from(format.channels) * u64::from(format.bits_per_sample).div_ceil(8);
+        (frame_bytes > 0).then_some(frame_bytes)
+    }
+}
+
+// Reads AIFF files too, into the same fields, so the checks and padding work on either container
+pub fn read_wav_info(path: &Path) -> Result<WavInfo> {
+    let mut file = File::open(path).with_context(|| format!("Failed to open {:?}", path))?;
+    let file_len = file.metadata()?.len();
+
+    let mut header = [0u8; 12];
+    file.read_exact(&mut header).context("WAV header is truncated")?;
+    if &header[0..4] == b"FORM" && (&header[8..12] == b"AIFF" || &header[8..12] == b"AIFC") {
+        return read_aiff_chunks(&mut file, path, file_len);
+    }
+    if &header[0..4] != b"RIFF" || &header[8..12] != b"WAVE" {
+        return Err(anyhow::anyhow!("{:?} is not a RIFF/WAVE or AIFF file", path));
+    }
+
+    let mut format = None;
+    loop {
+        let mut chunk = [0u8; 8];
+        file.read_exact(&mut chunk)
+            .with_context(|| format!("No data chunk found in {:?}", path))?;
+        let size = u64::from(u32::from_le_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]));
+
+        if &chunk[0..4] == b"data" {
+            let data_offset = file.stream_position()?;
+            // Streamed writers may leave a placeholder size, so never trust it past the end of the file
+            return Ok(WavInfo {
+                format,
+                data_offset,
+                data_len: size.min(file_len.saturating_sub(data_offset)),
+                file_len,
+                layout: Layout::Riff,
+            });
+        }
+
+        let mut skip = size;
+        if &chunk[0..4] == b"fmt " && size >= 16 {
+            let mut fmt = [0u8; 16];
+            file.read_exact(&mut fmt).context("fmt chunk is truncated")?;
+            format = Some(WavFormat {
+                format_tag: u16::from_le_bytes([fmt[0], fmt[1]]),
+                channels: u16::from_le_bytes([fmt[2], fmt[3]]),
+                sample_rate: u32::from_le_bytes([fmt[4], fmt[5], fmt[6], fmt[7]]),
+                bits_per_sample: u16::from_le_bytes([fmt[14], fmt[15]]),
+            });
+            skip -= 16;
+        }
+
+        // Chunks are word-aligned
+        file.seek(SeekFrom::Current((skip + (size & 1)) as i64))?;
+    }
+}
+
+fn read_aiff_chunks(file: &mut File, path: &Path, file_len: u64) -> Result<WavInfo> {
+    let mut format = None;
+    let mut frame_count_offset = None;
+    loop {
+        let mut chunk = [0u8; 8];
+        file.read_exact(&mut chunk)
+            .with_context(|| format!("No SSND chunk found in {:?}", path))?;
+        let size = u64::from(u32::from_be_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]));
+        let chunk_start = file.stream_position()?;
+
+        if &chunk[0..4] == b"SSND" {
+            let mut offsets = [0u8; 8];
+            file.read_exact(&mut offsets).context("SSND chunk is truncated")?;
+            let offset = u64::from(u32::from_be_bytes([offsets[0], offsets[1], offsets[2], offsets[3]]));
+            let data_offset = chunk_start + 8 + offset;
+            return Ok(WavInfo {
+                format,
+                data_offset,
+                data_len: size.saturating_sub(8 + offset).min(file_len.saturating_sub(data_offset)),
+                file_len,
+                layout: Layout::Aiff {
+                    ssnd_size_offset: chunk_start - 4,
+                    frame_count_offset: frame_count_offset
+                        .ok_or_else(|| anyhow::anyhow!("No COMM chunk before the audio data in {:?}", path))?,
+                },
+            });
+        }
+
+        if &chunk[0..4] == b"COMM" && size >= 18 {
+            let mut comm = [0u8; 22];
+            let read = if size >= 22 { 22 } else { 18 };
+            file.read_exact(&mut comm[..read]).context("COMM chunk is truncated")?;
+            // AIFC adds a compression type; "fl32" is the only non-integer one ffmpeg writes
+            let format_tag = if &comm[18..22] == b"fl32" { WAVE_FORMAT_IEEE_FLOAT } else { WAVE_FORMAT_PCM };
+            format = Some(WavFormat {
+                format_tag,
+                channels: u16::from_be_bytes([comm[0], comm[1]]),
+                sample_rate: extended_to_u32(&comm[8..18]),
+                bits_per_sample: u16::from_be_bytes([comm[6], comm[7]]),
+            });
+            frame_count_offset = Some(chunk_start + 2);
+        }
+
+        // Chunks are word-aligned
+        file.seek(SeekFrom::Start(chunk_start + size + (size & 1)))?;
+    }
+}
+
+// The 80-bit extended float AIFF stores its sample rate in
+fn extended_to_u32(bytes: &[u8]) -> u32 {
+    let exponent = i32::from(u16::from_be_bytes([bytes[0], bytes[1]]) & 0x7FFF) - 16383;
+    let mantissa = u64::from_be_bytes(bytes[2..10].try_into().unwrap());
+    if !(0..32).contains(&exponent) {
+        return 0;
+    }
+    (mantissa >> (63 - exponent)) as u32
+}
+
+// Appends silence so the data chunk ends on a sector boundary. Returns the number of bytes added.
+pub fn pad_to_sector(path: &Path) -> Result<u64> {
+    pad_to_frames(path, 0)
+}
+
+// Pads to a whole sector and to at least min_frames sectors, e.g. the shortest track a CD allows
+pub fn pad_to_frames(path: &Path, min_frames: u64) -> Result<u64> {
+    let info = read_wav_info(path)?;
+    let padding = info.cd_frames().max(min_frames) * CD_SECTOR_BYTES - info.data_len;
+    if padding == 0 {
+        return Ok(0);
+    }
+    if info.data_offset + info.data_len != info.file_len {
+        return Err(anyhow::anyhow!("Data chunk is not at the end of {:?}; cannot pad it", path));
+    }
+
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(path)
+        .with_context(|| format!("Failed to open {:?} for padding", path))?;
+    file.seek(SeekFrom::End(0))?;
+    file.write_all(&vec![0u8; padding as usize])?;
+
+    let data_len = u32::try_from(info.data_len + padding).context("Padded WAV exceeds 4 GiB")?;
+    let riff_len = u32::try_from(info.file_len + padding - 8).context("Padded WAV exceeds 4 GiB")?;
+    match info.layout {
+        Layout::Riff => {
+            file.seek(SeekFrom::Start(info.data_offset - 4))?;
+            file.write_all(&data_len.to_le_bytes())?;
+            file.seek(SeekFrom::Start(4))?;
+            file.write_all(&riff_len.to_le_bytes())?;
+        }
+        Layout::Aiff { ssnd_size_offset, frame_count_offset } => {
+            let ssnd_len = data_len + (info.data_offset - ssnd_size_offset - 4) as u32;
+            let frame_bytes = info.frame_bytes().context("AIFF has no COMM chunk")?;
+            let frames = u32::try_from((info.data_len + padding) / frame_bytes)?;
+            file.seek(SeekFrom::Start(ssnd_size_offset))?;
+            file.write_all(&ssnd_len.to_be_bytes())?;
+            file.seek(SeekFrom::Start(frame_count_offset))?;
+            file.write_all(&frames.to_be_bytes())?;
+            file.seek(SeekFrom::Start(4))?;
+            file.write_all(&riff_len.to_be_bytes())?;
+        }
+    }
+    file.flush()?;
+
+    Ok(padding)
+}
+
+// CRC32 of the PCM data chunk only, so the value survives header rewrites and tag edits.
+pub fn data_crc32(path: &Path, info: &WavInfo) -> Result<u32> {
+    let mut file = File::open(path).with_context(|| format!("Failed to open {:?}", path))?;
+    file.seek(SeekFrom::Start(info.data_offset))?;
+    let mut data = file.take(info.data_len);
+    let mut hasher = crc32fast::Hasher::new();
+    let mut buffer = vec![0u8; 64 * 1024];
+    loop {
+        let read = data.read(&mut buffer).with_context(|| format!("Failed to read {:?}", path))?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hasher.finalize())
+}
+
+// This many full-scale samples in a row on one channel count as clipping. A single one is
+// usually a peak that just reaches the ceiling.
+pub const CLIP_RUN_SAMPLES: u64 = 3;
+// Stereo whose channels correlate this closely in the treble is all but mono there, as low-bitrate
+// MP3s with intensity stereo tend to be
+pub const NEAR_MONO_CORRELATION: f64 = 0.95;
+// Dynamic range below this is the flattened sound of a loudness-war master
+pub const COMPRESSED_DR_DB: f64 = 8.0;
+// Stretches the level is measured over for the dynamic range, as the common DR meters do
+const DR_BLOCK_SECONDS: u32 = 3;
+// The share of loudest blocks whose level is compared with the peak
+const DR_LOUD_FRACTION: f64 = 0.2;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SignalStats {
+    // Largest absolute sample, 1.0 being full scale
+    pub peak: f64,
+    // Runs of at least CLIP_RUN_SAMPLES full-scale samples
+    pub clipped_runs: u64,
+    // Correlation of left and right, from -1 to 1, measured on the sample-to-sample differences so
+    // the treble counts most; None unless the audio is stereo with something in it
+    pub stereo_correlation: Option<f64>,
+    // Left and right are the same throughout, i.e. mono on purpose rather than collapsed
+    pub dual_mono: bool,
+    // Peak over the RMS level of the loudest fifth of the track, in dB; a crude take on the DR
+    // meters' figure, where a full-scale sine reads 0. None for tracks under one block.
+    pub dynamic_range_db: Option<f64>,
+}
+
+impl SignalStats {
+    pub fn peak_dbfs(&self) -> f64 {
+        20.0 * self.peak.log10()
+    }
+
+    pub fn clips(&self) -> bool {
+        self.clipped_runs > 0
+    }
+
+    pub fn near_mono(&self) -> bool {
+        !self.dual_mono && self.stereo_correlation.is_some_and(|correlation| correlation >= NEAR_MONO_CORRELATION)
+    }
+
+    pub fn is_compressed(&self) -> bool {
+        self.dynamic_range_db.is_some_and(|dr| dr < COMPRESSED_DR_DB)
+    }
+
+    // What is worth a second look before burning, e.g. "clips 4 times, peak 0.00 dBFS"
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if self.clips() {
+            problems.push(format!("clips {} times, peak {:.2} dBFS", self.clipped_runs, self.peak_dbfs()));
+        }
+        if let Some(correlation) = self.stereo_correlation.filter(|_| self.near_mono()) {
+            problems.push(format!("nearly mono, L/R correlation {:.0}%", correlation * 100.0));
+        }
+        if let Some(dr) = self.dynamic_range_db.filter(|_| self.is_compressed()) {
+            problems.push(format!("heavily compressed, dynamic range {:.1} dB", dr));
+        }
+        problems
+    }
+}
+
+// Level, clipping and stereo image of interleaved samples fed one at a time, normalized to ±1.0
+struct SignalMeter {
+    full_scale: f64,
+    runs: Vec<u64>,
+    // The previous frame, for the differences the correlation is taken over
+    previous: [f64; 2],
+    frame: [f64; 2],
+    sum_lr: f64,
+    sum_ll: f64,
+    sum_rr: f64,
+    next_channel: usize,
+    // Sum of squares of the block being measured for the dynamic range, and its samples so far
+    block_energy: f64,
+    block_samples: u64,
+    samples_per_block: u64,
+    // Mean square of every finished block
+    block_levels: Vec<f64>,
+    stats: SignalStats,
+}
+
+impl SignalMeter {
+    fn new(channels: u16, sample_rate: u32, full_scale: f64) -> Self {
+        Self {
+            full_scale,
+            runs: vec![0; usize::from(channels.max(1))],
+            previous: [0.0; 2],
+            frame: [0.0; 2],
+            sum_lr: 0.0,
+            sum_ll: 0.0,
+            sum_rr: 0.0,
+            next_channel: 0,
+            block_energy: 0.0,
+            block_samples: 0,
+            samples_per_block: u64::from(DR_BLOCK_SECONDS * sample_rate) * u64::from(channels.max(1)),
+            block_levels: Vec::new(),
+            stats: SignalStats {
+                dual_mono: channels == 2,
+                ..SignalStats::default()
+            },
+        }
+    }
+
+    fn add(&mut self, sample: f64) {
+        let level = sample.abs();
+        self.stats.peak = self.stats.peak.max(level);
+        self.block_energy += sample * sample;
+        self.block_samples += 1;
+        if self.block_samples == self.samples_per_block {
+            self.block_levels.push(self.block_energy / self.block_samples as f64);
+            self.block_energy = 0.0;
+            self.block_samples = 0;
+        }
+        let run = &mut self.runs[self.next_channel];
+        if level >= self.full_scale {
+            *run += 1;
+            // Counted once, when the run gets long enough
+            if *run == CLIP_RUN_SAMPLES {
+                self.stats.clipped_runs += 1;
+            }
+        } else {
+            *run = 0;
+        }
+        if self.runs.len() == 2 {
+            self.frame[self.next_channel] = sample;
+            if self.next_channel == 1 {
+                self.add_stereo_frame();
+            }
+        }
+        self.next_channel = (self.next_channel + 1) % self.runs.len();
+    }
+
+    fn add_stereo_frame(&mut self) {
+        let [left, right] = self.frame;
+        let (dl, dr) = (left - self.previous[0], right - self.previous[1]);
+        self.sum_lr += dl * dr;
+        self.sum_ll += dl * dl;
+        self.sum_rr += dr * dr;
+        self.stats.dual_mono &= left == right;
+        self.previous = self.frame;
+    }
+
+    fn finish(mut self) -> SignalStats {
+        let energy = (self.sum_ll * self.sum_rr).sqrt();
+        self.stats.stereo_correlation = (energy > 0.0).then(|| self.sum_lr / energy);
+        self.stats.dynamic_range_db = self.dynamic_range_db();
+        self.stats
+    }
+
+    // The final partial block is left out, as a fade-out there would only drag the figure around
+    fn dynamic_range_db(&mut self) -> Option<f64> {
+        if self.block_levels.is_empty() || self.stats.peak == 0.0 {
+            return None;
+        }
+        self.block_levels.sort_by(|a, b| b.total_cmp(a));
+        let loud = ((self.block_levels.len() as f64 * DR_LOUD_FRACTION).ceil() as usize).max(1);
+        let mean_square = self.block_levels[..loud].iter().sum::<f64>() / loud as f64;
+        if mean_square == 0.0 {
+            return None;
+        }
+        // RMS times √2, so a full-scale sine comes out at the same level as its peak
+        let rms = (2.0 * mean_square).sqrt();
+        Some(20.0 * (self.stats.peak / rms).log10())
+    }
+}
+
+// Streams the data chunk once; 16- and 24-bit PCM and 32-bit float, in WAV or AIFF byte order.
+pub fn data_stats(path: &Path, info: &WavInfo) -> Result<SignalStats> {
+    let format = info.format.as_ref().context("No fmt chunk to read the samples by")?;
+    let big_endian = matches!(info.layout, Layout::Aiff { .. });
+    let float = format.format_tag == WAVE_FORMAT_IEEE_FLOAT;
+    let sample_bytes = usize::from(format.bits_per_sample.div_ceil(8));
+    // The largest positive integer sample, e.g. 32767, is as full as integer audio gets
+    let full_scale = match (float, format.bits_per_sample) {
+        (true, 32) => 1.0,
+        (false, 16 | 24) => 1.0 - 1.0 / f64::from(1u32 << (format.bits_per_sample - 1)),
+        _ => {
+            return Err(anyhow::anyhow!(
+                "Can't measure peaks of {}-bit {} samples",
+                format.bits_per_sample,
+                if float { "float" } else { "integer" }
+            ))
+        }
+    };
+    let decode = |bytes: &[u8]| -> f64 {
+        let mut word = [0u8; 4];
+        // Integer samples are placed in the top bytes of an i32, so both widths scale alike
+        if big_endian {
+            word[..sample_bytes].copy_from_slice(bytes);
+        } else {
+            for (i, byte) in bytes.iter().rev().enumerate() {
+                word[i] = *byte;
+            }
+        }
+        if float {
+            f64::from(f32::from_be_bytes(word))
+        } else {
+            f64::from(i32::from_be_bytes(word)) / 2f64.powi(31)
+        }
+    };
+
+    let mut file = File::open(path).with_context(|| format!("Failed to open {:?}", path))?;
+    file.seek(SeekFrom::Start(info.data_offset))?;
+    let mut data = file.take(info.data_len);
+    let mut meter = SignalMeter::new(format.channels, format.sample_rate, full_scale);
+    let mut buffer = vec![0u8; 64 * 1024 / sample_bytes * sample_bytes];
+    let mut filled = 0;
+    loop {
+        let read = data.read(&mut buffer[filled..]).with_context(|| format!("Failed to read {:?}", path))?;
+        filled += read;
+        let whole = filled / sample_bytes * sample_bytes;
+        for sample in buffer[..whole].chunks_exact(sample_bytes) {
+            meter.add(decode(sample));
+        }
+        buffer.copy_within(whole..filled, 0);
+        filled -= whole;
+        if read == 0 {
+            break;
+        }
+    }
+    Ok(meter.finish())
+}
+
+// Peaks of the first and last `bytes` of headerless 16-bit little-endian audio, 1.0 being full
+// scale. Only those two stretches are read, however long the file.
+pub fn raw_cdda_edge_peaks(path: &Path, bytes: u64) -> Result<(f64, f64)> {
+    let mut file = File::open(path).with_context(|| format!("Failed to open {:?}", path))?;
+    let len = file.metadata()?.len();
+    let bytes = bytes.min(len) / 2 * 2;
+    let mut peak_at = |offset: u64| -> Result<f64> {
+        let mut buffer = vec![0u8; bytes as usize];
+        file.seek(SeekFrom::Start(offset))?;
+        file.read_exact(&mut buffer).with_context(|| format!("Failed to read {:?}", path))?;
+        Ok(buffer
+            .chunks_exact(2)
+            .map(|sample| f64::from(i16::from_le_bytes([sample[0], sample[1]])).abs() / 32768.0)
+            .fold(0.0, f64::max))
+    };
+    let head = peak_at(0)?;
+    let tail = peak_at((len - bytes) / 2 * 2)?;
+    Ok((head, tail))
+}
+
+// The same for headerless 16-bit little-endian stereo, such as the tracks of a BIN image
+pub fn raw_cdda_stats(path: &Path) -> Result<SignalStats> {
+    let bytes = std::fs::metadata(path).with_context(|| format!("Failed to open {:?}", path))?.len();
+    let info = WavInfo {
+        format: Some(WavFormat {
+            format_tag: WAVE_FORMAT_PCM,
+            channels: 2,
+            sample_rate: 44100,
+            bits_per_sample: 16,
+        }),
+        data_offset: 0,
+        data_len: bytes,
+        file_len: bytes,
+        layout: Layout::Riff,
+    };
+    data_stats(path, &info)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use super::*;
+    use crate::testing;
+
+    // 16-bit stereo 44.1 kHz AIFF of interleaved samples, as ffmpeg writes it
+    fn write_aiff(path: &Path, samples: &[i16]) {
+        let frames = samples.len() as u32 / 2;
+        let ssnd_len = 8 + samples.len() as u32 * 2;
+        let mut bytes = b"FORM".to_vec();
+        bytes.extend((4 + 8 + 18 + 8 + ssnd_len).to_be_bytes());
+        bytes.extend(b"AIFFCOMM");
+        bytes.extend(18u32.to_be_bytes());
+        bytes.extend(2u16.to_be_bytes());
+        bytes.extend(frames.to_be_bytes());
+        bytes.extend(16u16.to_be_bytes());
+        // 44100 as an 80-bit extended float: 2^15 times 1.3458...
+        bytes.extend([0x40, 0x0E, 0xAC, 0x44, 0, 0, 0, 0, 0, 0]);
+        bytes.extend(b"SSND");
+        bytes.extend(ssnd_len.to_be_bytes());
+        bytes.extend([0u8; 8]);
+        for sample in samples {
+            bytes.extend(sample.to_be_bytes());
+        }
+        fs::write(path, bytes).unwrap();
+    }
+
+    fn read_be_samples(path: &Path, info: &WavInfo) -> Vec<i16> {
+        let bytes = fs::read(path).unwrap();
+        bytes[info.data_offset as usize..(info.data_offset + info.data_len) as usize]
+            .chunks_exact(2)
+            .map(|pair| i16::from_be_bytes([pair[0], pair[1]]))
+            .collect()
+    }
+
+    #[test]
+    fn reads_aiff_header_and_samples() {
+        let dir = testing::temp_dir("aiff");
+        let path = dir.join("track.aiff");
+        let samples: Vec<i16> = (0..2000).map(|index| (index * 37 - 20000) as i16).collect();
+        write_aiff(&path, &samples);
+
+        let info = read_wav_info(&path).unwrap();
+        let format = info.format.as_ref().unwrap();
+        assert_eq!(format.sample_rate, 44100);
+        assert_eq!(format.channels, 2);
+        assert_eq!(format.bits_per_sample, 16);
+        assert!(format.is_cdda());
+        assert_eq!(info.sample_frames(), Some(1000));
+        assert_eq!(info.data_offset + info.data_len, info.file_len);
+        let read = read_be_samples(&path, &info);
+        assert_eq!(read[..4], [-20000, -19963, -19926, -19889]);
+        assert_eq!(read, samples);
+    }
+
+    #[test]
+    fn pads_aiff_to_a_sector() {
+        let dir = testing::temp_dir("aiff-pad");
+        let path = dir.join("track.aiff");
+        let samples: Vec<i16> = (0..2000).map(|index| index as i16).collect();
+        write_aiff(&path, &samples);
+
+        // 1000 frames is 4000 bytes, so 704 more fill the second sector
+        assert_eq!(pad_to_sector(&path).unwrap(), 704);
+        let info = read_wav_info(&path).unwrap();
+        assert_eq!(info.data_len, 2 * CD_SECTOR_BYTES);
+        assert_eq!(info.sample_frames(), Some(2 * 588));
+        // The COMM frame count has to agree with the longer SSND chunk
+        let bytes = fs::read(&path).unwrap();
+        assert_eq!(bytes[22..26], 1176u32.to_be_bytes());
+        let read = read_be_samples(&path, &info);
+        assert_eq!(read[..samples.len()], samples[..]);
+        assert!(read[samples.len()..].iter().all(|sample| *sample == 0));
+    }
+}
@@ -0,0 +1,85 @@
+use std::io::{BufReader, Read};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use anyhow::{Context, Result};
+
+// Decoding at a low rate keeps the scan cheap; the overview only has a few hundred points anyway
+const SCAN_RATE: u32 = 8000;
+// Samples folded into one block while decoding, so memory use doesn't grow with the track length
+const BLOCK_SAMPLES: usize = 400;
+// Buckets that reach this far are drawn as clipping
+pub const CLIP_LEVEL: f32 = 0.99;
+
+// Lowest and highest sample of each stretch of the track, in -1.0..=1.0, both channels together
+#[derive(Clone, Debug)]
+pub struct Waveform {
+    pub buckets: Vec<(f32, f32)>,
+}
+
+impl Waveform {
+    pub fn is_silent(&self) -> bool {
+        self.buckets.iter().all(|(low, high)| low.abs() < 0.001 && high.abs() < 0.001)
+    }
+
+    pub fn clips(&self) -> bool {
+        self.buckets.iter().any(|(low, high)| -low >= CLIP_LEVEL || *high >= CLIP_LEVEL)
+    }
+}
+
+// Decodes the whole file through ffmpeg for a min/max overview in `points` buckets. Only for
+// display: the low scan rate smooths off the sharpest peaks.
+pub fn scan_waveform(path: &Path, points: usize) -> Result<Waveform> {
+    let mut child = Command::new("ffmpeg")
+        .args(["-v", "error", "-i"])
+        .arg(path)
+        .args(["-vn", "-ar", &SCAN_RATE.to_string(), "-f", "s16le", "-acodec", "pcm_s16le", "-"])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .context("Failed to run ffmpeg for the waveform")?;
+    let mut stdout = BufReader::new(child.stdout.take().context("No output from ffmpeg")?);
+
+    let mut blocks: Vec<(f32, f32)> = Vec::new();
+    let mut buffer = vec![0u8; BLOCK_SAMPLES * 2];
+    loop {
+        let read = read_full(&mut stdout, &mut buffer)?;
+        if read < 2 {
+            break;
+        }
+        let block = buffer[..read - read % 2]
+            .chunks_exact(2)
+            .map(|bytes| f32::from(i16::from_le_bytes([bytes[0], bytes[1]])) / 32768.0)
+            .fold((0.0f32, 0.0f32), |(low, high), sample| (low.min(sample), high.max(sample)));
+        blocks.push(block);
+    }
+    let status = child.wait().context("Failed to wait for ffmpeg")?;
+    if !status.success() {
+        return Err(anyhow::anyhow!("ffmpeg could not decode {:?}", path));
+    }
+
+    let points = points.clamp(1, blocks.len().max(1));
+    let buckets = (0..points)
+        .map(|point| {
+            let start = point * blocks.len() / points;
+            let end = ((point + 1) * blocks.len() / points).max(start + 1).min(blocks.len());
+            blocks[start.min(end)..end]
+                .iter()
+                .fold((0.0f32, 0.0f32), |(low, high), &(block_low, block_high)| (low.min(block_low), high.max(block_high)))
+        })
+        .collect();
+    Ok(Waveform { buckets })
+}
+
+// Fills the buffer unless the stream ends first; returns how much was read
+fn read_full(reader: &mut impl Read, buffer: &mut [u8]) -> Result<usize> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match reader.read(&mut buffer[filled..]) {
+            Ok(0) => break,
+            Ok(read) => filled += read,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e).context("Failed to read decoded audio"),
+        }
+    }
+    Ok(filled)
+}